@@ -1,5 +1,52 @@
-use crate::infrastructure::ble::Device;
+use humidity_core::{
+    plant::{Profile, Season},
+    power::Estimate,
+    settings::DeviceConfig,
+};
+
+use chrono::{DateTime, Local};
+
+use crate::infrastructure::ble::{Dated, Device, Heard};
+
+use super::usecase::{Command, Outcome};
 
 pub trait ListDevicesUI {
     fn render(&mut self, devices: &[Device]) -> Result<(), Box<dyn std::error::Error>>;
 }
+
+pub trait HistoryUI {
+    fn render(
+        &mut self,
+        records: &[Dated],
+        profile: Option<&Profile>,
+        season: Season,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+pub trait PowerUI {
+    fn render(
+        &mut self,
+        estimates: &[(&'static str, Estimate)],
+        capacity_mah: u32,
+        charge: Option<u8>,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+pub trait BroadcastsUI {
+    fn render(&mut self, heard: &[Heard]) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+pub trait SettingsUI {
+    fn render(
+        &mut self,
+        settings: &DeviceConfig,
+        status: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+pub trait CommandsUI {
+    fn render(
+        &mut self,
+        log: &[(DateTime<Local>, Command, Outcome)],
+    ) -> Result<(), Box<dyn std::error::Error>>;
+}
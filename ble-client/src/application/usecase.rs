@@ -1,7 +1,111 @@
-use crate::infrastructure::ble::BLE;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Datelike, Local};
+use humidity_core::{
+    lifecycle::HISTORY_LEN,
+    plant::{catalog, Profile, Season},
+    power::{self, Config, Model},
+    rpc::Diagnostics,
+    sample::Record,
+    sensors::{Hygrometer, Sensor},
+    settings::{Calibration, DeviceConfig, MAX_INTERVAL, MIN_INTERVAL},
+};
+
+use crate::infrastructure::{
+    ble::{Dated, Device, Heard, Rpc, BLE},
+    store::ProfileStore,
+};
 
 use super::ui;
 
+/// Broadcasts kept on screen.
+const BROADCAST_LOG_LEN: usize = 32;
+/// Outcomes of commands kept on screen.
+const COMMAND_LOG_LEN: usize = 16;
+/// Steps of the settings, for each key press.
+const INTERVAL_STEP: u32 = 60;
+const SAMPLES_STEP: u8 = 8;
+const CALIBRATION_STEP: u16 = 10;
+
+/// Settings editable from the client, in screen order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    Interval,
+    Samples,
+    Sensor,
+    CalibrationLow,
+    CalibrationHigh,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 5] = [
+        Setting::Interval,
+        Setting::Samples,
+        Setting::Sensor,
+        Setting::CalibrationLow,
+        Setting::CalibrationHigh,
+    ];
+}
+
+/// Commands sent to a connected device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Ping,
+    Diagnostics,
+    ReadHistory,
+    SetTime,
+    Sample,
+    ClearHistory,
+    Reboot,
+    ResetInterlock,
+}
+
+/// What came of a command.
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    /// The device clock, in seconds, and how long the device took to answer.
+    Pong(u32, Duration),
+    Diagnostics(Diagnostics),
+    /// Records read, and the latest one.
+    History(usize, Option<Record<Hygrometer>>),
+    Done,
+    Failed(String),
+}
+
+/// Settings of the firmware for each board, as found in their constants. The
+/// interval and samples are those of a device as built, and give way to the
+/// settings read from it.
+const BOARDS: [(&str, Model, Config); 2] = [
+    (
+        "ESP32-S3",
+        Model::Esp32S3,
+        Config {
+            interval_s: 15 * 60,
+            samples: u8::MAX,
+            sample_us: 100,
+            warmup_ms: 1000,
+            sensor_ua: 5_000,
+            advertise_every: 2,
+            advertise_ms: 5_000,
+            connected_ms: 2_000,
+        },
+    ),
+    (
+        "ESP32-C6",
+        Model::Esp32C6,
+        Config {
+            interval_s: 5 * 60,
+            samples: 64,
+            sample_us: 100,
+            warmup_ms: 1000,
+            sensor_ua: 5_000,
+            advertise_every: 6,
+            advertise_ms: 5_000,
+            connected_ms: 2_000,
+        },
+    ),
+];
+
 pub async fn list_devices<'data>(
     ble: &BLE,
     presenter: &mut impl ui::ListDevicesUI,
@@ -9,3 +113,198 @@ pub async fn list_devices<'data>(
     let devices = ble.get_devices().await;
     presenter.render(&devices)
 }
+
+/// Logs a broadcast, keeping the latest ones first.
+pub fn log_broadcast(
+    log: &mut Vec<Heard>,
+    heard: Heard,
+    presenter: &mut impl ui::BroadcastsUI,
+) -> Result<(), Box<dyn std::error::Error>> {
+    log.insert(0, heard);
+    log.truncate(BROADCAST_LOG_LEN);
+    presenter.render(log)
+}
+
+pub async fn show_history(
+    device: &Device,
+    store: &ProfileStore,
+    presenter: &mut impl ui::HistoryUI,
+) -> Result<Vec<Dated>, Box<dyn std::error::Error>> {
+    let records = device.read_history().await?;
+    render_history(device, &records, store, presenter)?;
+    Ok(records)
+}
+
+/// Adds a record notified by the device, dated as received, keeping as many
+/// as it does.
+pub fn add_record(
+    device: &Device,
+    records: &mut Vec<Dated>,
+    record: Record<Hygrometer>,
+    store: &ProfileStore,
+    presenter: &mut impl ui::HistoryUI,
+) -> Result<(), Box<dyn std::error::Error>> {
+    records.push(Dated { at: Some(Local::now()), record });
+    if records.len() > HISTORY_LEN {
+        records.remove(0);
+    }
+    render_history(device, records, store, presenter)
+}
+
+pub fn render_history(
+    device: &Device,
+    records: &[Dated],
+    store: &ProfileStore,
+    presenter: &mut impl ui::HistoryUI,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let season = Season::from_month(Local::now().month() as u8);
+    presenter.render(records, store.get(&device.id), season)
+}
+
+/// Assigns the next profile of the catalog to the device.
+pub fn cycle_profile(
+    device: &Device,
+    store: &mut ProfileStore,
+) -> Result<&'static Profile, Box<dyn std::error::Error>> {
+    let next = match store.get(&device.id) {
+        Some(current) => {
+            let idx = catalog::CATALOG.iter().position(|p| p == current).unwrap_or_default();
+            &catalog::CATALOG[(idx + 1) % catalog::CATALOG.len()]
+        }
+        None => &catalog::CATALOG[0],
+    };
+    store.assign(&device.id, next)?;
+    Ok(next)
+}
+
+/// Asks the device which chip it runs on, if it takes commands and tells.
+pub async fn read_model(device: &Device) -> Option<Model> {
+    let mut rpc = device.rpc().await.ok()?;
+    let diagnostics = rpc.diagnostics().await;
+    rpc.close().await.ok()?;
+    diagnostics.ok()?.model
+}
+
+/// Estimates the battery life of the board of the device, or of each one when
+/// it is not known, sampling as the settings of the device say, and how much
+/// is left given the latest record.
+pub fn estimate_power(
+    records: &[Dated],
+    settings: &DeviceConfig,
+    model: Option<Model>,
+    capacity_mah: u32,
+    presenter: &mut impl ui::PowerUI,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let estimates: Vec<_> = BOARDS
+        .iter()
+        .filter(|(_, board, _)| model.map_or(true, |model| model == *board))
+        .map(|(board, model, config)| {
+            let config =
+                Config { interval_s: settings.interval, samples: settings.samples, ..*config };
+            (*board, power::estimate(model.chip(), &config))
+        })
+        .collect();
+    let charge = records
+        .iter()
+        .rev()
+        .find_map(|dated| dated.record.battery)
+        .map(|battery| battery.percentage());
+    presenter.render(&estimates, capacity_mah, charge)
+}
+
+pub async fn show_settings(
+    device: &Device,
+    presenter: &mut impl ui::SettingsUI,
+) -> Result<DeviceConfig, Box<dyn std::error::Error>> {
+    let settings = device.read_settings().await?;
+    presenter.render(&settings, None)?;
+    Ok(settings)
+}
+
+/// Steps a setting up or down. Calibrating starts from the one of the sensor
+/// model, and switching the model drops the calibration, made for another one.
+pub fn adjust_setting(settings: &mut DeviceConfig, setting: Setting, up: bool) {
+    let step = |value: u16, step: u16| {
+        if up {
+            value.saturating_add(step)
+        } else {
+            value.saturating_sub(step)
+        }
+    };
+    let sensor = settings.sensor;
+    let calibration = || Calibration { low: sensor.low(), high: sensor.high() };
+    match setting {
+        Setting::Interval => {
+            let interval = if up {
+                settings.interval.saturating_add(INTERVAL_STEP)
+            } else {
+                settings.interval.saturating_sub(INTERVAL_STEP)
+            };
+            settings.interval = interval.clamp(MIN_INTERVAL, MAX_INTERVAL);
+        }
+        Setting::Samples => {
+            let samples = step(settings.samples as u16, SAMPLES_STEP as u16);
+            settings.samples = samples.clamp(1, u8::MAX as u16) as u8;
+        }
+        Setting::Sensor => {
+            settings.sensor = match settings.sensor {
+                Hygrometer::YL69 => Hygrometer::HW390,
+                Hygrometer::HW390 => Hygrometer::YL69,
+            };
+            settings.calibration = None;
+        }
+        Setting::CalibrationLow => {
+            let calibration = settings.calibration.get_or_insert_with(calibration);
+            calibration.low = step(calibration.low, CALIBRATION_STEP);
+        }
+        Setting::CalibrationHigh => {
+            let calibration = settings.calibration.get_or_insert_with(calibration);
+            calibration.high = step(calibration.high, CALIBRATION_STEP);
+        }
+    }
+}
+
+/// Writes the settings, once checked the device would accept them.
+pub async fn save_settings(
+    device: &Device,
+    settings: &DeviceConfig,
+    presenter: &mut impl ui::SettingsUI,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let status = match settings.validate() {
+        Ok(()) => match device.write_settings(settings).await {
+            Ok(()) => "saved, applied from the next wake".to_owned(),
+            Err(err) => format!("cannot save: {err}"),
+        },
+        Err(err) => format!("invalid settings: {err:?}"),
+    };
+    presenter.render(settings, Some(&status))
+}
+
+/// Sends a command, and logs its outcome, keeping the latest ones first.
+pub async fn send_command(
+    rpc: &mut Rpc,
+    command: Command,
+    log: &mut Vec<(DateTime<Local>, Command, Outcome)>,
+    presenter: &mut impl ui::CommandsUI,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let sent = Instant::now();
+    let outcome = match command {
+        Command::Ping => rpc.ping().await.map(|now| Outcome::Pong(now, sent.elapsed())),
+        Command::Diagnostics => rpc.diagnostics().await.map(Outcome::Diagnostics),
+        Command::ReadHistory => rpc
+            .history_range(0, HISTORY_LEN as u16)
+            .await
+            .map(|records| Outcome::History(records.len(), records.last().copied())),
+        Command::SetTime => {
+            rpc.set_time(Local::now().timestamp() as u32).await.map(|_| Outcome::Done)
+        }
+        Command::Sample => rpc.trigger_sample().await.map(|_| Outcome::Done),
+        Command::ClearHistory => rpc.clear_history().await.map(|_| Outcome::Done),
+        Command::Reboot => rpc.reboot().await.map(|_| Outcome::Done),
+        Command::ResetInterlock => rpc.reset_interlock().await.map(|_| Outcome::Done),
+    };
+    let outcome = outcome.unwrap_or_else(|err| Outcome::Failed(err.to_string()));
+    log.insert(0, (Local::now(), command, outcome));
+    log.truncate(COMMAND_LOG_LEN);
+    presenter.render(log)
+}
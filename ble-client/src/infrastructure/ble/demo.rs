@@ -0,0 +1,54 @@
+//! Made up history of a device watering a bonsai, for trying the client out
+//! without any hardware.
+
+use chrono::{DateTime, Duration, Local, Timelike};
+use humidity_core::{
+    battery::{Battery, Chemistry},
+    irrigation::{Command, Irrigator, Profile},
+    lifecycle::HISTORY_LEN,
+    sample::Record,
+    sensors::Hygrometer,
+    settings::DeviceConfig,
+    simulation::{Model, Probe, Soil, Weather},
+};
+
+use super::Dated;
+
+/// Sampling interval of the ESP32-S3 firmware.
+const INTERVAL: u32 = 15 * 60;
+const SAMPLES: u8 = u8::MAX;
+const PROFILE: Profile =
+    Profile { dry: 35, target: 60, hysteresis: 5, min_interval: 30 * 60, pulse_ms: 3000 };
+const FLOW_ML_PER_MIN: u32 = 100;
+pub const SETTINGS: DeviceConfig = DeviceConfig {
+    interval: INTERVAL,
+    samples: SAMPLES,
+    sensor: Hygrometer::HW390,
+    calibration: None,
+};
+
+/// Returns the records a device would hold now, after running for a few days.
+pub fn history(seed: u32, at: DateTime<Local>) -> Vec<Dated> {
+    // Aligns the simulated days with the local ones.
+    let now = at.num_seconds_from_midnight();
+    let wakes = 4 * 24 * 60 * 60 / INTERVAL;
+    let start = now + 24 * 60 * 60 - (wakes - 1) * INTERVAL % (24 * 60 * 60);
+
+    let mut model = Model::new(Soil::BONSAI, Weather::INDOORS, 45 + (seed % 20) as u8);
+    let mut probe = Probe::new(Hygrometer::HW390, seed);
+    let mut irrigator = Irrigator::new();
+    let mut records = vec![];
+    for wake in 0..wakes {
+        let now = start + wake * INTERVAL;
+        let summary = probe.sample(SAMPLES, model.moisture(now), &model.weather.at(now), now);
+        if let Command::Water(pulse_ms) = irrigator.decide(&PROFILE, &summary, now).command {
+            model.water(now, FLOW_ML_PER_MIN * pulse_ms / 60_000);
+            irrigator.watered(now);
+        }
+        let battery = Battery { millivolts: 4100 - (wake / 8) as u16, chemistry: Chemistry::LiIon };
+        let record = Record { summary, battery: Some(battery), fault: None };
+        let ago = Duration::seconds(((wakes - 1 - wake) * INTERVAL) as i64);
+        records.push(Dated { at: Some(at - ago), record });
+    }
+    records.split_off(records.len().saturating_sub(HISTORY_LEN))
+}
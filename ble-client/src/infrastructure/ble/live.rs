@@ -0,0 +1,49 @@
+//! Records a connected device notifies as it samples, while the client stays.
+
+use std::{error::Error, pin::Pin};
+
+use btleplug::{
+    api::{Characteristic, Peripheral as _, ValueNotification},
+    platform::Peripheral,
+};
+use futures::{Stream, StreamExt};
+use humidity_core::{sample::Record, sensors::Hygrometer, serde, shared::protocol};
+
+type Notifications = Pin<Box<dyn Stream<Item = ValueNotification> + Send>>;
+
+/// A connection to a device following its new records, until closed.
+pub struct Live {
+    peripheral: Peripheral,
+    notifications: Notifications,
+}
+
+impl Live {
+    /// Subscribes to the history of a connected device.
+    pub(super) async fn ble(
+        peripheral: Peripheral,
+        historical: Characteristic,
+    ) -> Result<Self, Box<dyn Error>> {
+        peripheral.subscribe(&historical).await?;
+        let notifications = peripheral.notifications().await?;
+        Ok(Self { peripheral, notifications })
+    }
+
+    /// Waits for the next record, or returns nothing once disconnected.
+    pub async fn next(&mut self) -> Option<Record<Hygrometer>> {
+        let historical = super::uuid(protocol::HISTORICAL);
+        while let Some(notification) = self.notifications.next().await {
+            if notification.uuid != historical {
+                continue;
+            }
+            // Records of another layout are skipped, the next one may do.
+            if let Ok(record) = serde::deserialize(&notification.value) {
+                return Some(record);
+            }
+        }
+        None
+    }
+
+    pub async fn close(self) -> Result<(), Box<dyn Error>> {
+        Ok(self.peripheral.disconnect().await?)
+    }
+}
@@ -1,10 +1,45 @@
-use std::error::Error;
+use std::{
+    env,
+    error::Error,
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use btleplug::{
-    api::{self, Central, Manager as _, Peripheral, ScanFilter},
+    api::{
+        self, bleuuid, Central, CentralEvent, Characteristic, Manager as _, Peripheral, ScanFilter,
+        WriteType,
+    },
     platform::{self, Adapter, Manager},
 };
-use futures::future;
+use chrono::{DateTime, Local, TimeZone, Utc};
+use futures::{future, Future, Stream, StreamExt};
+use humidity_core::{
+    bthome::{self, Measurement},
+    sample::Record,
+    sensors::Hygrometer,
+    serde,
+    settings::{DeviceConfig, DEVICE_CONFIG_LEN},
+    shared::{self, broadcast, protocol, Broadcast},
+    time::cts,
+};
+use humidity_sim::protocol::Client;
+use uuid::Uuid;
+
+/// Comma separated addresses of simulated devices, listed along the real ones.
+const SIM_ENV: &str = "HUMIDITY_SIM";
+/// Lists a demo device along the real ones when set.
+const DEMO_ENV: &str = "HUMIDITY_DEMO";
+/// Bind key of devices encrypting their BTHome advertisements, as 32
+/// hexadecimal digits.
+const BTHOME_KEY_ENV: &str = "BTHOME_BIND_KEY";
+
+pub use live::Live;
+pub use rpc::Rpc;
+
+mod demo;
+mod live;
+mod rpc;
 
 pub struct BLE {
     central: Adapter,
@@ -15,7 +50,39 @@ pub struct Device {
     pub id: String,
     pub name: String,
 
-    peripheral: platform::Peripheral,
+    link: Link,
+}
+
+/// A broadcast received from a device, without connecting to it.
+#[derive(Clone)]
+pub struct Heard {
+    pub id: String,
+    pub at: DateTime<Local>,
+    pub payload: Payload,
+}
+
+/// A record of the history, and when it was stored, once the device knows the
+/// time.
+#[derive(Clone, Copy)]
+pub struct Dated {
+    pub at: Option<DateTime<Local>>,
+    pub record: Record<Hygrometer>,
+}
+
+/// Readings of an advertisement, in either format the firmware sends.
+#[derive(Clone)]
+pub enum Payload {
+    Custom(Broadcast),
+    BTHome(Measurement),
+}
+
+#[derive(Clone)]
+enum Link {
+    Ble(platform::Peripheral),
+    /// A simulator listening on the address.
+    Sim(String),
+    /// Made up data, different on every read.
+    Demo,
 }
 
 impl Device {
@@ -29,12 +96,293 @@ impl Device {
             .flatten()
             .unwrap_or_default();
 
-        Device { id, name, peripheral: peripheral.clone() }
+        Device { id, name, link: Link::Ble(peripheral.clone()) }
+    }
+
+    fn simulated(addr: &str) -> Self {
+        Device {
+            id: format!("sim:{addr}"),
+            name: shared::BLE_DEVICE_NAME.to_owned(),
+            link: Link::Sim(addr.to_owned()),
+        }
+    }
+
+    fn demo() -> Self {
+        Device { id: "demo".to_owned(), name: shared::BLE_DEVICE_NAME.to_owned(), link: Link::Demo }
     }
 
     pub fn is_named(&self) -> bool {
         !self.name.is_empty()
     }
+
+    pub async fn read_history(&self) -> Result<Vec<Dated>, Box<dyn Error>> {
+        match &self.link {
+            Link::Ble(peripheral) => {
+                Self::connected(peripheral, Self::read_ble_history(peripheral)).await
+            }
+            Link::Sim(addr) => {
+                let addr = addr.clone();
+                tokio::task::spawn_blocking(move || Self::read_sim_history(&addr))
+                    .await?
+                    .map_err(|err| err as Box<dyn Error>)
+            }
+            Link::Demo => {
+                let seed = SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos();
+                Ok(demo::history(seed, Local::now()))
+            }
+        }
+    }
+
+    /// Reads the settings of the device.
+    pub async fn read_settings(&self) -> Result<DeviceConfig, Box<dyn Error>> {
+        let data = match &self.link {
+            Link::Ble(peripheral) => {
+                Self::connected(peripheral, async {
+                    let characteristic =
+                        Self::find_ble(peripheral, protocol::DEVICE_CONFIG).await?;
+                    Ok(peripheral.read(&characteristic).await?)
+                })
+                .await?
+            }
+            Link::Sim(addr) => {
+                let addr = addr.clone();
+                tokio::task::spawn_blocking(move || Self::read_sim_settings(&addr))
+                    .await?
+                    .map_err(|err| err as Box<dyn Error>)?
+            }
+            Link::Demo => return Ok(demo::SETTINGS),
+        };
+        Ok(DeviceConfig::decode(&data).map_err(|err| format!("{err:?}"))?)
+    }
+
+    /// Writes the settings of the device, applied from its next wake.
+    pub async fn write_settings(&self, settings: &DeviceConfig) -> Result<(), Box<dyn Error>> {
+        let mut data = [0u8; DEVICE_CONFIG_LEN];
+        let n = settings.encode(&mut data).map_err(|err| format!("{err:?}"))?;
+        let data = data[..n].to_vec();
+        match &self.link {
+            Link::Ble(peripheral) => {
+                Self::connected(peripheral, async {
+                    let characteristic =
+                        Self::find_ble(peripheral, protocol::DEVICE_CONFIG).await?;
+                    Ok(peripheral.write(&characteristic, &data, WriteType::WithResponse).await?)
+                })
+                .await
+            }
+            Link::Sim(addr) => {
+                let addr = addr.clone();
+                tokio::task::spawn_blocking(move || Self::write_sim_settings(&addr, &data))
+                    .await?
+                    .map_err(|err| err as Box<dyn Error>)
+            }
+            Link::Demo => Err("the demo device cannot be configured".into()),
+        }
+    }
+
+    /// Connects to the device to send it commands.
+    pub async fn rpc(&self) -> Result<Rpc, Box<dyn Error>> {
+        match &self.link {
+            Link::Ble(peripheral) => {
+                Self::disconnect_on_error(peripheral, async {
+                    let version = Self::find_ble(peripheral, protocol::PROTOCOL_VERSION).await?;
+                    Self::check_version(&peripheral.read(&version).await?)?;
+                    let request = Self::lookup(peripheral, protocol::RPC_REQUEST)?;
+                    let response = Self::lookup(peripheral, protocol::RPC_RESPONSE)?;
+                    Rpc::ble(peripheral.clone(), request, response).await
+                })
+                .await
+            }
+            Link::Sim(addr) => {
+                let addr = addr.clone();
+                let (client, version) = tokio::task::spawn_blocking(move || {
+                    let mut client = Self::connect_sim(addr)?;
+                    let version = client.read(protocol::PROTOCOL_VERSION.uuid)?;
+                    std::io::Result::Ok((client, version))
+                })
+                .await??;
+                if let Err(err) = Self::check_version(&version) {
+                    client.disconnect()?;
+                    return Err(err);
+                }
+                Ok(Rpc::sim(client))
+            }
+            Link::Demo => Err("the demo device takes no commands".into()),
+        }
+    }
+
+    /// Connects to the device to follow the records it stores while the
+    /// client stays connected.
+    pub async fn live(&self) -> Result<Live, Box<dyn Error>> {
+        match &self.link {
+            Link::Ble(peripheral) => {
+                Self::disconnect_on_error(peripheral, async {
+                    let historical = Self::find_ble(peripheral, protocol::HISTORICAL).await?;
+                    Live::ble(peripheral.clone(), historical).await
+                })
+                .await
+            }
+            Link::Sim(_) => Err("the simulator sends no notifications".into()),
+            Link::Demo => Err("the demo device sends no notifications".into()),
+        }
+    }
+
+    /// Rejects devices speaking another version of the protocol.
+    fn check_version(version: &[u8]) -> Result<(), Box<dyn Error>> {
+        match version {
+            [protocol::VERSION] => Ok(()),
+            [version] => {
+                Err(format!("device protocol version {version}, expected {}", protocol::VERSION)
+                    .into())
+            }
+            _ => Err(format!("invalid protocol version {version:?}").into()),
+        }
+    }
+
+    /// Connects to the device and finds one of its characteristics, setting
+    /// the time of devices with the Current Time Service on the way.
+    async fn find_ble(
+        peripheral: &platform::Peripheral,
+        characteristic: protocol::Characteristic,
+    ) -> Result<Characteristic, Box<dyn Error>> {
+        peripheral.connect().await?;
+        peripheral.discover_services().await?;
+        if let Ok(current_time) = Self::lookup(peripheral, protocol::CURRENT_TIME) {
+            peripheral.write(&current_time, &current_time_now(), WriteType::WithResponse).await?;
+        }
+        Self::lookup(peripheral, characteristic)
+    }
+
+    /// Runs work connecting to the device, then disconnects however it ended,
+    /// so that the device is not kept busy past its advertising window.
+    async fn connected<T>(
+        peripheral: &platform::Peripheral,
+        work: impl Future<Output = Result<T, Box<dyn Error>>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let result = work.await;
+        let disconnected = peripheral.disconnect().await;
+        // A failure of the work tells more than one disconnecting after it.
+        let value = result?;
+        disconnected?;
+        Ok(value)
+    }
+
+    /// Runs work connecting to the device and leaving it connected, unless it
+    /// fails.
+    async fn disconnect_on_error<T>(
+        peripheral: &platform::Peripheral,
+        work: impl Future<Output = Result<T, Box<dyn Error>>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let result = work.await;
+        if result.is_err() {
+            let _ = peripheral.disconnect().await;
+        }
+        result
+    }
+
+    /// Connects to a simulator and sets its time, as done with devices.
+    fn connect_sim(addr: impl std::net::ToSocketAddrs) -> io::Result<Client> {
+        let mut client = Client::connect(addr)?;
+        client.write(protocol::CURRENT_TIME.uuid, &current_time_now())?;
+        Ok(client)
+    }
+
+    /// Finds one of the characteristics discovered on a connected device.
+    fn lookup(
+        peripheral: &platform::Peripheral,
+        characteristic: protocol::Characteristic,
+    ) -> Result<Characteristic, Box<dyn Error>> {
+        let uuid = uuid(characteristic);
+        let characteristic = peripheral.characteristics().into_iter().find(|c| c.uuid == uuid);
+        Ok(characteristic.ok_or(format!("characteristic {uuid} not found"))?)
+    }
+
+    /// Reads the history, leaving the device connected.
+    async fn read_ble_history(
+        peripheral: &platform::Peripheral,
+    ) -> Result<Vec<Dated>, Box<dyn Error>> {
+        let historical = Self::find_ble(peripheral, protocol::HISTORICAL).await?;
+
+        let mut records = vec![];
+        loop {
+            let data = peripheral.read(&historical).await?;
+            if data.is_empty() {
+                break;
+            }
+            records.push(serde::deserialize(&data).map_err(|err| format!("{err:?}"))?);
+        }
+        // Devices predating time synchronization leave the records undated.
+        let mut times = vec![];
+        if let Ok(history_times) = Self::lookup(peripheral, protocol::HISTORY_TIMES) {
+            loop {
+                let data = peripheral.read(&history_times).await?;
+                if data.is_empty() {
+                    break;
+                }
+                times.extend(data);
+            }
+        }
+
+        Ok(date(records, &times))
+    }
+
+    /// Blocks until the simulator serves the connection, on its next
+    /// advertising window.
+    fn read_sim_history(addr: &str) -> Result<Vec<Dated>, Box<dyn Error + Send + Sync>> {
+        let mut client = Self::connect_sim(addr)?;
+        let mut records = vec![];
+        loop {
+            let data = client.read(protocol::HISTORICAL.uuid)?;
+            if data.is_empty() {
+                break;
+            }
+            records.push(serde::deserialize(&data).map_err(|err| format!("{err:?}"))?);
+        }
+        let mut times = vec![];
+        loop {
+            let data = client.read(protocol::HISTORY_TIMES.uuid)?;
+            if data.is_empty() {
+                break;
+            }
+            times.extend(data);
+        }
+
+        client.disconnect()?;
+        Ok(date(records, &times))
+    }
+
+    fn read_sim_settings(addr: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let mut client = Self::connect_sim(addr)?;
+        let data = client.read(protocol::DEVICE_CONFIG.uuid)?;
+        client.disconnect()?;
+        Ok(data)
+    }
+
+    fn write_sim_settings(addr: &str, data: &[u8]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut client = Self::connect_sim(addr)?;
+        client.write(protocol::DEVICE_CONFIG.uuid, data)?;
+        client.disconnect()?;
+        Ok(())
+    }
+}
+
+/// The time to write to the Current Time Service.
+fn current_time_now() -> [u8; cts::CURRENT_TIME_LEN] {
+    cts::encode(Utc::now().timestamp() as u32)
+}
+
+/// Pairs records with the times of the history, 4 bytes each and 0 when
+/// unknown, in the same order.
+fn date(records: Vec<Record<Hygrometer>>, times: &[u8]) -> Vec<Dated> {
+    let mut times = times.chunks_exact(4).map(|time| {
+        let unix = u32::from_le_bytes([time[0], time[1], time[2], time[3]]);
+        (unix > 0).then(|| Local.timestamp_opt(unix as i64, 0).single()).flatten()
+    });
+    records.into_iter().map(|record| Dated { at: times.next().flatten(), record }).collect()
+}
+
+/// The UUID of a characteristic of the device, as btleplug has it.
+fn uuid(characteristic: protocol::Characteristic) -> Uuid {
+    Uuid::from_u128(characteristic.uuid.as_u128())
 }
 
 impl BLE {
@@ -53,6 +401,47 @@ impl BLE {
     pub async fn get_devices(&self) -> Vec<Device> {
         let peripherals = self.central.peripherals().await.unwrap();
         let devices_futures = peripherals.iter().map(Device::new);
-        future::join_all(devices_futures).await
+        let mut devices = future::join_all(devices_futures).await;
+        if let Ok(addrs) = env::var(SIM_ENV) {
+            devices.extend(addrs.split(',').filter(|addr| !addr.is_empty()).map(Device::simulated));
+        }
+        if env::var_os(DEMO_ENV).is_some() {
+            devices.push(Device::demo());
+        }
+        devices
+    }
+
+    /// Listens to the broadcasts of the devices in range, as they advertise.
+    /// Encrypted BTHome ones are skipped unless the bind key is set.
+    pub async fn broadcasts(&self) -> Result<impl Stream<Item = Heard> + Unpin, Box<dyn Error>> {
+        let key = match env::var(BTHOME_KEY_ENV) {
+            Ok(hex) => Some(bthome::parse_key(&hex).ok_or("invalid BTHome bind key")?),
+            Err(_) => None,
+        };
+        let bthome_uuid = bleuuid::uuid_from_u16(bthome::SERVICE_UUID);
+        let central = self.central.clone();
+        let events = self.central.events().await?;
+        Ok(Box::pin(events.filter_map(move |event| {
+            let central = central.clone();
+            async move {
+                let (id, payload) = match event {
+                    CentralEvent::ManufacturerDataAdvertisement { id, manufacturer_data } => {
+                        let payload = manufacturer_data.get(&broadcast::COMPANY_ID)?;
+                        (id, Payload::Custom(Broadcast::decode(payload).ok()?))
+                    }
+                    CentralEvent::ServiceDataAdvertisement { id, service_data } => {
+                        let data = service_data.get(&bthome_uuid)?;
+                        // The address is part of the nonce of encrypted payloads.
+                        let properties = central.peripheral(&id).await.ok()?.properties().await;
+                        let address = properties.ok()??.address.into_inner();
+                        let measurement =
+                            bthome::decode(data, key.as_ref().map(|key| (key, &address))).ok()?;
+                        (id, Payload::BTHome(measurement))
+                    }
+                    _ => return None,
+                };
+                Some(Heard { id: id.to_string(), at: Local::now(), payload })
+            }
+        })))
     }
 }
@@ -0,0 +1,213 @@
+//! Typed client of the RPC characteristics: requests are written to one, and
+//! responses notified on the other, matched by request id.
+
+use std::{error::Error, pin::Pin, time::Duration};
+
+use btleplug::{
+    api::{Characteristic, Peripheral as _, ValueNotification, WriteType},
+    platform::Peripheral,
+};
+use futures::{Stream, StreamExt};
+use humidity_core::{
+    rpc::{Code, Diagnostics, Request, Response, FRAME_LEN},
+    sample::Record,
+    sensors::Hygrometer,
+    shared::protocol,
+};
+use humidity_sim::protocol::Client;
+/// How long to wait for each response.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Notifications = Pin<Box<dyn Stream<Item = ValueNotification> + Send>>;
+
+/// A connection to a device taking commands, until closed.
+pub struct Rpc {
+    session: Session,
+    next_id: u8,
+}
+
+enum Session {
+    Ble {
+        peripheral: Peripheral,
+        request: Characteristic,
+        responses: Notifications,
+    },
+    /// Taken out while a blocking call runs.
+    Sim(Option<Client>),
+}
+
+impl Rpc {
+    /// Subscribes to the responses of a connected device.
+    pub(super) async fn ble(
+        peripheral: Peripheral,
+        request: Characteristic,
+        response: Characteristic,
+    ) -> Result<Self, Box<dyn Error>> {
+        peripheral.subscribe(&response).await?;
+        let responses = peripheral.notifications().await?;
+        Ok(Self { session: Session::Ble { peripheral, request, responses }, next_id: 0 })
+    }
+
+    pub(super) fn sim(client: Client) -> Self {
+        Self { session: Session::Sim(Some(client)), next_id: 0 }
+    }
+
+    /// Returns the device clock, in seconds.
+    pub async fn ping(&mut self) -> Result<u32, Box<dyn Error>> {
+        match self.call(Request::Ping).await?.as_slice() {
+            [Response::Pong(now)] => Ok(*now),
+            responses => Err(format!("unexpected responses {responses:?}").into()),
+        }
+    }
+
+    /// Reads up to `count` records of the history, from the `start`th oldest.
+    pub async fn history_range(
+        &mut self,
+        start: u16,
+        count: u16,
+    ) -> Result<Vec<Record<Hygrometer>>, Box<dyn Error>> {
+        let responses = self.call(Request::GetHistoryRange { start, count }).await?;
+        Ok(responses
+            .into_iter()
+            .filter_map(|response| match response {
+                Response::Record(record) => Some(record),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Drops the history, once disconnected.
+    pub async fn clear_history(&mut self) -> Result<(), Box<dyn Error>> {
+        self.command(Request::ClearHistory).await
+    }
+
+    /// Sets the time, in seconds since the Unix epoch.
+    pub async fn set_time(&mut self, unix: u32) -> Result<(), Box<dyn Error>> {
+        self.command(Request::SetTime(unix)).await
+    }
+
+    /// Samples the soil, once disconnected.
+    pub async fn trigger_sample(&mut self) -> Result<(), Box<dyn Error>> {
+        self.command(Request::TriggerSample).await
+    }
+
+    /// Restarts the device, once disconnected.
+    pub async fn reboot(&mut self) -> Result<(), Box<dyn Error>> {
+        self.command(Request::Reboot).await
+    }
+
+    /// Lets the pump run again after a halt, once disconnected.
+    pub async fn reset_interlock(&mut self) -> Result<(), Box<dyn Error>> {
+        self.command(Request::ResetInterlock).await
+    }
+
+    pub async fn diagnostics(&mut self) -> Result<Diagnostics, Box<dyn Error>> {
+        match self.call(Request::GetDiagnostics).await?.as_slice() {
+            [Response::Diagnostics(diagnostics)] => Ok(*diagnostics),
+            responses => Err(format!("unexpected responses {responses:?}").into()),
+        }
+    }
+
+    /// Disconnects, letting the device carry out the commands.
+    pub async fn close(self) -> Result<(), Box<dyn Error>> {
+        match self.session {
+            Session::Ble { peripheral, .. } => Ok(peripheral.disconnect().await?),
+            Session::Sim(client) => match client {
+                Some(client) => Ok(client.disconnect()?),
+                None => Ok(()),
+            },
+        }
+    }
+
+    async fn command(&mut self, request: Request) -> Result<(), Box<dyn Error>> {
+        match self.call(request).await?.as_slice() {
+            [Response::Done] => Ok(()),
+            responses => Err(format!("unexpected responses {responses:?}").into()),
+        }
+    }
+
+    /// Sends a request, and collects its responses until the last one.
+    async fn call(
+        &mut self,
+        request: Request,
+    ) -> Result<Vec<Response<Hygrometer>>, Box<dyn Error>> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let mut frame = [0u8; FRAME_LEN];
+        let n = request.encode(id, &mut frame).map_err(|err| format!("{err:?}"))?;
+        self.send(&frame[..n]).await?;
+
+        let mut responses = vec![];
+        loop {
+            let frame = self.receive().await?;
+            // Left over from an earlier request.
+            if frame.first() != Some(&id) {
+                continue;
+            }
+            let (_, response) =
+                Response::decode(request.opcode(), &frame).map_err(|err| format!("{err:?}"))?;
+            match response.code() {
+                Code::More => responses.push(response),
+                Code::Done => {
+                    responses.push(response);
+                    return Ok(responses);
+                }
+                code => return Err(format!("{:?} failed: {code:?}", request.opcode()).into()),
+            }
+        }
+    }
+
+    async fn send(&mut self, frame: &[u8]) -> Result<(), Box<dyn Error>> {
+        match &mut self.session {
+            Session::Ble { peripheral, request, .. } => {
+                Ok(peripheral.write(request, frame, WriteType::WithResponse).await?)
+            }
+            Session::Sim(client) => {
+                let frame = frame.to_vec();
+                Self::blocking(client, move |client| {
+                    client.write(protocol::RPC_REQUEST.uuid, &frame)
+                })
+                .await
+            }
+        }
+    }
+
+    async fn receive(&mut self) -> Result<Vec<u8>, Box<dyn Error>> {
+        match &mut self.session {
+            Session::Ble { responses, .. } => loop {
+                let notification = tokio::time::timeout(RESPONSE_TIMEOUT, responses.next())
+                    .await?
+                    .ok_or("disconnected")?;
+                if notification.uuid == super::uuid(protocol::RPC_RESPONSE) {
+                    return Ok(notification.value);
+                }
+            },
+            // The simulator answers before acknowledging the request.
+            Session::Sim(client) => {
+                let frame =
+                    Self::blocking(client, |client| client.read(protocol::RPC_RESPONSE.uuid))
+                        .await?;
+                if frame.is_empty() {
+                    return Err("no response".into());
+                }
+                Ok(frame)
+            }
+        }
+    }
+
+    /// Runs a call of the simulator client, which blocks until the simulator
+    /// serves the connection.
+    async fn blocking<T: Send + 'static>(
+        client: &mut Option<Client>,
+        call: impl FnOnce(&mut Client) -> std::io::Result<T> + Send + 'static,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut taken = client.take().ok_or("disconnected")?;
+        let (taken, result) = tokio::task::spawn_blocking(move || {
+            let result = call(&mut taken);
+            (taken, result)
+        })
+        .await?;
+        *client = Some(taken);
+        Ok(result?)
+    }
+}
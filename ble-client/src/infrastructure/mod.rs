@@ -1,2 +1,3 @@
 pub mod ble;
+pub mod store;
 pub mod term;
@@ -0,0 +1,57 @@
+use std::{collections::BTreeMap, env, fs, io, path::PathBuf};
+
+use humidity_core::plant::{catalog, Profile};
+
+/// Remembers which plant profile is assigned to each monitor, persisted as
+/// `device-id=profile-name` lines.
+pub struct ProfileStore {
+    path: PathBuf,
+    assignments: BTreeMap<String, String>,
+}
+
+impl ProfileStore {
+    /// Opens the store in the configuration directory of the platform, or in
+    /// the working directory when it cannot be told.
+    pub fn open_default() -> Self {
+        Self::open(config_dir().map(|dir| dir.join("humidity")).unwrap_or_default().join(FILE))
+    }
+
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let assignments = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(id, name)| (id.to_owned(), name.to_owned()))
+            .collect();
+        Self { path, assignments }
+    }
+
+    pub fn get(&self, device_id: &str) -> Option<&'static Profile> {
+        self.assignments.get(device_id).and_then(|name| catalog::find(name))
+    }
+
+    pub fn assign(&mut self, device_id: &str, profile: &Profile) -> io::Result<()> {
+        self.assignments.insert(device_id.to_owned(), profile.name().to_owned());
+        let contents: String =
+            self.assignments.iter().map(|(id, name)| format!("{id}={name}\n")).collect();
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, contents)
+    }
+}
+
+const FILE: &str = "profiles.txt";
+
+/// Returns where the platform keeps the configuration of users.
+fn config_dir() -> Option<PathBuf> {
+    let var = |name| env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|home| home.join(".config")))
+    }
+}
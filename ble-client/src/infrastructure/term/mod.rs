@@ -1,4 +1,4 @@
-use std::io::stdout;
+use std::{io::stdout, time::Duration};
 
 use crossterm::{
     cursor::{MoveTo, MoveToNextLine},
@@ -8,12 +8,28 @@ use crossterm::{
     ExecutableCommand,
 };
 
-use crate::{application::usecase, infrastructure::ble::BLE};
+use futures::StreamExt;
+use humidity_core::{lifecycle::LIVE_LEN, settings::DeviceConfig};
 
-use super::ble::Device;
+use crate::{
+    application::{
+        ui::SettingsUI,
+        usecase::{self, Command},
+    },
+    infrastructure::{ble::BLE, store::ProfileStore},
+};
+
+use super::ble::{Dated, Device};
 
 mod widgets;
 
+const BATTERY_CAPACITY_MAH: u32 = 2000;
+const BATTERY_CAPACITY_STEP_MAH: u32 = 100;
+/// How long to wait for broadcasts before checking the keyboard again.
+const BROADCAST_POLL: Duration = Duration::from_millis(100);
+/// How long to wait for notified records before checking the keyboard again.
+const LIVE_POLL: Duration = Duration::from_millis(100);
+
 fn draw_actions(lines: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
     stdout().execute(MoveTo(0, 0))?.execute(Clear(ClearType::All))?;
     for line in lines {
@@ -33,6 +49,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
         draw_actions(&[
             "BLE Toolkit",
             "Press 's' to scan devices",
+            "Press 'b' to listen to broadcasts",
             "Press 'c' to connect to a device",
             "Press 'ESC' to exit",
         ])?;
@@ -42,6 +59,9 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 KeyCode::Char('s') | KeyCode::Char('S') => {
                     cmd_scan_devices(&ble).await?;
                 }
+                KeyCode::Char('b') | KeyCode::Char('B') => {
+                    cmd_listen_broadcasts(&ble).await?;
+                }
                 KeyCode::Esc => {
                     stdout().execute(Clear(ClearType::All))?;
                     break;
@@ -57,6 +77,42 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+async fn cmd_listen_broadcasts(ble: &BLE) -> Result<(), Box<dyn std::error::Error>> {
+    draw_actions(&[
+        "Broadcast mode, readings are logged as devices advertise:",
+        "Press 'c' to clear the log",
+        "Press 'ESC' to go back",
+        "      time     device               moisture battery flags",
+    ])?;
+
+    let mut broadcasts = ble.broadcasts().await?;
+    let mut log = vec![];
+    let mut view = widgets::ListView::new("Broadcasts".to_owned());
+
+    loop {
+        if event::poll(Duration::ZERO)? {
+            if let Event::Key(key_event) = event::read()? {
+                match key_event.code {
+                    KeyCode::Char('c') => {
+                        log.clear();
+                        stdout()
+                            .execute(MoveTo(0, 5))?
+                            .execute(Clear(ClearType::FromCursorDown))?;
+                    }
+                    KeyCode::Esc => break,
+                    _ => {}
+                }
+            }
+        }
+        if let Ok(Some(heard)) = tokio::time::timeout(BROADCAST_POLL, broadcasts.next()).await {
+            stdout().execute(MoveTo(0, 5))?.execute(Clear(ClearType::FromCursorDown))?;
+            usecase::log_broadcast(&mut log, heard, &mut view)?;
+        }
+    }
+
+    Ok(())
+}
+
 async fn cmd_scan_devices(ble: &BLE) -> Result<(), Box<dyn std::error::Error>> {
     draw_actions(&[
         "Scan mode:",
@@ -121,6 +177,12 @@ async fn cmd_scan_devices(ble: &BLE) -> Result<(), Box<dyn std::error::Error>> {
                         _ => {}
                     }
                 },
+                KeyCode::Char('c') => {
+                    if let Some(device) = view.selected_item() {
+                        cmd_show_history(device).await?;
+                        return Ok(());
+                    }
+                }
                 KeyCode::Up => {
                     stdout().execute(MoveTo(0, 7))?.execute(Clear(ClearType::FromCursorDown))?;
                     view.select_prev_item();
@@ -142,3 +204,251 @@ async fn cmd_scan_devices(ble: &BLE) -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+fn draw_history_actions() -> Result<(), Box<dyn std::error::Error>> {
+    draw_actions(&[
+        "History mode:",
+        "Press 'r' to re-read the history",
+        "Press 'p' to assign the next plant profile",
+        "Press 'e' to estimate the battery life",
+        "Press 's' to edit the settings",
+        "Press 'c' to send commands",
+        "Press 'l' to follow new records live",
+        "Press 'ESC' to go back",
+        "    date          avg   min   max  moisture  battery     fault",
+    ])
+}
+
+async fn cmd_show_history(device: &Device) -> Result<(), Box<dyn std::error::Error>> {
+    draw_history_actions()?;
+
+    let mut store = ProfileStore::open_default();
+    let mut view = widgets::ListView::new(String::new());
+    let mut records = usecase::show_history(device, &store, &mut view).await?;
+
+    loop {
+        match event::read()? {
+            Event::Key(key_event) => match key_event.code {
+                KeyCode::Char('r') => {
+                    stdout().execute(MoveTo(0, 9))?.execute(Clear(ClearType::FromCursorDown))?;
+                    records = usecase::show_history(device, &store, &mut view).await?
+                }
+                KeyCode::Char('p') => {
+                    usecase::cycle_profile(device, &mut store)?;
+                    stdout().execute(MoveTo(0, 9))?.execute(Clear(ClearType::FromCursorDown))?;
+                    usecase::render_history(device, &records, &store, &mut view)?
+                }
+                KeyCode::Char('e') => {
+                    cmd_show_power(device, &records).await?;
+                    draw_history_actions()?;
+                    usecase::render_history(device, &records, &store, &mut view)?
+                }
+                KeyCode::Char('s') => {
+                    cmd_edit_settings(device).await?;
+                    draw_history_actions()?;
+                    usecase::render_history(device, &records, &store, &mut view)?
+                }
+                KeyCode::Char('c') => {
+                    cmd_send_commands(device).await?;
+                    draw_history_actions()?;
+                    usecase::render_history(device, &records, &store, &mut view)?
+                }
+                KeyCode::Char('l') => {
+                    cmd_follow_live(device, &mut records, &store, &mut view).await?;
+                    draw_history_actions()?;
+                    usecase::render_history(device, &records, &store, &mut view)?
+                }
+                KeyCode::Esc => {
+                    break;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+async fn cmd_follow_live(
+    device: &Device,
+    records: &mut Vec<Dated>,
+    store: &ProfileStore,
+    view: &mut widgets::ListView<widgets::Reading>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let limit = format!("Up to {LIVE_LEN} samples per connection, stored once disconnected");
+    draw_actions(&[
+        "Live mode, records are added as the device samples while connected:",
+        &limit,
+        "Press 'ESC' to disconnect and go back",
+        "    date          avg   min   max  moisture  battery     fault",
+    ])?;
+
+    let mut live = device.live().await?;
+    usecase::render_history(device, records, store, view)?;
+
+    loop {
+        if event::poll(Duration::ZERO)? {
+            if let Event::Key(key_event) = event::read()? {
+                if key_event.code == KeyCode::Esc {
+                    break;
+                }
+            }
+        }
+        match tokio::time::timeout(LIVE_POLL, live.next()).await {
+            Ok(Some(record)) => {
+                stdout().execute(MoveTo(0, 5))?.execute(Clear(ClearType::FromCursorDown))?;
+                usecase::add_record(device, records, record, store, view)?;
+            }
+            // Disconnected by the device.
+            Ok(None) => return Ok(()),
+            Err(_) => {}
+        }
+    }
+
+    live.close().await
+}
+
+async fn cmd_edit_settings(device: &Device) -> Result<(), Box<dyn std::error::Error>> {
+    draw_actions(&[
+        "Settings mode, changes apply from the next wake of the device:",
+        "Press 'UP' and 'DOWN' to select a setting",
+        "Press '+' and '-' to change it",
+        "Press 'x' to drop the calibration",
+        "Press 'w' to write the settings to the device",
+        "Press 'r' to re-read them",
+        "Press 'ESC' to go back",
+    ])?;
+
+    let mut view = widgets::ListView::new(String::new());
+    let mut settings = usecase::show_settings(device, &mut view).await?;
+    let render = |view: &mut widgets::ListView<widgets::Entry>,
+                  settings: &DeviceConfig,
+                  status: Option<&str>|
+     -> Result<(), Box<dyn std::error::Error>> {
+        stdout().execute(MoveTo(0, 8))?.execute(Clear(ClearType::FromCursorDown))?;
+        Ok(SettingsUI::render(view, settings, status)?)
+    };
+
+    loop {
+        match event::read()? {
+            Event::Key(key_event) => match key_event.code {
+                KeyCode::Char('+') | KeyCode::Char('-') => {
+                    if let Some(entry) = view.selected_item() {
+                        let up = key_event.code.is_char('+');
+                        usecase::adjust_setting(&mut settings, entry.setting, up);
+                        render(&mut view, &settings, None)?;
+                    }
+                }
+                KeyCode::Char('x') => {
+                    settings.calibration = None;
+                    render(&mut view, &settings, None)?;
+                }
+                KeyCode::Char('w') => {
+                    stdout().execute(MoveTo(0, 8))?.execute(Clear(ClearType::FromCursorDown))?;
+                    usecase::save_settings(device, &settings, &mut view).await?;
+                }
+                KeyCode::Char('r') => {
+                    stdout().execute(MoveTo(0, 8))?.execute(Clear(ClearType::FromCursorDown))?;
+                    settings = usecase::show_settings(device, &mut view).await?;
+                }
+                KeyCode::Up => {
+                    view.select_prev_item();
+                    render(&mut view, &settings, None)?;
+                }
+                KeyCode::Down => {
+                    view.select_next_item();
+                    render(&mut view, &settings, None)?;
+                }
+                KeyCode::Esc => {
+                    break;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+async fn cmd_send_commands(device: &Device) -> Result<(), Box<dyn std::error::Error>> {
+    draw_actions(&[
+        "Commands mode, changes are carried out once disconnected:",
+        "Press 'p' to ping, 'd' for diagnostics, 'h' to read the history",
+        "Press 't' to set the time, 'm' to measure now",
+        "Press 'c' to clear the history, 'b' to reboot",
+        "Press 'i' to let a halted pump run again, once the reservoir is refilled",
+        "Press 'ESC' to disconnect and go back",
+    ])?;
+
+    let mut rpc = device.rpc().await?;
+    let mut log = vec![];
+    let mut view = widgets::ListView::new("Commands".to_owned());
+
+    loop {
+        let command = match event::read()? {
+            Event::Key(key_event) => match key_event.code {
+                KeyCode::Char('p') => Command::Ping,
+                KeyCode::Char('d') => Command::Diagnostics,
+                KeyCode::Char('h') => Command::ReadHistory,
+                KeyCode::Char('t') => Command::SetTime,
+                KeyCode::Char('m') => Command::Sample,
+                KeyCode::Char('c') => Command::ClearHistory,
+                KeyCode::Char('b') => Command::Reboot,
+                KeyCode::Char('i') => Command::ResetInterlock,
+                KeyCode::Esc => break,
+                _ => continue,
+            },
+            _ => continue,
+        };
+        stdout().execute(MoveTo(0, 7))?.execute(Clear(ClearType::FromCursorDown))?;
+        usecase::send_command(&mut rpc, command, &mut log, &mut view).await?;
+    }
+
+    rpc.close().await
+}
+
+async fn cmd_show_power(
+    device: &Device,
+    records: &[Dated],
+) -> Result<(), Box<dyn std::error::Error>> {
+    draw_actions(&[
+        "Power mode:",
+        "Press '+' and '-' to change the battery capacity",
+        "Press 'ESC' to go back",
+        "      board    average  days  left  sampling  radio  sleep",
+    ])?;
+
+    let settings = device.read_settings().await?;
+    let model = usecase::read_model(device).await;
+    let mut capacity_mah = BATTERY_CAPACITY_MAH;
+    let mut view = widgets::ListView::new(String::new());
+    usecase::estimate_power(records, &settings, model, capacity_mah, &mut view)?;
+
+    loop {
+        match event::read()? {
+            Event::Key(key_event) => match key_event.code {
+                KeyCode::Char('+') => {
+                    capacity_mah += BATTERY_CAPACITY_STEP_MAH;
+                    stdout().execute(MoveTo(0, 5))?.execute(Clear(ClearType::FromCursorDown))?;
+                    usecase::estimate_power(records, &settings, model, capacity_mah, &mut view)?
+                }
+                KeyCode::Char('-') => {
+                    capacity_mah = capacity_mah
+                        .saturating_sub(BATTERY_CAPACITY_STEP_MAH)
+                        .max(BATTERY_CAPACITY_STEP_MAH);
+                    stdout().execute(MoveTo(0, 5))?.execute(Clear(ClearType::FromCursorDown))?;
+                    usecase::estimate_power(records, &settings, model, capacity_mah, &mut view)?
+                }
+                KeyCode::Esc => {
+                    break;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
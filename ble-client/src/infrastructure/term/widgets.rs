@@ -1,17 +1,35 @@
 use std::io::stdout;
 
+use chrono::{DateTime, Local};
 use crossterm::{
     style::{Color, Print, SetBackgroundColor, SetForegroundColor},
     terminal::{Clear, ClearType},
     ExecutableCommand,
 };
 
-use crate::{application, infrastructure::ble::Device};
+use humidity_core::{
+    plant::{Profile, Season, Status},
+    power::{Estimate, Phase},
+    settings::{Calibration, DeviceConfig},
+    shared::Flag,
+};
+
+use crate::{
+    application::{
+        self,
+        usecase::{Command, Outcome, Setting},
+    },
+    infrastructure::ble::{Dated, Device, Heard, Payload},
+};
 
 pub type Predicate<T> = dyn Fn(&T) -> bool;
 pub type BoxedPredicate<T> = Box<Predicate<T>>;
 pub trait ListItem: Clone {
     fn display(&self) -> String;
+
+    fn color(&self) -> Option<Color> {
+        None
+    }
 }
 
 pub struct ListView<T: ListItem> {
@@ -58,9 +76,11 @@ impl<T: ListItem> ListView<T> {
                 stdout()
                     .execute(SetBackgroundColor(Color::White))?
                     .execute(SetForegroundColor(Color::Black))?;
+            } else if let Some(color) = item.color() {
+                stdout().execute(SetForegroundColor(color))?;
             }
             stdout().execute(Print(item.display()))?;
-            if is_selected {
+            if is_selected || item.color().is_some() {
                 stdout()
                     .execute(SetBackgroundColor(Color::Black))?
                     .execute(SetForegroundColor(Color::White))?;
@@ -81,6 +101,10 @@ impl<T: ListItem> ListView<T> {
         self.filter = filter;
     }
 
+    pub fn selected_item(&self) -> Option<&T> {
+        self.visible_items.get(self.selected)
+    }
+
     pub fn select_next_item(&mut self) {
         self.selected = if self.selected < self.visible_items.len() - 1 {
             self.selected + 1
@@ -129,6 +153,298 @@ impl ListItem for Device {
     }
 }
 
+impl application::ui::BroadcastsUI for ListView<Heard> {
+    fn render(&mut self, heard: &[Heard]) -> Result<(), Box<dyn std::error::Error>> {
+        self.set_items(heard.to_vec());
+        ListView::render(self)
+    }
+}
+
+impl ListItem for Heard {
+    fn display(&self) -> String {
+        let Heard { id, at, payload } = self;
+        let percentage = |value: Option<u8>| value.map_or("-".to_owned(), |v| format!("{v}%"));
+        let hundredths = |value: Option<u16>| percentage(value.map(|v| ((v + 50) / 100) as u8));
+        let (moisture, battery, details) = match payload {
+            Payload::Custom(broadcast) => {
+                let flags = [
+                    (Flag::Dry, "dry"),
+                    (Flag::LowBattery, "low-battery"),
+                    (Flag::SensorFault, "sensor-fault"),
+                    (Flag::PumpFault, "pump-fault"),
+                    (Flag::Watered, "watered"),
+                ]
+                .iter()
+                .filter(|(flag, _)| broadcast.status.contains(*flag))
+                .map(|(_, name)| *name)
+                .collect::<Vec<_>>()
+                .join(",");
+                (percentage(broadcast.moisture), percentage(broadcast.battery), flags)
+            }
+            Payload::BTHome(measurement) => {
+                let temperature = measurement
+                    .temperature
+                    .map_or(String::new(), |t| format!(" {:.1}°C", t as f32 / 100.0));
+                (
+                    hundredths(measurement.moisture),
+                    percentage(measurement.battery),
+                    format!("bthome{temperature}"),
+                )
+            }
+        };
+        format!(
+            " => {} {:<20} {:>8} {:>7} {}\r\n",
+            at.format("%H:%M:%S"),
+            id,
+            moisture,
+            battery,
+            details,
+        )
+    }
+
+    fn color(&self) -> Option<Color> {
+        // BTHome carries readings only, no status.
+        let Payload::Custom(broadcast) = &self.payload else {
+            return None;
+        };
+        let status = broadcast.status;
+        if status.contains(Flag::SensorFault) || status.contains(Flag::PumpFault) {
+            Some(Color::Red)
+        } else if status.contains(Flag::Dry) || status.contains(Flag::LowBattery) {
+            Some(Color::Yellow)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Reading {
+    dated: Dated,
+    status: Option<Status>,
+}
+
+impl application::ui::HistoryUI for ListView<Reading> {
+    fn render(
+        &mut self,
+        records: &[Dated],
+        profile: Option<&Profile>,
+        season: Season,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.title = match profile {
+            Some(profile) => format!("History [{}, {season:?}]", profile.name()),
+            None => "History [no profile]".to_owned(),
+        };
+        self.set_items(
+            records
+                .iter()
+                .map(|dated| Reading {
+                    dated: *dated,
+                    status: profile.map(|p| p.status(dated.record.summary.moisture(), season)),
+                })
+                .collect(),
+        );
+        ListView::render(self)
+    }
+}
+
+impl ListItem for Reading {
+    fn display(&self) -> String {
+        let Dated { at, record } = &self.dated;
+        format!(
+            " => {:<11} {:>5} {:>5} {:>5} {:>8}% {:>5}mV {:>3}% {}\r\n",
+            at.map_or("-".to_owned(), |at| at.format("%m-%d %H:%M").to_string()),
+            record.summary.avg,
+            record.summary.min,
+            record.summary.max,
+            record.summary.moisture(),
+            record.battery.map_or("-".to_owned(), |battery| battery.millivolts.to_string()),
+            record.battery.map_or("-".to_owned(), |battery| battery.percentage().to_string()),
+            record.fault.map(|fault| format!("{fault:?}")).unwrap_or_default(),
+        )
+    }
+
+    fn color(&self) -> Option<Color> {
+        self.status.map(|status| match status {
+            Status::TooDry => Color::Red,
+            Status::Ok => Color::Green,
+            Status::TooWet => Color::Blue,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Budget {
+    board: &'static str,
+    estimate: Estimate,
+    capacity_mah: u32,
+    charge: Option<u8>,
+}
+
+impl application::ui::PowerUI for ListView<Budget> {
+    fn render(
+        &mut self,
+        estimates: &[(&'static str, Estimate)],
+        capacity_mah: u32,
+        charge: Option<u8>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.title = format!("Power budget [{capacity_mah}mAh]");
+        self.set_items(
+            estimates
+                .iter()
+                .map(|(board, estimate)| Budget {
+                    board,
+                    estimate: *estimate,
+                    capacity_mah,
+                    charge,
+                })
+                .collect(),
+        );
+        ListView::render(self)
+    }
+}
+
+impl ListItem for Budget {
+    fn display(&self) -> String {
+        let Budget { board, estimate, capacity_mah, charge } = self;
+        let days = estimate.days(*capacity_mah);
+        let left = charge
+            .map(|charge| format!("{:>5}", days * charge as u32 / 100))
+            .unwrap_or_else(|| format!("{:>5}", "-"));
+        let radio = [Phase::RadioInit, Phase::Advertising, Phase::Connected]
+            .iter()
+            .map(|phase| estimate.share(*phase) as u32)
+            .sum::<u32>();
+        format!(
+            " => {:<8} {:>6}uA {:>5} {} {:>7}% {:>5}% {:>5}%\r\n",
+            board,
+            estimate.average_ua(),
+            days,
+            left,
+            estimate.share(Phase::Boot) as u32 + estimate.share(Phase::Sampling) as u32,
+            radio,
+            estimate.share(Phase::DeepSleep),
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct Entry {
+    pub setting: Setting,
+    value: String,
+}
+
+impl application::ui::SettingsUI for ListView<Entry> {
+    fn render(
+        &mut self,
+        settings: &DeviceConfig,
+        status: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.title = match status {
+            Some(status) => format!("Settings [{status}]"),
+            None => "Settings".to_owned(),
+        };
+        let calibration = |value: fn(&Calibration) -> u16| {
+            settings.calibration.as_ref().map_or("model".to_owned(), |c| value(c).to_string())
+        };
+        self.set_items(
+            Setting::ALL
+                .iter()
+                .map(|setting| Entry {
+                    setting: *setting,
+                    value: match setting {
+                        Setting::Interval => format!("{}min", settings.interval / 60),
+                        Setting::Samples => settings.samples.to_string(),
+                        Setting::Sensor => format!("{:?}", settings.sensor),
+                        Setting::CalibrationLow => calibration(|c| c.low),
+                        Setting::CalibrationHigh => calibration(|c| c.high),
+                    },
+                })
+                .collect(),
+        );
+        ListView::render(self)
+    }
+}
+
+impl ListItem for Entry {
+    fn display(&self) -> String {
+        let name = match self.setting {
+            Setting::Interval => "interval",
+            Setting::Samples => "samples",
+            Setting::Sensor => "sensor",
+            Setting::CalibrationLow => "in water",
+            Setting::CalibrationHigh => "in air",
+        };
+        format!(" => {:<10} {:>8}\r\n", name, self.value)
+    }
+}
+
+/// A command sent, with what came of it.
+#[derive(Clone)]
+pub struct Sent {
+    at: DateTime<Local>,
+    command: Command,
+    outcome: Outcome,
+}
+
+impl application::ui::CommandsUI for ListView<Sent> {
+    fn render(
+        &mut self,
+        log: &[(DateTime<Local>, Command, Outcome)],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.set_items(
+            log.iter()
+                .map(|(at, command, outcome)| Sent {
+                    at: *at,
+                    command: *command,
+                    outcome: outcome.clone(),
+                })
+                .collect(),
+        );
+        ListView::render(self)
+    }
+}
+
+impl ListItem for Sent {
+    fn display(&self) -> String {
+        let outcome = match &self.outcome {
+            Outcome::Pong(now, round_trip) => {
+                format!("device clock {now}s, answered in {}ms", round_trip.as_millis())
+            }
+            Outcome::Diagnostics(diagnostics) => format!(
+                "clock {}s, {} advertisements, {} records, battery {}",
+                diagnostics.now,
+                diagnostics.sequence,
+                diagnostics.records,
+                diagnostics
+                    .battery
+                    .map_or("-".to_owned(), |battery| format!("{}%", battery.percentage())),
+            ),
+            Outcome::History(count, last) => match last {
+                Some(record) => {
+                    format!("{count} records, latest {}% moist", record.summary.moisture())
+                }
+                None => "no records".to_owned(),
+            },
+            Outcome::Done => "done, carried out once disconnected".to_owned(),
+            Outcome::Failed(reason) => format!("failed: {reason}"),
+        };
+        format!(
+            " => {} {:<13} {}\r\n",
+            self.at.format("%H:%M:%S"),
+            format!("{:?}", self.command),
+            outcome
+        )
+    }
+
+    fn color(&self) -> Option<Color> {
+        match self.outcome {
+            Outcome::Failed(_) => Some(Color::Red),
+            _ => None,
+        }
+    }
+}
+
 fn within_upper_bound(value: usize, upper_bound: usize) -> usize {
     if value >= upper_bound && upper_bound > 0 {
         upper_bound - 1
@@ -1,5 +1,6 @@
 use btleplug::api::Peripheral;
 
+use humidity_core::shared::protocol;
 use infrastructure::term;
 use std::error::Error;
 use tokio::time::Instant;
@@ -9,8 +10,8 @@ mod application;
 mod infrastructure;
 
 async fn collect_data(device: impl Peripheral) -> Result<(), Box<dyn Error>> {
-    let pong = Uuid::try_parse("987312e0-2354-11eb-9f10-fbc30a62cf50")?;
-    // let _historical = Uuid::try_parse("987312e0-2354-11eb-9f10-fbc30a62cf60")?;
+    let pong = Uuid::from_u128(protocol::HUMIDITY.uuid.as_u128());
+    // let _historical = Uuid::from_u128(protocol::HISTORICAL.uuid.as_u128());
 
     // println!("connecting to {}", device.id());
     let since_connecting = Instant::now();
@@ -0,0 +1,184 @@
+//! # Advertising policy
+//!
+//! Bringing up the radio and advertising is by far the most expensive part of
+//! a wake. The [`Advertiser`] decides on which wakes it is worth doing, as
+//! configured by a [`Policy`]: whenever the periodic advertising job is due,
+//! once enough history has piled up without being delivered to a client, when a
+//! reading crosses the alert threshold, or right after a button press. It is
+//! meant to be kept across deep sleeps to count undelivered records.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::advertising::{Advertiser, Inputs, Policy};
+//! let policy = Policy { backlog: 24, on_alert: true, on_button: true };
+//! let mut advertiser = Advertiser::new();
+//! advertiser.stored();
+//! let inputs = Inputs { alert: Some(false), button: false, due: false };
+//! println!("advertise: {:?}", advertiser.decide(&policy, &inputs));
+//! ```
+
+/// When to advertise. Any of the enabled conditions is enough.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Policy {
+    /// Advertise once at least this many records are undelivered, zero to
+    /// disable.
+    pub backlog: u16,
+    /// Advertise when a reading enters or leaves the alert range.
+    pub on_alert: bool,
+    /// Advertise when the device was woken up by a button press.
+    pub on_button: bool,
+}
+
+/// Conditions observed during the current wake.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Inputs {
+    /// Whether the reading taken on this wake calls for an alert, if any was
+    /// taken.
+    pub alert: Option<bool>,
+    /// Whether the device was woken up by a button press.
+    pub button: bool,
+    /// Whether the periodic advertising job is due on this wake.
+    pub due: bool,
+}
+
+/// Why the device advertises on this wake.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reason {
+    /// The periodic advertising job is due.
+    Periodic,
+    /// Too many records are waiting to be delivered.
+    Backlog,
+    /// A reading crossed the alert threshold.
+    Alert,
+    /// A button was pressed.
+    Button,
+}
+
+/// Decides whether to advertise on each wake.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Advertiser {
+    pending: u16,
+    alerting: bool,
+}
+
+impl Default for Advertiser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Advertiser {
+    pub const fn new() -> Self {
+        Self { pending: 0, alerting: false }
+    }
+
+    /// Counts a record stored in the history and not delivered yet.
+    pub fn stored(&mut self) {
+        self.pending = self.pending.saturating_add(1);
+    }
+
+    /// Acknowledges that a client read the whole history.
+    pub fn delivered(&mut self) {
+        self.pending = 0;
+    }
+
+    /// Number of records not delivered to any client yet.
+    pub fn pending(&self) -> u16 {
+        self.pending
+    }
+
+    /// Returns why to advertise on this wake, or `None` to stay silent. Must be
+    /// called once per wake.
+    pub fn decide(&mut self, policy: &Policy, inputs: &Inputs) -> Option<Reason> {
+        let crossed = match inputs.alert {
+            Some(alert) if alert != self.alerting => {
+                self.alerting = alert;
+                true
+            }
+            _ => false,
+        };
+
+        if policy.on_button && inputs.button {
+            Some(Reason::Button)
+        } else if policy.on_alert && crossed {
+            Some(Reason::Alert)
+        } else if policy.backlog > 0 && self.pending >= policy.backlog {
+            Some(Reason::Backlog)
+        } else if inputs.due {
+            Some(Reason::Periodic)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    const POLICY: Policy = Policy { backlog: 5, on_alert: true, on_button: true };
+
+    const QUIET: Inputs = Inputs { alert: Some(false), button: false, due: false };
+
+    #[test]
+    fn test_periodic_when_due() {
+        let mut sut = Advertiser::new();
+
+        let due = Inputs { due: true, ..QUIET };
+        assert_eq!(None, sut.decide(&POLICY, &QUIET));
+        assert_eq!(Some(Reason::Periodic), sut.decide(&POLICY, &due));
+        assert_eq!(None, sut.decide(&POLICY, &QUIET));
+    }
+
+    #[test]
+    fn test_backlog() {
+        let mut sut = Advertiser::new();
+
+        for _ in 0..4 {
+            sut.stored();
+            assert_eq!(None, sut.decide(&POLICY, &QUIET));
+        }
+        sut.stored();
+        assert_eq!(Some(Reason::Backlog), sut.decide(&POLICY, &QUIET));
+        assert_eq!(Some(Reason::Backlog), sut.decide(&POLICY, &QUIET));
+
+        sut.delivered();
+        assert_eq!(0, sut.pending());
+        assert_eq!(None, sut.decide(&POLICY, &QUIET));
+    }
+
+    #[test]
+    fn test_alert_crossing() {
+        let mut sut = Advertiser::new();
+
+        let alert = Inputs { alert: Some(true), ..QUIET };
+        let unknown = Inputs { alert: None, ..QUIET };
+        assert_eq!(Some(Reason::Alert), sut.decide(&POLICY, &alert));
+        assert_eq!(None, sut.decide(&POLICY, &alert));
+        assert_eq!(None, sut.decide(&POLICY, &unknown));
+        assert_eq!(Some(Reason::Alert), sut.decide(&POLICY, &QUIET));
+        assert_eq!(None, sut.decide(&POLICY, &QUIET));
+    }
+
+    #[test]
+    fn test_button_before_periodic() {
+        let mut sut = Advertiser::new();
+
+        let button = Inputs { button: true, due: true, ..QUIET };
+        assert_eq!(Some(Reason::Button), sut.decide(&POLICY, &button));
+    }
+
+    #[test_case(Inputs { alert: Some(true), ..QUIET }; "alert")]
+    #[test_case(Inputs { button: true, ..QUIET }; "button")]
+    fn test_disabled_triggers(inputs: Inputs) {
+        let policy = Policy { backlog: 0, on_alert: false, on_button: false };
+        let mut sut = Advertiser::new();
+
+        for _ in 0..300 {
+            sut.stored();
+            assert_eq!(None, sut.decide(&policy, &inputs));
+        }
+    }
+}
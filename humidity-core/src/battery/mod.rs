@@ -0,0 +1,199 @@
+//! # Battery monitoring
+//!
+//! The supply voltage is measured through a resistor [`Divider`], so it fits
+//! within the ADC range, and converted into a state of charge using a voltage
+//! curve specific to the [`Chemistry`] powering the device.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::battery::{Battery, Chemistry, Divider};
+//! let divider = Divider::new(100_000, 100_000);
+//! let battery = Battery { millivolts: divider.millivolts(1940), chemistry: Chemistry::LiIon };
+//! println!("battery: {}mV {}%", battery.millivolts, battery.percentage());
+//! ```
+
+use crate::serde::{self, Deserializable, Serializable};
+
+/// State of charge at or below which the battery is considered low.
+pub const LOW_BATTERY_PERCENTAGE: u8 = 20;
+
+/// Resistor divider between the battery and the ADC pin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divider {
+    /// Resistance between the battery and the ADC pin, in Ohms.
+    top: u32,
+    /// Resistance between the ADC pin and ground, in Ohms.
+    bottom: u32,
+}
+
+impl Divider {
+    pub const fn new(top: u32, bottom: u32) -> Self {
+        Self { top, bottom }
+    }
+
+    /// Given the calibrated voltage at the ADC pin, returns the battery voltage.
+    pub fn millivolts(&self, adc_millivolts: u16) -> u16 {
+        let millivolts = adc_millivolts as u32 * (self.top + self.bottom) / self.bottom;
+        millivolts.min(u16::MAX as u32) as u16
+    }
+}
+
+/// Battery chemistries, each with its own discharge curve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Chemistry {
+    /// Single Li-ion or Li-Po cell, 3.0V to 4.2V.
+    LiIon,
+    /// Two alkaline AA cells in series, 2.0V to 3.2V.
+    Alkaline2xAA,
+}
+
+impl Chemistry {
+    /// Voltage to state of charge curve, sorted by voltage.
+    fn curve(&self) -> &'static [(u16, u8)] {
+        match self {
+            Chemistry::LiIon => &[
+                (3000, 0),
+                (3300, 5),
+                (3600, 10),
+                (3700, 20),
+                (3750, 30),
+                (3790, 40),
+                (3830, 50),
+                (3870, 60),
+                (3920, 70),
+                (3970, 80),
+                (4100, 90),
+                (4200, 100),
+            ],
+            Chemistry::Alkaline2xAA => &[
+                (2000, 0),
+                (2200, 5),
+                (2300, 10),
+                (2400, 20),
+                (2500, 40),
+                (2600, 60),
+                (2700, 75),
+                (2800, 85),
+                (2900, 92),
+                (3000, 96),
+                (3200, 100),
+            ],
+        }
+    }
+
+    /// Returns the state of charge for the given voltage, interpolating linearly
+    /// between the points of the discharge curve.
+    pub fn percentage(&self, millivolts: u16) -> u8 {
+        let curve = self.curve();
+        let (first, last) = (curve[0], curve[curve.len() - 1]);
+        if millivolts <= first.0 {
+            return first.1;
+        }
+        if millivolts >= last.0 {
+            return last.1;
+        }
+
+        let upper = curve.iter().position(|(mv, _)| *mv >= millivolts).unwrap();
+        let (mv0, pct0) = curve[upper - 1];
+        let (mv1, pct1) = curve[upper];
+        let span = (pct1 - pct0) as u32 * (millivolts - mv0) as u32 / (mv1 - mv0) as u32;
+        pct0 + span as u8
+    }
+}
+
+/// A battery voltage reading.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Battery {
+    /// Battery voltage in millivolts.
+    pub millivolts: u16,
+    /// Battery chemistry.
+    pub chemistry: Chemistry,
+}
+
+impl Battery {
+    /// Returns the estimated state of charge, from 0 to 100.
+    pub fn percentage(&self) -> u8 {
+        self.chemistry.percentage(self.millivolts)
+    }
+
+    /// Whether the battery should be replaced or recharged soon.
+    pub fn is_low(&self) -> bool {
+        self.percentage() <= LOW_BATTERY_PERCENTAGE
+    }
+}
+
+impl Serializable for Chemistry {
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        ser.write_u8(*self as u8)
+    }
+}
+
+impl Deserializable for Chemistry {
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        match de.read_u8()? {
+            0 => Ok(Self::LiIon),
+            1 => Ok(Self::Alkaline2xAA),
+            _ => Err(serde::Error::Other),
+        }
+    }
+}
+
+impl Serializable for Battery {
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        let mut n = ser.write_u16(self.millivolts)?;
+        n += self.chemistry.serialize(ser)?;
+        Ok(n)
+    }
+}
+
+impl Deserializable for Battery {
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        let millivolts = de.read_u16()?;
+        let chemistry = Chemistry::deserialize(de)?;
+        Ok(Self { millivolts, chemistry })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(100_000, 100_000, 1950, 3900)]
+    #[test_case(47_000, 100_000, 2000, 2940)]
+    fn test_divider(top: u32, bottom: u32, input: u16, expected: u16) {
+        assert_eq!(expected, Divider::new(top, bottom).millivolts(input));
+    }
+
+    #[test_case(Chemistry::LiIon, 2500, 0; "li-ion below curve")]
+    #[test_case(Chemistry::LiIon, 3700, 20; "li-ion on point")]
+    #[test_case(Chemistry::LiIon, 3850, 55; "li-ion interpolated")]
+    #[test_case(Chemistry::LiIon, 4300, 100; "li-ion above curve")]
+    #[test_case(Chemistry::Alkaline2xAA, 1800, 0; "alkaline below curve")]
+    #[test_case(Chemistry::Alkaline2xAA, 2450, 30; "alkaline interpolated")]
+    #[test_case(Chemistry::Alkaline2xAA, 3300, 100; "alkaline above curve")]
+    fn test_percentage(chemistry: Chemistry, millivolts: u16, expected: u8) {
+        assert_eq!(expected, chemistry.percentage(millivolts));
+    }
+
+    #[test_case(3600, true)]
+    #[test_case(3700, true)]
+    #[test_case(3750, false)]
+    fn test_is_low(millivolts: u16, expected: bool) {
+        let sut = Battery { millivolts, chemistry: Chemistry::LiIon };
+        assert_eq!(expected, sut.is_low());
+    }
+
+    #[test]
+    fn battery_serde() {
+        let input = Battery { millivolts: 2870, chemistry: Chemistry::Alkaline2xAA };
+
+        let mut buffer = [0u8; 60];
+        let n = serde::serialize(&input, &mut buffer).unwrap();
+
+        let output = serde::deserialize::<Battery>(&buffer[..n]).unwrap();
+        assert_eq!(input, output);
+    }
+}
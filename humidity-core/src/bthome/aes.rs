@@ -0,0 +1,248 @@
+//! AES-128 block encryption, and the CCM mode built upon it.
+//!
+//! Only the forward cipher is implemented, as CCM never needs to decrypt a
+//! block. Tables make it small and fast enough for a handful of blocks per
+//! advertisement, not constant time.
+
+/// Size of a block and of a key.
+pub const BLOCK: usize = 16;
+/// Size of a CCM nonce, leaving two bytes to count the blocks.
+pub const NONCE: usize = 13;
+
+const ROUNDS: usize = 10;
+
+#[rustfmt::skip]
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+const RCON: [u8; ROUNDS] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+/// Errors that can happen while decrypting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The message was altered, or encrypted with another key or nonce.
+    ErrAuthentication,
+}
+
+/// AES-128 with its expanded key.
+pub struct Aes128 {
+    round_keys: [[u8; BLOCK]; ROUNDS + 1],
+}
+
+impl Aes128 {
+    pub fn new(key: &[u8; BLOCK]) -> Self {
+        let mut round_keys = [[0u8; BLOCK]; ROUNDS + 1];
+        round_keys[0] = *key;
+        for round in 1..=ROUNDS {
+            let previous = round_keys[round - 1];
+            let mut word = [previous[13], previous[14], previous[15], previous[12]];
+            word.iter_mut().for_each(|byte| *byte = SBOX[*byte as usize]);
+            word[0] ^= RCON[round - 1];
+            for i in 0..BLOCK {
+                let byte = previous[i] ^ if i < 4 { word[i] } else { round_keys[round][i - 4] };
+                round_keys[round][i] = byte;
+            }
+        }
+        Self { round_keys }
+    }
+
+    pub fn encrypt_block(&self, block: &mut [u8; BLOCK]) {
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..=ROUNDS {
+            block.iter_mut().for_each(|byte| *byte = SBOX[*byte as usize]);
+            shift_rows(block);
+            if round != ROUNDS {
+                mix_columns(block);
+            }
+            add_round_key(block, &self.round_keys[round]);
+        }
+    }
+
+    /// Encrypts the message in place with AES-CCM, authenticating it along
+    /// the associated data, and returns its `M` bytes long tag.
+    pub fn ccm_encrypt<const M: usize>(
+        &self,
+        nonce: &[u8; NONCE],
+        aad: &[u8],
+        message: &mut [u8],
+    ) -> [u8; M] {
+        let tag = self.cbc_mac::<M>(nonce, aad, message);
+        self.ctr(nonce, message);
+        self.encrypt_tag(nonce, tag)
+    }
+
+    /// Decrypts the message in place with AES-CCM, checking it against its tag.
+    /// The message is left scrambled when the tag does not match.
+    pub fn ccm_decrypt<const M: usize>(
+        &self,
+        nonce: &[u8; NONCE],
+        aad: &[u8],
+        message: &mut [u8],
+        tag: &[u8; M],
+    ) -> Result<(), Error> {
+        self.ctr(nonce, message);
+        let expected = self.encrypt_tag(nonce, self.cbc_mac::<M>(nonce, aad, message));
+        // Compares all bytes, not to leak how much of the tag matched.
+        let diff = expected.iter().zip(tag).fold(0, |diff, (a, b)| diff | (a ^ b));
+        if diff == 0 {
+            Ok(())
+        } else {
+            Err(Error::ErrAuthentication)
+        }
+    }
+
+    fn cbc_mac<const M: usize>(&self, nonce: &[u8; NONCE], aad: &[u8], message: &[u8]) -> [u8; M] {
+        let mut mac = [0u8; BLOCK];
+        // The first block holds the flags, the nonce and the message length.
+        mac[0] = (!aad.is_empty() as u8) << 6 | ((M as u8 - 2) / 2) << 3 | 1;
+        mac[1..=NONCE].copy_from_slice(nonce);
+        mac[14..].copy_from_slice(&(message.len() as u16).to_be_bytes());
+        self.encrypt_block(&mut mac);
+
+        if !aad.is_empty() {
+            // Associated data is prefixed with its length, both padded to blocks.
+            let length = (aad.len() as u16).to_be_bytes();
+            let mut bytes = length.iter().chain(aad).peekable();
+            while bytes.peek().is_some() {
+                mac.iter_mut().zip(bytes.by_ref().take(BLOCK)).for_each(|(m, b)| *m ^= b);
+                self.encrypt_block(&mut mac);
+            }
+        }
+        for chunk in message.chunks(BLOCK) {
+            mac.iter_mut().zip(chunk).for_each(|(m, b)| *m ^= b);
+            self.encrypt_block(&mut mac);
+        }
+
+        let mut tag = [0u8; M];
+        tag.copy_from_slice(&mac[..M]);
+        tag
+    }
+
+    /// Encrypts the tag with the first key stream block.
+    fn encrypt_tag<const M: usize>(&self, nonce: &[u8; NONCE], mut tag: [u8; M]) -> [u8; M] {
+        let stream = self.key_stream(nonce, 0);
+        tag.iter_mut().zip(stream).for_each(|(t, s)| *t ^= s);
+        tag
+    }
+
+    /// XORs the message with the key stream, starting at the second block.
+    fn ctr(&self, nonce: &[u8; NONCE], message: &mut [u8]) {
+        for (i, chunk) in message.chunks_mut(BLOCK).enumerate() {
+            let stream = self.key_stream(nonce, i as u16 + 1);
+            chunk.iter_mut().zip(stream).for_each(|(m, s)| *m ^= s);
+        }
+    }
+
+    fn key_stream(&self, nonce: &[u8; NONCE], counter: u16) -> [u8; BLOCK] {
+        let mut block = [0u8; BLOCK];
+        block[0] = 1;
+        block[1..=NONCE].copy_from_slice(nonce);
+        block[14..].copy_from_slice(&counter.to_be_bytes());
+        self.encrypt_block(&mut block);
+        block
+    }
+}
+
+fn add_round_key(block: &mut [u8; BLOCK], key: &[u8; BLOCK]) {
+    block.iter_mut().zip(key).for_each(|(b, k)| *b ^= k);
+}
+
+/// Rotates each row left by its index, bytes being stored column by column.
+fn shift_rows(block: &mut [u8; BLOCK]) {
+    let state = *block;
+    for column in 0..4 {
+        for row in 0..4 {
+            block[column * 4 + row] = state[((column + row) % 4) * 4 + row];
+        }
+    }
+}
+
+fn mix_columns(block: &mut [u8; BLOCK]) {
+    for column in block.chunks_mut(4) {
+        let [a, b, c, d] = [column[0], column[1], column[2], column[3]];
+        let all = a ^ b ^ c ^ d;
+        column[0] ^= all ^ double(a ^ b);
+        column[1] ^= all ^ double(b ^ c);
+        column[2] ^= all ^ double(c ^ d);
+        column[3] ^= all ^ double(d ^ a);
+    }
+}
+
+/// Multiplies by x in GF(2^8).
+fn double(byte: u8) -> u8 {
+    (byte << 1) ^ if byte & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hex<const N: usize>(text: &str) -> [u8; N] {
+        let mut bytes = [0u8; N];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_fips_197() {
+        let sut = Aes128::new(&hex("000102030405060708090a0b0c0d0e0f"));
+        let mut block = hex("00112233445566778899aabbccddeeff");
+
+        sut.encrypt_block(&mut block);
+        assert_eq!(hex::<16>("69c4e0d86a7b0430d8cdb78070b4c55a"), block);
+    }
+
+    #[test]
+    fn test_rfc_3610_packet_vector_1() {
+        let sut = Aes128::new(&hex("c0c1c2c3c4c5c6c7c8c9cacbcccdcecf"));
+        let nonce = hex("00000003020100a0a1a2a3a4a5");
+        let aad: [u8; 8] = hex("0001020304050607");
+        let plaintext: [u8; 23] = hex("08090a0b0c0d0e0f101112131415161718191a1b1c1d1e");
+        let mut message = plaintext;
+
+        let tag = sut.ccm_encrypt::<8>(&nonce, &aad, &mut message);
+        assert_eq!(hex::<23>("588c979a61c663d2f066d0c2c0f989806d5f6b61dac384"), message);
+        assert_eq!(hex::<8>("17e8d12cfdf926e0"), tag);
+
+        assert_eq!(Ok(()), sut.ccm_decrypt(&nonce, &aad, &mut message, &tag));
+        assert_eq!(plaintext, message);
+    }
+
+    #[test]
+    fn test_tampering() {
+        let sut = Aes128::new(&[7; BLOCK]);
+        let nonce = [1; NONCE];
+        let mut message = *b"moisture";
+        let tag = sut.ccm_encrypt::<4>(&nonce, &[], &mut message);
+
+        let mut tampered = message;
+        tampered[0] ^= 1;
+        assert_eq!(
+            Err(Error::ErrAuthentication),
+            sut.ccm_decrypt(&nonce, &[], &mut tampered, &tag)
+        );
+        let other = Aes128::new(&[8; BLOCK]);
+        assert_eq!(
+            Err(Error::ErrAuthentication),
+            other.ccm_decrypt(&nonce, &[], &mut message, &tag)
+        );
+    }
+}
@@ -0,0 +1,100 @@
+/// Counters reserved at once, so that storage is only written once every so
+/// many advertisements.
+pub const BLOCK: u32 = 1024;
+
+/// Hands out encryption counters which never repeat, even after a power loss
+/// wipes the memory it lives in. Counters are reserved in blocks, numbered in
+/// persistent storage such as flash, and a cold start skips whatever was left
+/// of the block in use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Counter {
+    next: u32,
+    end: u32,
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Counter {
+    /// Creates a counter with nothing reserved yet.
+    pub const fn new() -> Self {
+        Self { next: 0, end: 0 }
+    }
+
+    /// Returns the next counter. Once the reserved ones run out, `reserve`
+    /// returns the number of the next free block, having stored the one after.
+    pub fn next<E>(&mut self, reserve: impl FnOnce() -> Result<u32, E>) -> Result<u32, E> {
+        if self.next == self.end {
+            let block = reserve()?;
+            self.next = block.wrapping_mul(BLOCK);
+            self.end = self.next.wrapping_add(BLOCK);
+        }
+        let counter = self.next;
+        self.next = self.next.wrapping_add(1);
+        Ok(counter)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Reserves the block kept in a fake flash.
+    fn reserve(flash: &mut u32) -> impl FnOnce() -> Result<u32, ()> + '_ {
+        || {
+            let block = *flash;
+            *flash += 1;
+            Ok(block)
+        }
+    }
+
+    #[test]
+    fn test_counts_within_block() {
+        let mut flash = 0;
+        let mut sut = Counter::new();
+
+        assert_eq!(Ok(0), sut.next(reserve(&mut flash)));
+        assert_eq!(Ok(1), sut.next(reserve(&mut flash)));
+        assert_eq!(1, flash);
+    }
+
+    #[test]
+    fn test_reserves_next_block() {
+        let mut flash = 0;
+        let mut sut = Counter::new();
+
+        for _ in 0..BLOCK {
+            sut.next(reserve(&mut flash)).unwrap();
+        }
+
+        assert_eq!(Ok(BLOCK), sut.next(reserve(&mut flash)));
+        assert_eq!(2, flash);
+    }
+
+    #[test]
+    fn test_keeps_increasing_across_cold_start() {
+        let mut flash = 0;
+        let mut sut = Counter::new();
+        let mut last = 0;
+        for _ in 0..3 {
+            last = sut.next(reserve(&mut flash)).unwrap();
+        }
+
+        // Memory is wiped, the flash is not.
+        let mut sut = Counter::new();
+
+        assert!(sut.next(reserve(&mut flash)).unwrap() > last);
+    }
+
+    #[test]
+    fn test_storage_failure() {
+        let mut flash = 0;
+        let mut sut = Counter::new();
+
+        assert_eq!(Err(()), sut.next(|| Err(())));
+        assert_eq!(Ok(0), sut.next(reserve(&mut flash)));
+    }
+}
@@ -0,0 +1,400 @@
+//! # BTHome
+//!
+//! Encodes readings as [BTHome v2](https://bthome.io) service data, so home
+//! automation systems such as Home Assistant pick the device up on their own,
+//! instead of the custom [`Broadcast`](crate::shared::Broadcast) payload.
+//!
+//! Objects are written in ascending id order, as the format requires, and only
+//! when known: a packet id to drop duplicates, the battery, the soil
+//! temperature when compensating, the air humidity and the soil moisture.
+//!
+//! The payload can be encrypted with AES-CCM and a per-device bind key, shared
+//! with the receivers beforehand. The nonce is made of the device address and
+//! a counter sent along, which must grow with every advertisement. A
+//! [`Counter`] keeps it growing across power losses.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::bthome::{self, Encryption, Measurement};
+//! let measurement = Measurement { moisture: Some(6012), ..Measurement::default() };
+//! let plain = bthome::encode(&measurement, None);
+//! assert_eq!(&[0x40, 0x14, 0x7c, 0x17], plain.as_bytes());
+//!
+//! let key = bthome::parse_key("231d39c1d7cc1ab1aee224cd096db932").unwrap();
+//! let mac = [0x54, 0x48, 0xe6, 0x8f, 0x80, 0xa5];
+//! let encrypted = bthome::encode(&measurement, Some(&Encryption { key, mac, counter: 7 }));
+//! assert_eq!(Ok(measurement), bthome::decode(encrypted.as_bytes(), Some((&key, &mac))));
+//! ```
+
+use crate::{ess, sample::Summary, sensors::Sensor};
+use aes::{Aes128, NONCE};
+
+pub use counter::Counter;
+
+mod aes;
+mod counter;
+
+/// 16-bit UUID of the service data, assigned to BTHome.
+pub const SERVICE_UUID: u16 = 0xFCD2;
+/// Size of a bind key.
+pub const KEY_LEN: usize = aes::BLOCK;
+/// Largest encoded size, with every object and encryption.
+pub const MAX_LEN: usize = 1 + OBJECTS_LEN + COUNTER_LEN + MIC_LEN;
+
+/// BTHome version 2, in the top bits of the device information.
+const VERSION: u8 = 2 << 5;
+const ENCRYPTED: u8 = 0x01;
+/// Set by devices advertising on events rather than regularly, same layout.
+const TRIGGER_BASED: u8 = 0x04;
+
+const COUNTER_LEN: usize = 4;
+const MIC_LEN: usize = 4;
+/// Largest size of the objects, ids included.
+const OBJECTS_LEN: usize = 2 + 2 + 3 + 3 + 3;
+
+/// Object ids, with the number of bytes of their value.
+const PACKET_ID: (u8, usize) = (0x00, 1);
+const BATTERY: (u8, usize) = (0x01, 1);
+const TEMPERATURE: (u8, usize) = (0x02, 2);
+const HUMIDITY: (u8, usize) = (0x03, 2);
+const MOISTURE: (u8, usize) = (0x14, 2);
+
+/// Errors that can happen while decoding service data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The service data is cut short.
+    ErrLength,
+    /// The service data was encoded by another BTHome version, or variant.
+    ErrVersion(u8),
+    /// The service data is encrypted, and no key was given.
+    ErrEncrypted,
+    /// The service data was altered, or encrypted with another key.
+    ErrAuthentication,
+    /// An object this decoder does not know the size of, so cannot skip.
+    ErrObject(u8),
+}
+
+/// Readings carried by an advertisement, each one optional.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Measurement {
+    /// Increases with every advertisement, so receivers drop duplicates.
+    pub packet_id: Option<u8>,
+    /// Battery charge from 0 to 100.
+    pub battery: Option<u8>,
+    /// Temperature in hundredths of a degree Celsius.
+    pub temperature: Option<i16>,
+    /// Air humidity in hundredths of a percent.
+    pub humidity: Option<u16>,
+    /// Soil moisture in hundredths of a percent.
+    pub moisture: Option<u16>,
+}
+
+/// What it takes to encrypt a payload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Encryption {
+    /// The bind key shared with receivers.
+    pub key: [u8; KEY_LEN],
+    /// The device address, most significant byte first, as displayed.
+    pub mac: [u8; 6],
+    /// Must never repeat under the same key.
+    pub counter: u32,
+}
+
+/// Encoded service data, to follow the [`SERVICE_UUID`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Payload {
+    data: [u8; MAX_LEN],
+    len: usize,
+}
+
+impl Payload {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+impl Measurement {
+    /// Describes the latest readings of the device, the soil temperature being
+    /// known only when the sample was compensated.
+    pub fn new<S: Sensor>(
+        summary: Option<&Summary<S>>,
+        battery: Option<u8>,
+        packet_id: u8,
+    ) -> Self {
+        Self {
+            packet_id: Some(packet_id),
+            battery: battery.map(|battery| battery.min(100)),
+            temperature: summary.and_then(|summary| summary.compensation).map(|c| c.temperature),
+            humidity: None,
+            moisture: summary.map(|summary| u16::from_le_bytes(ess::humidity(summary))),
+        }
+    }
+}
+
+/// Encodes the measurement, encrypted when asked to.
+pub fn encode(measurement: &Measurement, encryption: Option<&Encryption>) -> Payload {
+    let mut data = [0u8; MAX_LEN];
+    let mut len = 1;
+    let mut push = |(id, size): (u8, usize), value: &[u8]| {
+        data[len] = id;
+        data[len + 1..len + 1 + size].copy_from_slice(&value[..size]);
+        len += 1 + size;
+    };
+    if let Some(packet_id) = measurement.packet_id {
+        push(PACKET_ID, &[packet_id]);
+    }
+    if let Some(battery) = measurement.battery {
+        push(BATTERY, &[battery]);
+    }
+    if let Some(temperature) = measurement.temperature {
+        push(TEMPERATURE, &temperature.to_le_bytes());
+    }
+    if let Some(humidity) = measurement.humidity {
+        push(HUMIDITY, &humidity.to_le_bytes());
+    }
+    if let Some(moisture) = measurement.moisture {
+        push(MOISTURE, &moisture.to_le_bytes());
+    }
+
+    data[0] = VERSION;
+    if let Some(encryption) = encryption {
+        data[0] |= ENCRYPTED;
+        let counter = encryption.counter.to_le_bytes();
+        let nonce = nonce(&encryption.mac, data[0], &counter);
+        let mic =
+            Aes128::new(&encryption.key).ccm_encrypt::<MIC_LEN>(&nonce, &[], &mut data[1..len]);
+        data[len..len + COUNTER_LEN].copy_from_slice(&counter);
+        data[len + COUNTER_LEN..len + COUNTER_LEN + MIC_LEN].copy_from_slice(&mic);
+        len += COUNTER_LEN + MIC_LEN;
+    }
+    Payload { data, len }
+}
+
+/// Decodes service data, given the bind key and the address of the device
+/// when encrypted.
+pub fn decode(
+    payload: &[u8],
+    key: Option<(&[u8; KEY_LEN], &[u8; 6])>,
+) -> Result<Measurement, Error> {
+    let (&info, rest) = payload.split_first().ok_or(Error::ErrLength)?;
+    if info & !(ENCRYPTED | TRIGGER_BASED) != VERSION {
+        return Err(Error::ErrVersion(info >> 5));
+    }
+
+    let mut objects = [0u8; OBJECTS_LEN];
+    let objects = if info & ENCRYPTED != 0 {
+        let (key, mac) = key.ok_or(Error::ErrEncrypted)?;
+        let len = rest.len().checked_sub(COUNTER_LEN + MIC_LEN).ok_or(Error::ErrLength)?;
+        let objects = objects.get_mut(..len).ok_or(Error::ErrLength)?;
+        objects.copy_from_slice(&rest[..len]);
+        let counter = rest[len..len + COUNTER_LEN].try_into().unwrap();
+        let mic = rest[len + COUNTER_LEN..].try_into().unwrap();
+        Aes128::new(key)
+            .ccm_decrypt::<MIC_LEN>(&nonce(mac, info, counter), &[], objects, mic)
+            .map_err(|_| Error::ErrAuthentication)?;
+        &objects[..]
+    } else {
+        rest
+    };
+
+    let mut measurement = Measurement::default();
+    let mut objects = objects;
+    while let Some((&id, rest)) = objects.split_first() {
+        let size = match id {
+            0x00 => PACKET_ID.1,
+            0x01 => BATTERY.1,
+            0x02 => TEMPERATURE.1,
+            0x03 => HUMIDITY.1,
+            0x14 => MOISTURE.1,
+            _ => return Err(Error::ErrObject(id)),
+        };
+        let value = rest.get(..size).ok_or(Error::ErrLength)?;
+        let word = || u16::from_le_bytes([value[0], value[1]]);
+        match id {
+            0x00 => measurement.packet_id = Some(value[0]),
+            0x01 => measurement.battery = Some(value[0]),
+            0x02 => measurement.temperature = Some(word() as i16),
+            0x03 => measurement.humidity = Some(word()),
+            _ => measurement.moisture = Some(word()),
+        }
+        objects = &rest[size..];
+    }
+    Ok(measurement)
+}
+
+/// Parses a bind key written as 32 hexadecimal digits, usable in constants.
+pub const fn parse_key(hex: &str) -> Option<[u8; KEY_LEN]> {
+    const fn digit(c: u8) -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'a'..=b'f' => Some(c - b'a' + 10),
+            b'A'..=b'F' => Some(c - b'A' + 10),
+            _ => None,
+        }
+    }
+
+    let hex = hex.as_bytes();
+    if hex.len() != KEY_LEN * 2 {
+        return None;
+    }
+    let mut key = [0u8; KEY_LEN];
+    let mut i = 0;
+    while i < KEY_LEN {
+        match (digit(hex[i * 2]), digit(hex[i * 2 + 1])) {
+            (Some(high), Some(low)) => key[i] = high << 4 | low,
+            _ => return None,
+        }
+        i += 1;
+    }
+    Some(key)
+}
+
+/// Builds the nonce: the address, the UUID, the device information and the counter.
+fn nonce(mac: &[u8; 6], info: u8, counter: &[u8; COUNTER_LEN]) -> [u8; NONCE] {
+    let mut nonce = [0u8; NONCE];
+    nonce[..6].copy_from_slice(mac);
+    nonce[6..8].copy_from_slice(&SERVICE_UUID.to_le_bytes());
+    nonce[8] = info;
+    nonce[9..].copy_from_slice(counter);
+    nonce
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sensors::{Compensation, Hygrometer};
+    use test_case::test_case;
+
+    /// Example of the BTHome encryption documentation.
+    const KEY: [u8; KEY_LEN] = match parse_key("231d39c1d7cc1ab1aee224cd096db932") {
+        Some(key) => key,
+        None => panic!(),
+    };
+    const MAC: [u8; 6] = [0x54, 0x48, 0xe6, 0x8f, 0x80, 0xa5];
+    const SPEC: Measurement = Measurement {
+        packet_id: None,
+        battery: None,
+        temperature: Some(2506),
+        humidity: Some(5055),
+        moisture: None,
+    };
+
+    #[test]
+    fn test_encode_spec_example() {
+        let encryption = Encryption { key: KEY, mac: MAC, counter: 0x3322_1100 };
+
+        let sut = encode(&SPEC, Some(&encryption));
+
+        assert_eq!(
+            [
+                0x41, 0xa4, 0x72, 0x66, 0xc9, 0x5f, 0x73, 0x00, 0x11, 0x22, 0x33, 0x78, 0x23, 0x72,
+                0x14
+            ],
+            sut.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_encode_plain() {
+        let sut = encode(&SPEC, None);
+
+        assert_eq!([0x40, 0x02, 0xca, 0x09, 0x03, 0xbf, 0x13], sut.as_bytes());
+    }
+
+    #[test]
+    fn test_encode_in_id_order() {
+        let measurement = Measurement {
+            packet_id: Some(9),
+            battery: Some(87),
+            temperature: Some(-150),
+            humidity: None,
+            moisture: Some(6000),
+        };
+
+        let sut = encode(&measurement, None);
+
+        assert_eq!(
+            [0x40, 0x00, 0x09, 0x01, 0x57, 0x02, 0x6a, 0xff, 0x14, 0x70, 0x17],
+            sut.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_largest() {
+        let measurement = Measurement {
+            packet_id: Some(u8::MAX),
+            battery: Some(100),
+            temperature: Some(i16::MIN),
+            humidity: Some(u16::MAX),
+            moisture: Some(u16::MAX),
+        };
+        let encryption = Encryption { key: KEY, mac: MAC, counter: u32::MAX };
+
+        let sut = encode(&measurement, Some(&encryption));
+
+        assert_eq!(MAX_LEN, sut.as_bytes().len());
+        assert_eq!(Ok(measurement), decode(sut.as_bytes(), Some((&KEY, &MAC))));
+    }
+
+    #[test]
+    fn test_decode_spec_example() {
+        let payload = [
+            0x41, 0xa4, 0x72, 0x66, 0xc9, 0x5f, 0x73, 0x00, 0x11, 0x22, 0x33, 0x78, 0x23, 0x72,
+            0x14,
+        ];
+
+        assert_eq!(Ok(SPEC), decode(&payload, Some((&KEY, &MAC))));
+    }
+
+    #[test_case(&[], None, Error::ErrLength; "empty")]
+    #[test_case(&[0x20, 0x01, 0x50], None, Error::ErrVersion(1); "version 1")]
+    #[test_case(&[0x40, 0x02, 0xca], None, Error::ErrLength; "truncated object")]
+    #[test_case(&[0x40, 0x2e, 0x50], None, Error::ErrObject(0x2e); "unknown object")]
+    #[test_case(&[0x41, 0xa4, 0x00, 0x11, 0x22, 0x33, 0x78], Some((&KEY, &MAC)), Error::ErrLength; "truncated mic")]
+    #[test_case(&[0x41, 0xa4, 0x72, 0x66, 0xc9, 0x5f, 0x73, 0x00, 0x11, 0x22, 0x33, 0x78, 0x23, 0x72, 0x14], None, Error::ErrEncrypted; "no key")]
+    #[test_case(&[0x41, 0xa4, 0x72, 0x66, 0xc9, 0x5f, 0x73, 0x00, 0x11, 0x22, 0x34, 0x78, 0x23, 0x72, 0x14], Some((&KEY, &MAC)), Error::ErrAuthentication; "replayed counter")]
+    #[test_case(&[0x41, 0xa4, 0x72, 0x66, 0xc9, 0x5f, 0x73, 0x00, 0x11, 0x22, 0x33, 0x78, 0x23, 0x72, 0x14], Some((&KEY, &[0; 6])), Error::ErrAuthentication; "other device")]
+    fn test_decode_errors(
+        payload: &[u8],
+        key: Option<(&[u8; KEY_LEN], &[u8; 6])>,
+        expected: Error,
+    ) {
+        assert_eq!(Err(expected), decode(payload, key));
+    }
+
+    #[test]
+    fn test_measurement() {
+        let summary = Summary {
+            n: 64,
+            avg: 1450,
+            min: 1440,
+            max: 1460,
+            sensor: Hygrometer::HW390,
+            compensation: Some(Compensation { temperature: 3000, coefficient: 3 }),
+        };
+
+        let sut = Measurement::new(Some(&summary), Some(120), 3);
+
+        assert_eq!(Some(3), sut.packet_id);
+        assert_eq!(Some(100), sut.battery);
+        assert_eq!(Some(3000), sut.temperature);
+        assert_eq!(Some(6000), sut.moisture);
+        assert_eq!(None, sut.humidity);
+    }
+
+    #[test]
+    fn test_measurement_without_sample() {
+        let sut = Measurement::new::<Hygrometer>(None, None, 0);
+
+        assert_eq!(Measurement { packet_id: Some(0), ..Measurement::default() }, sut);
+    }
+
+    #[test_case("231d39c1d7cc1ab1aee224cd096db932", Some(KEY); "lowercase")]
+    #[test_case("231D39C1D7CC1AB1AEE224CD096DB932", Some(KEY); "uppercase")]
+    #[test_case("231d39c1d7cc1ab1aee224cd096db9", None; "short")]
+    #[test_case("231d39c1d7cc1ab1aee224cd096db93g", None; "not hexadecimal")]
+    fn test_parse_key(hex: &str, expected: Option<[u8; KEY_LEN]>) {
+        assert_eq!(expected, parse_key(hex));
+    }
+}
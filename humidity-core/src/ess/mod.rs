@@ -0,0 +1,188 @@
+//! # Environmental Sensing Service
+//!
+//! Encodes sampling results as the Bluetooth SIG Environmental Sensing Service
+//! does, so generic BLE apps understand the device without knowing about the
+//! custom [`Summary`] format.
+//!
+//! The soil moisture is exposed through the [`HUMIDITY`] characteristic, in
+//! hundredths of a percent, and described by an [`EsMeasurement`] descriptor
+//! telling it is the arithmetic mean of several readings of the soil, taken at
+//! a regular interval.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::{ess, sample::Summary, sensors::Hygrometer};
+//! let summary = Summary {
+//!     n: 64,
+//!     avg: 1420,
+//!     min: 1410,
+//!     max: 1431,
+//!     sensor: Hygrometer::HW390,
+//!     compensation: None,
+//! };
+//! let value = ess::humidity(&summary);
+//! let descriptor = ess::EsMeasurement::new(&summary, 6, 15 * 60).encode();
+//! println!("humidity: {value:02x?} described by {descriptor:02x?}");
+//! ```
+
+use crate::{sample::Summary, sensors::Sensor};
+
+/// Environmental Sensing Service.
+pub const SERVICE: u16 = 0x181A;
+/// Humidity characteristic, a `u16` in hundredths of a percent.
+pub const HUMIDITY: u16 = 0x2A6F;
+/// Environmental Sensing Measurement descriptor.
+pub const ES_MEASUREMENT: u16 = 0x290C;
+
+/// Encoded size of the [`EsMeasurement`] descriptor.
+pub const ES_MEASUREMENT_LEN: usize = 11;
+
+/// How the exposed value was computed from the readings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Sampling {
+    Unspecified = 0x00,
+    Instantaneous = 0x01,
+    ArithmeticMean = 0x02,
+    Rms = 0x03,
+    Maximum = 0x04,
+    Minimum = 0x05,
+    Accumulated = 0x06,
+    Count = 0x07,
+}
+
+/// What the measured value applies to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Application {
+    Unspecified = 0x00,
+    Air = 0x01,
+    Water = 0x02,
+    Barometric = 0x03,
+    Soil = 0x04,
+}
+
+/// The Environmental Sensing Measurement descriptor of a characteristic.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EsMeasurement {
+    pub sampling: Sampling,
+    /// Time the readings were taken over, in seconds, 0 when unknown.
+    pub measurement_period: u32,
+    /// Time between two updates of the value, in seconds, 0 when unknown.
+    pub update_interval: u32,
+    pub application: Application,
+    /// Uncertainty of the value, in steps of 0.5 %, `0xFF` when unknown.
+    pub uncertainty: u8,
+}
+
+/// Returns the value of the [`HUMIDITY`] characteristic for the soil moisture
+/// of the summary, in hundredths of a percent.
+pub fn humidity<S: Sensor>(summary: &Summary<S>) -> [u8; 2] {
+    let value = ((1.0 - summary.percentage()) * 10_000.0 + 0.5) as u16;
+    value.to_le_bytes()
+}
+
+impl EsMeasurement {
+    /// Describes the soil moisture of a summary, whose readings took the given
+    /// time, updated every `update_interval` seconds. The uncertainty is the
+    /// spread of the readings.
+    pub fn new<S: Sensor>(
+        summary: &Summary<S>,
+        measurement_period: u32,
+        update_interval: u32,
+    ) -> Self {
+        let span = summary.sensor.high().saturating_sub(summary.sensor.low()).max(1) as u32;
+        let spread = summary.max.saturating_sub(summary.min) as u32;
+        // Half the spread as a percentage, in steps of 0.5 %.
+        let uncertainty = (spread * 100 / span).min(0xFE) as u8;
+        Self {
+            sampling: if summary.n > 1 {
+                Sampling::ArithmeticMean
+            } else {
+                Sampling::Instantaneous
+            },
+            measurement_period,
+            update_interval,
+            application: Application::Soil,
+            uncertainty,
+        }
+    }
+
+    /// Encodes the descriptor, flags first, all fields little endian.
+    pub fn encode(&self) -> [u8; ES_MEASUREMENT_LEN] {
+        let mut data = [0u8; ES_MEASUREMENT_LEN];
+        // Flags are all reserved.
+        data[2] = self.sampling as u8;
+        data[3..6].copy_from_slice(&self.measurement_period.min(0xFF_FFFF).to_le_bytes()[..3]);
+        data[6..9].copy_from_slice(&self.update_interval.min(0xFF_FFFF).to_le_bytes()[..3]);
+        data[9] = self.application as u8;
+        data[10] = self.uncertainty;
+        data
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sensors::{Compensation, Hygrometer};
+    use test_case::test_case;
+
+    fn summary(n: u8, avg: u16, min: u16, max: u16) -> Summary<Hygrometer> {
+        Summary { n, avg, min, max, sensor: Hygrometer::HW390, compensation: None }
+    }
+
+    #[test_case(1000, [0x10, 0x27]; "wet")]
+    #[test_case(2050, [0x00, 0x00]; "dry")]
+    #[test_case(1420, [0x70, 0x17]; "60 percent")]
+    #[test_case(900, [0x10, 0x27]; "clamped")]
+    fn test_humidity(avg: u16, expected: [u8; 2]) {
+        let sut = summary(1, avg, avg, avg);
+        assert_eq!(expected, humidity(&sut));
+    }
+
+    #[test]
+    fn test_humidity_is_compensated() {
+        let mut sut = summary(1, 1450, 1450, 1450);
+        sut.compensation = Some(Compensation { temperature: 3000, coefficient: 3 });
+
+        assert_eq!(6000u16.to_le_bytes(), humidity(&sut));
+    }
+
+    #[test]
+    fn test_es_measurement() {
+        let sut = EsMeasurement::new(&summary(64, 1420, 1410, 1431), 6, 15 * 60);
+
+        assert_eq!(Sampling::ArithmeticMean, sut.sampling);
+        assert_eq!(Application::Soil, sut.application);
+        assert_eq!(2, sut.uncertainty);
+        assert_eq!(
+            [0x00, 0x00, 0x02, 0x06, 0x00, 0x00, 0x84, 0x03, 0x00, 0x04, 0x02],
+            sut.encode()
+        );
+    }
+
+    #[test]
+    fn test_es_measurement_single_reading() {
+        let sut = EsMeasurement::new(&summary(1, 1420, 1420, 1420), 0, 0);
+
+        assert_eq!(Sampling::Instantaneous, sut.sampling);
+        assert_eq!(0, sut.uncertainty);
+    }
+
+    #[test]
+    fn test_es_measurement_saturates() {
+        let sut = EsMeasurement {
+            sampling: Sampling::Unspecified,
+            measurement_period: u32::MAX,
+            update_interval: 0x01_0203,
+            application: Application::Air,
+            uncertainty: 0xFF,
+        };
+
+        assert_eq!(
+            [0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x03, 0x02, 0x01, 0x01, 0xFF],
+            sut.encode()
+        );
+    }
+}
@@ -28,7 +28,35 @@ where
         self.elements[self.next()] = Some(elem);
     }
 
-    pub fn sync(&self) -> Syncer<T> {
+    /// Returns the number of elements kept.
+    pub fn len(&self) -> usize {
+        match self.elements.last() {
+            Some(Some(_)) => SIZE,
+            _ => self.len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an element by its position, the oldest one kept first.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len() {
+            return None;
+        }
+        // Once full, the oldest element is the next one to be overwritten.
+        let oldest = if self.len() == SIZE { self.len } else { 0 };
+        self.elements[(oldest + index) % SIZE].as_ref()
+    }
+
+    /// Drops every element.
+    pub fn clear(&mut self) {
+        self.elements = [Self::EMPTY; SIZE];
+        self.len = 0;
+    }
+
+    pub fn sync(&self) -> Syncer<'_, T> {
         Syncer::new(&self.elements[..self.len])
     }
 
@@ -67,3 +95,48 @@ where
         Ok(0)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serde::Serializer;
+
+    #[derive(Debug, PartialEq)]
+    struct Elem(u8);
+
+    impl serde::Serializable for Elem {
+        fn serialize(&self, ser: &mut Serializer) -> Result<usize, serde::Error> {
+            ser.write_u8(self.0)
+        }
+    }
+
+    #[test]
+    fn test_get_oldest_first() {
+        let mut sut = Historical::<3, Elem>::new();
+        assert!(sut.is_empty());
+
+        sut.store(Elem(1));
+        sut.store(Elem(2));
+        assert_eq!(2, sut.len());
+        assert_eq!(Some(&Elem(1)), sut.get(0));
+        assert_eq!(None, sut.get(2));
+
+        sut.store(Elem(3));
+        sut.store(Elem(4));
+        assert_eq!(3, sut.len());
+        assert_eq!([Some(&Elem(2)), Some(&Elem(3)), Some(&Elem(4))], [0, 1, 2].map(|i| sut.get(i)));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut sut = Historical::<3, Elem>::new();
+        sut.store(Elem(1));
+
+        sut.clear();
+
+        assert!(sut.is_empty());
+        assert_eq!(None, sut.get(0));
+        let mut out = [0u8; 1];
+        assert_eq!(Ok(0), sut.sync().write(&mut out));
+    }
+}
@@ -0,0 +1,254 @@
+//! # Irrigation
+//!
+//! Decides when to water a plant, given successive sampling results and a
+//! watering [`Profile`]. The [`Irrigator`] is a small state machine which holds
+//! no references to hardware, the firmware is responsible for driving the pump
+//! or valve as instructed by each [`Decision`].
+//!
+//! Watering starts once moisture drops below the dry threshold, and is done in
+//! pulses, separated by at least the minimum interval so water has time to soak
+//! in, until moisture gets within the hysteresis of the target. Stopping short
+//! of the target avoids overshooting, as water keeps spreading through the soil
+//! after the pump stops.
+//!
+//! Decisions are then checked by the [`Interlock`] against hard [`Limits`] on
+//! pump run time, daily water budget and pump cool-down, which also halts
+//! watering with a [`Fault`] when pulses have no effect on moisture.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::{
+//!     irrigation::{Irrigator, Profile},
+//!     sample::Summary,
+//!     sensors::Hygrometer,
+//! };
+//! let profile = Profile { dry: 35, target: 60, hysteresis: 5, min_interval: 1800, pulse_ms: 3000 };
+//! let summary = Summary {
+//!     n: 1,
+//!     avg: 1900,
+//!     min: 1900,
+//!     max: 1900,
+//!     sensor: Hygrometer::HW390,
+//!     compensation: None,
+//! };
+//! let mut irrigator = Irrigator::new();
+//! let decision = irrigator.decide(&profile, &summary, 0);
+//! println!("decision: {decision:?}");
+//! // Once the pump ran.
+//! irrigator.watered(0);
+//! ```
+
+pub use safety::{Fault, Interlock, Limits};
+
+use crate::{sample::Summary, sensors::Sensor};
+
+mod safety;
+
+/// Moisture thresholds and timings to water a plant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Profile {
+    /// Moisture below which the plant needs watering, from 0 to 100.
+    pub dry: u8,
+    /// Moisture to reach when watering, from 0 to 100.
+    pub target: u8,
+    /// Margin below the target at which watering is considered done.
+    pub hysteresis: u8,
+    /// Minimum time between two waterings, in seconds.
+    pub min_interval: u32,
+    /// How long to run the pump on each watering, in milliseconds.
+    pub pulse_ms: u32,
+}
+
+/// What the firmware should do with the pump.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    /// Leave the pump off.
+    Idle,
+    /// Run the pump for the given time, in milliseconds.
+    Water(u32),
+}
+
+/// Why a [`Command`] was issued.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reason {
+    /// Moisture is above the dry threshold.
+    Moist,
+    /// Moisture dropped below the dry threshold.
+    Dry,
+    /// Watering is in progress but moisture is still below the target.
+    BelowTarget,
+    /// Moisture got close enough to the target.
+    TargetReached,
+    /// Watering is needed, but the last one was too recent.
+    TooSoon,
+    /// Watering is needed, but the pump is resting after the last run.
+    CoolingDown,
+}
+
+/// The outcome of evaluating a sampling result.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub command: Command,
+    pub reason: Reason,
+}
+
+impl Decision {
+    const fn idle(reason: Reason) -> Self {
+        Self { command: Command::Idle, reason }
+    }
+}
+
+/// Whether the soil is being brought back to the target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    Idle,
+    Watering,
+}
+
+/// Watering state machine, meant to be kept across deep sleeps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Irrigator {
+    state: State,
+    last_watering: Option<u32>,
+}
+
+impl Default for Irrigator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Irrigator {
+    pub const fn new() -> Self {
+        Self { state: State::Idle, last_watering: None }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Evaluates a sampling result taken at `now`, in seconds, and returns what
+    /// to do with the pump. Pulses only count once [`watered`](Self::watered),
+    /// as the [`Interlock`] may still hold them back.
+    pub fn decide<S: Sensor>(
+        &mut self,
+        profile: &Profile,
+        summary: &Summary<S>,
+        now: u32,
+    ) -> Decision {
+        let moisture = summary.moisture();
+        let reason = match self.state {
+            State::Idle if moisture < profile.dry => {
+                self.state = State::Watering;
+                Reason::Dry
+            }
+            State::Idle => return Decision::idle(Reason::Moist),
+            State::Watering if moisture.saturating_add(profile.hysteresis) >= profile.target => {
+                self.state = State::Idle;
+                return Decision::idle(Reason::TargetReached);
+            }
+            State::Watering => Reason::BelowTarget,
+        };
+
+        if let Some(last_watering) = self.last_watering {
+            if now.wrapping_sub(last_watering) < profile.min_interval {
+                return Decision::idle(Reason::TooSoon);
+            }
+        }
+        Decision { command: Command::Water(profile.pulse_ms), reason }
+    }
+
+    /// Records a pulse that ran at `now`, in seconds, from which the minimum
+    /// interval starts.
+    pub fn watered(&mut self, now: u32) {
+        self.last_watering = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sensors::Hygrometer;
+
+    const PROFILE: Profile =
+        Profile { dry: 35, target: 60, hysteresis: 5, min_interval: 600, pulse_ms: 3000 };
+
+    fn summary(moisture: u32) -> Summary<Hygrometer> {
+        let sensor = Hygrometer::HW390;
+        let avg = sensor.high() - (moisture * (sensor.high() - sensor.low()) as u32 / 100) as u16;
+        Summary { n: 1, avg, min: avg, max: avg, sensor, compensation: None }
+    }
+
+    fn water(reason: Reason) -> Decision {
+        Decision { command: Command::Water(PROFILE.pulse_ms), reason }
+    }
+
+    /// Decides, and runs the pump as told.
+    fn decide(sut: &mut Irrigator, moisture: u32, now: u32) -> Decision {
+        let decision = sut.decide(&PROFILE, &summary(moisture), now);
+        if decision.command != Command::Idle {
+            sut.watered(now);
+        }
+        decision
+    }
+
+    #[test]
+    fn test_moist_soil_is_left_alone() {
+        let mut sut = Irrigator::new();
+
+        assert_eq!(Decision::idle(Reason::Moist), decide(&mut sut, 50, 0));
+        assert_eq!(Decision::idle(Reason::Moist), decide(&mut sut, 35, 300));
+        assert_eq!(State::Idle, sut.state());
+    }
+
+    #[test]
+    fn test_waters_until_target() {
+        let mut sut = Irrigator::new();
+
+        assert_eq!(water(Reason::Dry), decide(&mut sut, 30, 0));
+        assert_eq!(water(Reason::BelowTarget), decide(&mut sut, 45, 600));
+        assert_eq!(water(Reason::BelowTarget), decide(&mut sut, 54, 1200));
+        assert_eq!(Decision::idle(Reason::TargetReached), decide(&mut sut, 55, 1800));
+        assert_eq!(State::Idle, sut.state());
+    }
+
+    #[test]
+    fn test_idle_until_dry_again() {
+        let mut sut = Irrigator::new();
+
+        decide(&mut sut, 30, 0);
+        decide(&mut sut, 57, 600);
+        assert_eq!(Decision::idle(Reason::Moist), decide(&mut sut, 50, 1200));
+        assert_eq!(Decision::idle(Reason::Moist), decide(&mut sut, 36, 1800));
+        assert_eq!(water(Reason::Dry), decide(&mut sut, 34, 2400));
+    }
+
+    #[test]
+    fn test_minimum_interval_between_waterings() {
+        let mut sut = Irrigator::new();
+
+        assert_eq!(water(Reason::Dry), decide(&mut sut, 30, 1000));
+        assert_eq!(Decision::idle(Reason::TooSoon), decide(&mut sut, 31, 1300));
+        assert_eq!(State::Watering, sut.state());
+        assert_eq!(water(Reason::BelowTarget), decide(&mut sut, 40, 1600));
+    }
+
+    #[test]
+    fn test_minimum_interval_across_cycles() {
+        let mut sut = Irrigator::new();
+
+        decide(&mut sut, 30, 0);
+        decide(&mut sut, 60, 300);
+        assert_eq!(Decision::idle(Reason::TooSoon), decide(&mut sut, 20, 500));
+        assert_eq!(water(Reason::BelowTarget), decide(&mut sut, 20, 600));
+    }
+
+    #[test]
+    fn test_held_back_pulse_does_not_count() {
+        let mut sut = Irrigator::new();
+
+        assert_eq!(water(Reason::Dry), sut.decide(&PROFILE, &summary(30), 1000));
+        assert_eq!(water(Reason::BelowTarget), sut.decide(&PROFILE, &summary(30), 1300));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::serde::{self, Deserializable, Serializable};
+
+use super::{Command, Decision, Reason};
+
+const DAY: u32 = 24 * 60 * 60;
+
+/// Hard limits the pump must never exceed, regardless of what the
+/// [`super::Irrigator`] asks for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// Maximum time the pump may run on a single watering, in milliseconds.
+    pub max_run_ms: u32,
+    /// Maximum amount of water to pour in a day, in millilitres.
+    pub daily_budget_ml: u32,
+    /// Pump flow rate, in millilitres per minute.
+    pub flow_ml_per_min: u32,
+    /// Minimum rest time for the pump between two runs, in seconds.
+    pub cooldown: u32,
+    /// Consecutive pulses without moisture rising before halting.
+    pub no_effect_pulses: u8,
+    /// Minimum moisture rise for a pulse to be considered effective.
+    pub min_rise: u8,
+}
+
+impl Limits {
+    fn millilitres(&self, ms: u32) -> u32 {
+        (ms as u64 * self.flow_ml_per_min as u64 / 60_000) as u32
+    }
+
+    fn millis(&self, ml: u32) -> u32 {
+        (ml as u64 * 60_000 / self.flow_ml_per_min.max(1) as u64).min(u32::MAX as u64) as u32
+    }
+}
+
+/// Conditions which prevent watering until they are cleared.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Fault {
+    /// The daily water budget has been used up.
+    BudgetExhausted,
+    /// Moisture did not rise after several pulses, the reservoir is likely
+    /// empty or the tubing disconnected. Requires a manual [`Interlock::reset`],
+    /// which clients request over RPC.
+    NoEffect,
+}
+
+/// Enforces [`Limits`] on watering decisions and keeps track of the water used.
+/// Meant to be kept across deep sleeps, next to the [`super::Irrigator`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interlock {
+    day_start: u32,
+    used_ml: u32,
+    last_run: Option<u32>,
+    moisture_at_last_run: Option<u8>,
+    ineffective_pulses: u8,
+    halted: bool,
+}
+
+impl Default for Interlock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interlock {
+    pub const fn new() -> Self {
+        Self {
+            day_start: 0,
+            used_ml: 0,
+            last_run: None,
+            moisture_at_last_run: None,
+            ineffective_pulses: 0,
+            halted: false,
+        }
+    }
+
+    /// Water poured since the start of the current day, in millilitres.
+    pub fn used_ml(&self) -> u32 {
+        self.used_ml
+    }
+
+    /// Clears a [`Fault::NoEffect`] halt, once the reservoir has been refilled.
+    pub fn reset(&mut self) {
+        self.halted = false;
+        self.ineffective_pulses = 0;
+        self.moisture_at_last_run = None;
+    }
+
+    /// Checks a decision against the limits, given the current moisture and the
+    /// time, in seconds. Returns the decision to carry out, which may run the
+    /// pump for less time than requested, or the fault preventing watering.
+    pub fn guard(
+        &mut self,
+        limits: &Limits,
+        decision: Decision,
+        moisture: u8,
+        now: u32,
+    ) -> Result<Decision, Fault> {
+        self.observe(limits, moisture);
+        if self.halted {
+            return Err(Fault::NoEffect);
+        }
+        if now.wrapping_sub(self.day_start) >= DAY {
+            self.day_start = now;
+            self.used_ml = 0;
+        }
+
+        let Command::Water(pulse_ms) = decision.command else {
+            return Ok(decision);
+        };
+        if let Some(last_run) = self.last_run {
+            if now.wrapping_sub(last_run) < limits.cooldown {
+                return Ok(Decision::idle(Reason::CoolingDown));
+            }
+        }
+
+        let remaining_ml = limits.daily_budget_ml.saturating_sub(self.used_ml);
+        let pulse_ms = pulse_ms.min(limits.max_run_ms).min(limits.millis(remaining_ml));
+        if pulse_ms == 0 {
+            return Err(Fault::BudgetExhausted);
+        }
+
+        self.used_ml += limits.millilitres(pulse_ms);
+        self.last_run = Some(now);
+        self.moisture_at_last_run = Some(moisture);
+        Ok(Decision { command: Command::Water(pulse_ms), reason: decision.reason })
+    }
+
+    /// Checks whether the last pulse made any difference.
+    fn observe(&mut self, limits: &Limits, moisture: u8) {
+        let Some(before) = self.moisture_at_last_run.take() else {
+            return;
+        };
+        if moisture >= before.saturating_add(limits.min_rise) {
+            self.ineffective_pulses = 0;
+        } else {
+            self.ineffective_pulses = self.ineffective_pulses.saturating_add(1);
+            self.halted = self.ineffective_pulses >= limits.no_effect_pulses;
+        }
+    }
+}
+
+impl Serializable for Fault {
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        ser.write_u8(*self as u8)
+    }
+}
+
+impl Deserializable for Fault {
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        match de.read_u8()? {
+            0 => Ok(Self::BudgetExhausted),
+            1 => Ok(Self::NoEffect),
+            _ => Err(serde::Error::Other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LIMITS: Limits = Limits {
+        max_run_ms: 5000,
+        daily_budget_ml: 100,
+        flow_ml_per_min: 600,
+        cooldown: 300,
+        no_effect_pulses: 3,
+        min_rise: 2,
+    };
+
+    fn water(ms: u32) -> Decision {
+        Decision { command: Command::Water(ms), reason: Reason::Dry }
+    }
+
+    #[test]
+    fn test_idle_decisions_pass_through() {
+        let mut sut = Interlock::new();
+        let decision = Decision::idle(Reason::Moist);
+
+        assert_eq!(Ok(decision), sut.guard(&LIMITS, decision, 50, 0));
+    }
+
+    #[test]
+    fn test_max_run_time() {
+        let mut sut = Interlock::new();
+
+        assert_eq!(Ok(water(5000)), sut.guard(&LIMITS, water(20000), 30, 0));
+        assert_eq!(50, sut.used_ml());
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let mut sut = Interlock::new();
+
+        sut.guard(&LIMITS, water(1000), 30, 0).unwrap();
+        assert_eq!(
+            Ok(Decision::idle(Reason::CoolingDown)),
+            sut.guard(&LIMITS, water(1000), 35, 299)
+        );
+        assert_eq!(Ok(water(1000)), sut.guard(&LIMITS, water(1000), 35, 300));
+    }
+
+    #[test]
+    fn test_daily_budget() {
+        let mut sut = Interlock::new();
+
+        assert_eq!(Ok(water(5000)), sut.guard(&LIMITS, water(5000), 30, 0));
+        assert_eq!(Ok(water(4000)), sut.guard(&LIMITS, water(4000), 35, 300));
+        assert_eq!(Ok(water(1000)), sut.guard(&LIMITS, water(5000), 40, 600));
+        assert_eq!(Err(Fault::BudgetExhausted), sut.guard(&LIMITS, water(5000), 45, 900));
+        assert_eq!(100, sut.used_ml());
+
+        assert_eq!(Ok(water(5000)), sut.guard(&LIMITS, water(5000), 45, DAY));
+        assert_eq!(50, sut.used_ml());
+    }
+
+    #[test]
+    fn test_halts_when_moisture_does_not_rise() {
+        let mut sut = Interlock::new();
+
+        sut.guard(&LIMITS, water(100), 30, 0).unwrap();
+        sut.guard(&LIMITS, water(100), 31, 300).unwrap();
+        sut.guard(&LIMITS, water(100), 30, 600).unwrap();
+        assert_eq!(Err(Fault::NoEffect), sut.guard(&LIMITS, water(100), 31, 900));
+        assert_eq!(
+            Err(Fault::NoEffect),
+            sut.guard(&LIMITS, Decision::idle(Reason::Moist), 60, 1200)
+        );
+
+        sut.reset();
+        assert_eq!(Ok(water(100)), sut.guard(&LIMITS, water(100), 31, 1500));
+    }
+
+    #[test]
+    fn test_effective_pulse_clears_count() {
+        let mut sut = Interlock::new();
+
+        sut.guard(&LIMITS, water(100), 30, 0).unwrap();
+        sut.guard(&LIMITS, water(100), 30, 300).unwrap();
+        sut.guard(&LIMITS, water(100), 33, 600).unwrap();
+        sut.guard(&LIMITS, water(100), 33, 900).unwrap();
+        assert_eq!(Ok(water(100)), sut.guard(&LIMITS, water(100), 33, 1200));
+    }
+}
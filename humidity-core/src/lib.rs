@@ -9,8 +9,24 @@
 //!
 #![no_std]
 
+pub mod advertising;
+pub mod battery;
+pub mod bthome;
+pub mod ess;
 pub mod historical;
+pub mod irrigation;
+pub mod lifecycle;
+pub mod pattern;
+pub mod plant;
+pub mod power;
+pub mod retained;
+pub mod rpc;
 pub mod sample;
+pub mod schedule;
 pub mod sensors;
 pub mod serde;
+pub mod settings;
 pub mod shared;
+pub mod simulation;
+pub mod time;
+pub mod wake;
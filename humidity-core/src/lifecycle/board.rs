@@ -0,0 +1,163 @@
+use crate::{
+    advertising::Reason,
+    battery::Battery,
+    historical::Historical,
+    irrigation::{Decision, Fault},
+    pattern::Pattern,
+    rpc::Commands,
+    sample::{Record, Rollup, Summary},
+    schedule::{Clock, Jobs},
+    sensors::Sensor,
+    shared::Broadcast,
+    time::{Times, WallClock},
+    wake::Cause,
+};
+
+use super::HISTORY_LEN;
+
+/// Hardware hooks the [`Lifecycle`](super::Lifecycle) drives. The clock gives
+/// the time in seconds, and must keep counting during deep sleep.
+pub trait Board: Clock {
+    type Sensor: Sensor;
+    type Error;
+
+    /// Returns what woke the device up.
+    fn wake_cause(&mut self) -> Cause;
+
+    /// Plays an alarm pattern.
+    fn beep(&mut self, pattern: Pattern);
+
+    /// Measures the battery.
+    fn read_battery(&mut self) -> Result<Battery, Self::Error>;
+
+    /// Powers the sensor up, samples it, and powers it down.
+    fn sample(&mut self) -> Result<Summary<Self::Sensor>, Self::Error>;
+
+    /// Runs the pump for the given time, in milliseconds.
+    fn water(&mut self, pulse_ms: u32);
+
+    /// Brings up the radio, advertises the broadcast of the content for the
+    /// given time, in milliseconds, and serves the content to a client if one
+    /// connects. Boards able to notify the client sample again whenever
+    /// [`Content::next_sample`] elapses while it stays connected, then every
+    /// [`Content::interval`], notify the readings and their records to it, and
+    /// keep the records in [`Served::live`] until that is full.
+    fn serve(
+        &mut self,
+        window_ms: u32,
+        content: &Content<'_, Self::Sensor>,
+    ) -> Result<Served<Self::Sensor>, Self::Error>;
+
+    /// Notifies of progress through the cycle, typically to log it.
+    fn report(&mut self, _event: Event<Self::Sensor, Self::Error>) {}
+}
+
+/// What the device exposes to clients.
+pub struct Content<'a, S: Sensor> {
+    pub history: &'a Historical<HISTORY_LEN, Record<S>>,
+    /// Time of the clock each record of the history was stored at.
+    pub stamps: &'a Historical<HISTORY_LEN, u32>,
+    /// Time set by clients so far.
+    pub clock: WallClock,
+    pub last_sample: Option<Summary<S>>,
+    pub battery: Option<Battery>,
+    /// Latest readings, for clients not connecting.
+    pub broadcast: Broadcast,
+    /// Advertisements so far, counting this one. Kept across deep sleeps but
+    /// not power losses.
+    pub sequence: u32,
+    /// Seconds until the next sampling is due.
+    pub next_sample: u32,
+    /// Seconds between samplings, as last picked for the conditions.
+    pub interval: u32,
+}
+
+impl<'a, S: Sensor> Content<'a, S> {
+    /// Dates the records of the history, for clients to read.
+    pub fn times(&self) -> Times<'a, HISTORY_LEN> {
+        Times::new(self.stamps, self.clock)
+    }
+}
+
+/// Outcome of an advertising window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Served<S: Sensor> {
+    /// Whether a client connected.
+    pub connected: bool,
+    /// Whether a client read the whole history.
+    pub synced: bool,
+    /// Commands requested by the client, to carry out before sleeping.
+    pub commands: Commands,
+    /// Records of the readings taken while the client stayed connected,
+    /// already notified to it, to store once it disconnects.
+    pub live: Live<S>,
+}
+
+impl<S: Sensor> Default for Served<S> {
+    fn default() -> Self {
+        Self { connected: false, synced: false, commands: Commands::default(), live: Live::new() }
+    }
+}
+
+/// Readings kept from a single connection. Boards stop sampling once that many
+/// were taken, until the client reconnects.
+pub const LIVE_LEN: usize = 8;
+
+/// Records of the readings taken while a client stays connected, along the
+/// time of the clock each was taken at, oldest first. They are stored as they
+/// are, so notifying them tells the client what the history will hold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Live<S: Sensor> {
+    records: [Option<(u32, Record<S>)>; LIVE_LEN],
+    len: usize,
+}
+
+impl<S: Sensor> Default for Live<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Sensor> Live<S> {
+    const EMPTY: Option<(u32, Record<S>)> = Option::None;
+
+    pub const fn new() -> Self {
+        Self { records: [Self::EMPTY; LIVE_LEN], len: 0 }
+    }
+
+    /// Keeps a record taken at the given time, or returns it back once full.
+    pub fn push(&mut self, at: u32, record: Record<S>) -> Result<(), Record<S>> {
+        match self.records.get_mut(self.len) {
+            Some(slot) => {
+                *slot = Some((at, record));
+                self.len += 1;
+                Ok(())
+            }
+            None => Err(record),
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == LIVE_LEN
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(u32, Record<S>)> {
+        self.records[..self.len].iter().flatten()
+    }
+}
+
+/// Progress through the cycle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event<S: Sensor, E> {
+    Woken(Cause, Jobs),
+    Battery(Battery),
+    BatteryError(E),
+    Sampled(Summary<S>),
+    RolledUp(Rollup),
+    SensorError(E),
+    Irrigation(Result<Decision, Fault>),
+    Advertising(Reason),
+    Served(Served<S>),
+    RadioError(E),
+    Sleeping(u32),
+}
@@ -0,0 +1,990 @@
+//! # Device lifecycle
+//!
+//! Every wake goes through the same stages: find out why the device woke up,
+//! check the battery, sample the soil and water it, advertise and serve
+//! clients, then go back to sleep. [`Lifecycle`] walks through those
+//! [`Stage`]s, deciding what to do with the rest of this crate, and leaves the
+//! hardware to a [`Board`]. Firmwares only implement the board for their chip,
+//! keep the [`State`] in retained memory, and sleep for as long as the
+//! lifecycle says.
+//!
+//! Readings a board takes while a client stays connected past the next
+//! sampling are stored once it disconnects, dated when they were taken, but
+//! neither water the plant nor move the next sampling. Commands a client sends
+//! while connected are carried out then too: clearing
+//! the history, setting the time, sampling again before going to sleep, or
+//! rebooting, which sleeps as briefly as possible. Every record is stored with
+//! the time of the clock, which a [`WallClock`] turns into a date once a
+//! client has set the time, however long before.
+//!
+//! The moisture of the samples is also rolled up, typically hourly, into its
+//! range and average over each period, kept for longer than the records.
+//!
+//! Hardware failures do not stop the cycle: a sensor failure sounds the alarm
+//! and counts as an alert, a battery failure falls back to the last known
+//! reading, or stores records with an unknown battery before any, and a radio
+//! failure leaves the history undelivered for the next advertising window.
+//!
+//! ## Examples
+//!
+//! ```rust,ignore
+//! let mut state = State::default();
+//! let secs = Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+//! board.sleep(secs);
+//! ```
+
+pub use board::{Board, Content, Event, Live, Served, LIVE_LEN};
+
+use core::ops::DerefMut;
+
+use crate::{
+    advertising::{self, Advertiser},
+    battery::Battery,
+    historical::Historical,
+    irrigation::{self, Command, Interlock, Irrigator},
+    pattern::Pattern,
+    rpc::Commands,
+    sample::{Record, Rollup, Summary},
+    schedule::{self, Adaptive, Job, Scheduler, Task},
+    sensors::Sensor,
+    shared::{Broadcast, Flag, Status},
+    time::WallClock,
+    wake::{self, Plan},
+};
+
+mod board;
+
+/// Records kept in the history.
+pub const HISTORY_LEN: usize = 128;
+/// Rollups kept, two days of them when hourly.
+pub const ROLLUPS_LEN: usize = 48;
+/// Time asleep when rebooting, in seconds.
+const REBOOT_SECS: u32 = 1;
+
+/// Device settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    /// Measurement interval.
+    pub measure: schedule::Policy,
+    /// Time between battery checks, in seconds.
+    pub battery_check: u32,
+    /// Time between rollups of the samples, in seconds.
+    pub rollup: u32,
+    /// Time between periodic advertising windows, in seconds.
+    pub advertise: u32,
+    /// When to water.
+    pub watering: irrigation::Profile,
+    /// Hard limits on the pump.
+    pub limits: irrigation::Limits,
+    /// When to advertise.
+    pub advertising: advertising::Policy,
+    /// How long to advertise.
+    pub windows: wake::Windows,
+}
+
+impl Config {
+    fn tasks(&self) -> [Task; 4] {
+        [
+            (Job::Sample, self.measure.base),
+            (Job::BatteryCheck, self.battery_check),
+            (Job::Rollup, self.rollup),
+            (Job::Advertise, self.advertise),
+        ]
+    }
+}
+
+/// Everything kept across deep sleeps.
+pub struct State<S: Sensor> {
+    history: Historical<HISTORY_LEN, Record<S>>,
+    irrigator: Irrigator,
+    interlock: Interlock,
+    schedule: Adaptive,
+    jobs: Scheduler<4>,
+    advertiser: Advertiser,
+    last_sample: Option<Summary<S>>,
+    last_battery: Option<Battery>,
+    sequence: u32,
+    /// Time of the clock each record of the history was stored at.
+    stamps: Historical<HISTORY_LEN, u32>,
+    /// Time set by clients, and the drift of the clock between them.
+    clock: WallClock,
+    /// Samples since the latest rollup.
+    rollup: Rollup,
+    rollups: Historical<ROLLUPS_LEN, Rollup>,
+}
+
+impl<S: Sensor> Default for State<S> {
+    fn default() -> Self {
+        Self {
+            history: Historical::new(),
+            irrigator: Irrigator::new(),
+            interlock: Interlock::new(),
+            schedule: Adaptive::new(),
+            jobs: Scheduler::new(),
+            advertiser: Advertiser::new(),
+            last_sample: None,
+            last_battery: None,
+            sequence: 0,
+            stamps: Historical::new(),
+            clock: WallClock::new(),
+            rollup: Rollup::new(),
+            rollups: Historical::new(),
+        }
+    }
+}
+
+impl<S: Sensor> State<S> {
+    pub fn history(&self) -> &Historical<HISTORY_LEN, Record<S>> {
+        &self.history
+    }
+
+    /// Returns the time in seconds since the Unix epoch, given the one of the
+    /// clock, once a client has set it.
+    pub fn unix_time(&self, now: u32) -> Option<u32> {
+        self.clock.unix(now)
+    }
+
+    /// Moisture over each period between rollups, oldest first.
+    pub fn rollups(&self) -> &Historical<ROLLUPS_LEN, Rollup> {
+        &self.rollups
+    }
+
+    /// Time set by clients, to date the history with.
+    pub fn clock(&self) -> &WallClock {
+        &self.clock
+    }
+}
+
+/// Stages of a wake, in order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    Wake,
+    CheckBattery,
+    Sample,
+    Advertise,
+    /// Done, sleep for the given time, in seconds.
+    Sleep(u32),
+}
+
+/// Walks a single wake through its stages, over the state or anything giving
+/// access to it, such as a borrow of [retained memory](crate::retained).
+pub struct Lifecycle<'a, T> {
+    config: &'a Config,
+    state: T,
+    stage: Stage,
+    plan: Plan,
+    battery: Option<Battery>,
+    alert: Option<bool>,
+    status: Status,
+    /// Seconds until the sampling after the one of this wake.
+    interval: u32,
+    /// Whether this wake advertised already, before a sampling the client
+    /// asked for.
+    advertised: bool,
+}
+
+impl<'a, S: Sensor + Copy, T: DerefMut<Target = State<S>>> Lifecycle<'a, T> {
+    pub fn new(config: &'a Config, state: T) -> Self {
+        let plan = Plan { jobs: Default::default(), button: false, advertise_ms: 0 };
+        Self {
+            config,
+            state,
+            stage: Stage::Wake,
+            plan,
+            battery: None,
+            alert: None,
+            status: Status::empty(),
+            interval: config.measure.base,
+            advertised: false,
+        }
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// Gives access to what holds the state between stages, as to save it.
+    pub fn state_mut(&mut self) -> &mut T {
+        &mut self.state
+    }
+
+    /// Runs all stages, and returns how long to sleep, in seconds.
+    pub fn run(mut self, board: &mut impl Board<Sensor = S>) -> u32 {
+        loop {
+            if let Stage::Sleep(secs) = self.step(board) {
+                return secs;
+            }
+        }
+    }
+
+    /// Runs the current stage, and returns the next one.
+    pub fn step(&mut self, board: &mut impl Board<Sensor = S>) -> Stage {
+        self.stage = match self.stage {
+            Stage::Wake => self.wake(board),
+            Stage::CheckBattery => self.check_battery(board),
+            Stage::Sample => self.sample(board),
+            Stage::Advertise => self.advertise(board),
+            Stage::Sleep(secs) => Stage::Sleep(secs),
+        };
+        self.stage
+    }
+
+    fn wake(&mut self, board: &mut impl Board<Sensor = S>) -> Stage {
+        let cause = board.wake_cause();
+        let tasks = self.config.tasks();
+        self.plan = wake::plan(cause, &mut self.state.jobs, &tasks, board, &self.config.windows);
+        board.report(Event::Woken(cause, self.plan.jobs));
+        board.beep(Pattern::Boot);
+        if self.plan.jobs.contains(Job::Rollup) {
+            self.roll_up(board);
+        }
+        Stage::CheckBattery
+    }
+
+    /// Stores the moisture over the samples since the previous rollup, if any.
+    fn roll_up(&mut self, board: &mut impl Board<Sensor = S>) {
+        let rollup = core::mem::take(&mut self.state.rollup);
+        if !rollup.is_empty() {
+            board.report(Event::RolledUp(rollup));
+            self.state.rollups.store(rollup);
+        }
+    }
+
+    fn check_battery(&mut self, board: &mut impl Board<Sensor = S>) -> Stage {
+        self.battery = match self.state.last_battery {
+            Some(battery) if !self.plan.jobs.contains(Job::BatteryCheck) => Some(battery),
+            last => match board.read_battery() {
+                Ok(battery) => {
+                    board.report(Event::Battery(battery));
+                    self.state.last_battery = Some(battery);
+                    Some(battery)
+                }
+                Err(err) => {
+                    board.report(Event::BatteryError(err));
+                    last
+                }
+            },
+        };
+        if self.battery.is_some_and(|battery| battery.is_low()) {
+            board.beep(Pattern::LowBattery);
+            self.status.insert(Flag::LowBattery);
+        }
+
+        if self.plan.jobs.contains(Job::Sample) {
+            Stage::Sample
+        } else {
+            Stage::Advertise
+        }
+    }
+
+    fn sample(&mut self, board: &mut impl Board<Sensor = S>) -> Stage {
+        match board.sample() {
+            Ok(summary) => self.sampled(board, summary),
+            Err(err) => {
+                board.report(Event::SensorError(err));
+                board.beep(Pattern::SensorFault);
+                self.alert = Some(true);
+                self.status.insert(Flag::SensorFault);
+            }
+        }
+        // The advertiser decides once per wake, and the radio only comes up once.
+        if self.advertised {
+            let secs = self.state.jobs.next_wake(board);
+            self.sleep(board, secs)
+        } else {
+            Stage::Advertise
+        }
+    }
+
+    /// Waters, reschedules and stores a new sample.
+    fn sampled(&mut self, board: &mut impl Board<Sensor = S>, summary: Summary<S>) {
+        board.report(Event::Sampled(summary));
+
+        let config = self.config;
+        let now = board.now();
+        let moisture = summary.moisture();
+        let decision = self.state.irrigator.decide(&config.watering, &summary, now);
+        let guarded = self.state.interlock.guard(&config.limits, decision, moisture, now);
+        board.report(Event::Irrigation(guarded));
+        let (watered, fault) = match guarded {
+            Ok(decision) => {
+                if let Command::Water(pulse_ms) = decision.command {
+                    board.water(pulse_ms);
+                    self.state.irrigator.watered(now);
+                }
+                (decision.command != Command::Idle, None)
+            }
+            Err(fault) => (false, Some(fault)),
+        };
+
+        // Unknown battery levels are taken as full, so as not to slow down.
+        let inputs = schedule::Inputs {
+            moisture,
+            battery: self.battery.map_or(100, |battery| battery.percentage()),
+            hour: self.state.clock.unix(now).map(|unix| (unix / 3600 % 24) as u8),
+            watered,
+        };
+        let interval = self.state.schedule.next_interval(&config.measure, &inputs, now);
+        self.state.jobs.reschedule(&config.tasks(), Job::Sample, interval, board);
+        self.interval = interval;
+
+        // A sensor that is not powered or not connected reads zero across all samples.
+        let sensor_fault = summary.max == 0;
+        let low_moisture = moisture < config.watering.dry;
+        if sensor_fault {
+            board.beep(Pattern::SensorFault);
+            self.status.insert(Flag::SensorFault);
+        } else if low_moisture {
+            board.beep(Pattern::LowMoisture);
+            self.status.insert(Flag::Dry);
+        }
+        if fault.is_some() {
+            board.beep(Pattern::PumpFault);
+            self.status.insert(Flag::PumpFault);
+        }
+        if watered {
+            self.status.insert(Flag::Watered);
+        }
+        self.alert = Some(sensor_fault || low_moisture);
+
+        self.recorded(now, Record { summary, battery: self.battery, fault });
+    }
+
+    /// Stores a record taken at the given time, and rolls up its moisture.
+    fn recorded(&mut self, at: u32, record: Record<S>) {
+        // Faulty sensors read zero, which would skew the rollup.
+        if record.summary.max != 0 {
+            self.state.rollup.add(record.summary.moisture());
+        }
+        self.state.last_sample = Some(record.summary);
+        self.state.history.store(record);
+        self.state.stamps.store(at);
+        self.state.advertiser.stored();
+    }
+
+    fn advertise(&mut self, board: &mut impl Board<Sensor = S>) -> Stage {
+        self.advertised = true;
+        let inputs = advertising::Inputs {
+            alert: self.alert,
+            button: self.plan.button,
+            due: self.plan.jobs.contains(Job::Advertise),
+        };
+        if let Some(reason) = self.state.advertiser.decide(&self.config.advertising, &inputs) {
+            board.report(Event::Advertising(reason));
+            self.state.sequence = self.state.sequence.wrapping_add(1);
+            let content = Content {
+                history: &self.state.history,
+                stamps: &self.state.stamps,
+                clock: self.state.clock,
+                last_sample: self.state.last_sample,
+                battery: self.battery,
+                broadcast: Broadcast {
+                    moisture: self.state.last_sample.map(|summary| summary.moisture()),
+                    battery: self.battery.map(|battery| battery.percentage()),
+                    status: self.status,
+                },
+                sequence: self.state.sequence,
+                next_sample: self.state.jobs.due_in(&self.config.tasks(), Job::Sample, board),
+                interval: self.interval,
+            };
+            match board.serve(self.plan.advertise_ms, &content) {
+                Ok(served) => {
+                    board.report(Event::Served(served));
+                    // Records notified while connected are stored as they were,
+                    // and count as delivered along the history.
+                    for (at, record) in served.live.iter() {
+                        self.recorded(*at, *record);
+                    }
+                    if served.synced {
+                        self.state.advertiser.delivered();
+                    }
+                    if let Some(stage) = self.command(board, served.commands) {
+                        return stage;
+                    }
+                }
+                Err(err) => board.report(Event::RadioError(err)),
+            }
+        }
+
+        let secs = self.state.jobs.next_wake(board);
+        self.sleep(board, secs)
+    }
+
+    /// Carries out the commands of a client, and returns the stage to go to
+    /// instead of sleeping, if any.
+    fn command(&mut self, board: &mut impl Board<Sensor = S>, commands: Commands) -> Option<Stage> {
+        if commands.clear_history {
+            self.state.history.clear();
+            self.state.stamps.clear();
+        }
+        if let Some((at, unix)) = commands.time {
+            self.state.clock.sync(at, unix);
+        }
+        if commands.reset_interlock {
+            self.state.interlock.reset();
+        }
+        if commands.reboot {
+            Some(self.sleep(board, REBOOT_SECS))
+        } else if commands.sample {
+            Some(Stage::Sample)
+        } else {
+            None
+        }
+    }
+
+    fn sleep(&mut self, board: &mut impl Board<Sensor = S>, secs: u32) -> Stage {
+        board.report(Event::Sleeping(secs));
+        board.beep(Pattern::Sleep);
+        Stage::Sleep(secs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        battery::Chemistry, irrigation::Fault, retained::Retained, schedule::Clock,
+        sensors::Hygrometer, time::Times, wake::Cause,
+    };
+
+    const CONFIG: Config = Config {
+        measure: schedule::Policy {
+            min: 60,
+            base: 300,
+            max: 3600,
+            fast_change: 5,
+            night_start: 22,
+            night_end: 7,
+        },
+        battery_check: 86_400,
+        rollup: 3600,
+        advertise: 1800,
+        watering: irrigation::Profile {
+            dry: 35,
+            target: 60,
+            hysteresis: 5,
+            min_interval: 1800,
+            pulse_ms: 3000,
+        },
+        limits: irrigation::Limits {
+            max_run_ms: 10_000,
+            daily_budget_ml: 250,
+            flow_ml_per_min: 100,
+            cooldown: 900,
+            no_effect_pulses: 3,
+            min_rise: 2,
+        },
+        advertising: advertising::Policy { backlog: 24, on_alert: true, on_button: true },
+        windows: wake::Windows { regular_ms: 5_000, extended_ms: 60_000 },
+    };
+
+    const BATTERY: Battery = Battery { millivolts: 4000, chemistry: Chemistry::LiIon };
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Error {
+        Adc,
+        Radio,
+    }
+
+    /// Scripted hardware, recording what the lifecycle asked for.
+    struct FakeBoard {
+        now: u32,
+        cause: Cause,
+        battery: Result<Battery, Error>,
+        avg: Result<u16, Error>,
+        served: Result<Served<Hygrometer>, Error>,
+        beeps: [Option<Pattern>; 16],
+        watered: Option<u32>,
+        window: Option<u32>,
+        broadcast: Option<Broadcast>,
+        sequence: Option<u32>,
+        next_sample: Option<u32>,
+        interval: Option<u32>,
+        serves: u8,
+        samples: u8,
+        battery_reads: u8,
+        radio_errors: u8,
+    }
+
+    impl FakeBoard {
+        fn new() -> Self {
+            Self {
+                now: 1000,
+                cause: Cause::Reset,
+                battery: Ok(BATTERY),
+                avg: Ok(1200),
+                served: Ok(Served { connected: true, synced: true, ..Served::default() }),
+                beeps: [None; 16],
+                watered: None,
+                window: None,
+                broadcast: None,
+                sequence: None,
+                next_sample: None,
+                interval: None,
+                serves: 0,
+                samples: 0,
+                battery_reads: 0,
+                radio_errors: 0,
+            }
+        }
+
+        fn beeped(&self, pattern: Pattern) -> bool {
+            self.beeps.contains(&Some(pattern))
+        }
+
+        fn next_wake(&mut self, secs: u32, cause: Cause) {
+            *self = Self { now: self.now + secs, cause, ..Self::new() };
+        }
+    }
+
+    impl Clock for FakeBoard {
+        fn now(&self) -> u32 {
+            self.now
+        }
+    }
+
+    impl Board for FakeBoard {
+        type Sensor = Hygrometer;
+        type Error = Error;
+
+        fn wake_cause(&mut self) -> Cause {
+            self.cause
+        }
+
+        fn beep(&mut self, pattern: Pattern) {
+            if let Some(slot) = self.beeps.iter_mut().find(|slot| slot.is_none()) {
+                *slot = Some(pattern);
+            }
+        }
+
+        fn read_battery(&mut self) -> Result<Battery, Error> {
+            self.battery_reads += 1;
+            self.battery
+        }
+
+        fn sample(&mut self) -> Result<Summary<Hygrometer>, Error> {
+            self.samples += 1;
+            self.avg.map(|avg| Summary {
+                n: 1,
+                avg,
+                min: avg,
+                max: avg,
+                sensor: Hygrometer::HW390,
+                compensation: None,
+            })
+        }
+
+        fn water(&mut self, pulse_ms: u32) {
+            self.watered = Some(pulse_ms);
+        }
+
+        fn serve(
+            &mut self,
+            window_ms: u32,
+            content: &Content<'_, Hygrometer>,
+        ) -> Result<Served<Hygrometer>, Error> {
+            self.serves += 1;
+            self.window = Some(window_ms);
+            self.broadcast = Some(content.broadcast);
+            self.sequence = Some(content.sequence);
+            self.next_sample = Some(content.next_sample);
+            self.interval = Some(content.interval);
+            self.served
+        }
+
+        fn report(&mut self, event: Event<Hygrometer, Error>) {
+            if let Event::RadioError(_) = event {
+                self.radio_errors += 1;
+            }
+        }
+    }
+
+    fn history_len(state: &State<Hygrometer>) -> usize {
+        let mut syncer = state.history().sync();
+        let mut buffer = [0u8; 32];
+        let mut n = 0;
+        while syncer.write(&mut buffer).unwrap() > 0 {
+            n += 1;
+        }
+        n
+    }
+
+    #[test]
+    fn test_stages() {
+        let mut state = State::default();
+        let mut board = FakeBoard::new();
+        let mut sut = Lifecycle::new(&CONFIG, &mut state);
+
+        assert_eq!(Stage::Wake, sut.stage());
+        assert_eq!(Stage::CheckBattery, sut.step(&mut board));
+        assert_eq!(Stage::Sample, sut.step(&mut board));
+        assert_eq!(Stage::Advertise, sut.step(&mut board));
+        assert_eq!(Stage::Sleep(300), sut.step(&mut board));
+        assert_eq!(Stage::Sleep(300), sut.step(&mut board));
+    }
+
+    #[test]
+    fn test_cold_boot() {
+        let mut state = State::default();
+        let mut board = FakeBoard::new();
+
+        assert_eq!(300, Lifecycle::new(&CONFIG, &mut state).run(&mut board));
+        assert_eq!((1, 1), (board.battery_reads, board.samples));
+        assert_eq!(1, history_len(&state));
+        assert_eq!(Some(Pattern::Boot), board.beeps[0]);
+        assert!(board.beeped(Pattern::Sleep));
+        // The advertising job is due on the first wake, as every job.
+        assert_eq!(Some(5_000), board.window);
+        assert_eq!(None, board.watered);
+    }
+
+    #[test]
+    fn test_advertises_every_half_hour() {
+        let mut state = State::default();
+        let mut board = FakeBoard::new();
+        let mut windows = 0;
+
+        for _ in 0..12 {
+            let secs = Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+            windows += board.window.is_some() as u32;
+            board.next_wake(secs, Cause::Timer);
+        }
+
+        assert_eq!(3600, board.now - 1000);
+        assert_eq!(2, windows);
+    }
+
+    #[test]
+    fn test_battery_checked_daily() {
+        let mut state = State::default();
+        let mut board = FakeBoard::new();
+
+        let secs = Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        board.next_wake(secs, Cause::Timer);
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+
+        assert_eq!((0, 1), (board.battery_reads, board.samples));
+        assert_eq!(2, history_len(&state));
+    }
+
+    #[test]
+    fn test_rolls_up_hourly() {
+        let mut state = State::default();
+        let mut board = FakeBoard::new();
+
+        for _ in 0..=12 {
+            let secs = Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+            board.next_wake(secs, Cause::Timer);
+        }
+
+        // Rolled up on the first wake, with nothing sampled yet, and an hour later.
+        assert_eq!(1, state.rollups().len());
+        let rollup = state.rollups().get(0).unwrap();
+        assert_eq!((12, Some(81)), (rollup.n, rollup.avg()));
+    }
+
+    #[test]
+    fn test_retained_state_sealed_between_stages() {
+        let mut retained = Retained::<State<Hygrometer>, 1>::new();
+        let mut board = FakeBoard::new();
+        let mut sut = Lifecycle::new(&CONFIG, retained.get_mut());
+        while sut.stage() != Stage::Advertise {
+            sut.step(&mut board);
+            sut.state_mut().seal();
+        }
+
+        // As when the radio panics, the borrow never ends.
+        core::mem::forget(sut);
+
+        assert!(retained.validate());
+        assert_eq!(1, history_len(retained.get()));
+    }
+
+    #[test]
+    fn test_dry_soil() {
+        let mut state = State::default();
+        let mut board = FakeBoard { avg: Ok(2500), ..FakeBoard::new() };
+
+        assert_eq!(60, Lifecycle::new(&CONFIG, &mut state).run(&mut board));
+        assert_eq!(Some(3000), board.watered);
+        assert!(board.beeped(Pattern::LowMoisture));
+        // Crossing into the alert range advertises right away.
+        assert_eq!(Some(5_000), board.window);
+
+        let broadcast = board.broadcast.unwrap();
+        assert_eq!(Some(0), broadcast.moisture);
+        assert_eq!(Some(BATTERY.percentage()), broadcast.battery);
+        assert!(broadcast.status.contains(Flag::Dry));
+        assert!(broadcast.status.contains(Flag::Watered));
+        assert!(!broadcast.status.contains(Flag::SensorFault));
+    }
+
+    #[test]
+    fn test_sequence_counts_advertisements() {
+        let mut state = State::default();
+        let mut board = FakeBoard { avg: Ok(2500), ..FakeBoard::new() };
+
+        let secs = Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        assert_eq!(Some(1), board.sequence);
+
+        board.next_wake(secs, Cause::Button);
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        assert_eq!(Some(2), board.sequence);
+        assert_eq!(2, state.sequence);
+    }
+
+    #[test]
+    fn test_dates_history_once_time_set() {
+        let mut state = State::default();
+        let mut board = FakeBoard::new();
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+
+        board.next_wake(60, Cause::Button);
+        let commands = Commands { time: Some((board.now, 1_700_000_000)), ..Commands::default() };
+        board.served = Ok(Served { connected: true, commands, ..Served::default() });
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+
+        let mut data = [0u8; 8];
+        let mut sut = Times::new(&state.stamps, *state.clock());
+        assert_eq!(Ok(4), sut.write(&mut data));
+        assert_eq!(1_699_999_940u32.to_le_bytes(), data[..4]);
+    }
+
+    #[test]
+    fn test_slower_overnight_once_time_set() {
+        let mut state = State::default();
+        let mut board = FakeBoard::new();
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+
+        board.next_wake(60, Cause::Button);
+        // 22:13:20 UTC.
+        let commands = Commands { time: Some((board.now, 1_700_000_000)), ..Commands::default() };
+        board.served = Ok(Served { connected: true, commands, ..Served::default() });
+        let secs = Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        board.next_wake(secs, Cause::Timer);
+
+        assert_eq!(600, Lifecycle::new(&CONFIG, &mut state).run(&mut board));
+    }
+
+    #[test]
+    fn test_clear_history_and_set_time() {
+        let mut state = State::default();
+        let mut board = FakeBoard::new();
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        assert_eq!(None, state.unix_time(board.now));
+
+        board.next_wake(300, Cause::Button);
+        let time = Some((board.now + 10, 1_700_000_010));
+        let commands = Commands { clear_history: true, time, ..Commands::default() };
+        board.served = Ok(Served { connected: true, synced: true, commands, ..Served::default() });
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+
+        assert_eq!(0, history_len(&state));
+        assert_eq!(Some(1_700_000_060), state.unix_time(board.now + 60));
+    }
+
+    #[test]
+    fn test_trigger_sample() {
+        let mut state = State::default();
+        let mut board = FakeBoard { cause: Cause::Button, ..FakeBoard::new() };
+        let commands = Commands { sample: true, ..Commands::default() };
+        board.served = Ok(Served { connected: true, synced: true, commands, ..Served::default() });
+        let mut sut = Lifecycle::new(&CONFIG, &mut state);
+        sut.step(&mut board);
+        assert_eq!(Stage::Advertise, sut.step(&mut board));
+
+        assert_eq!(Stage::Sample, sut.step(&mut board));
+        assert!(matches!(sut.step(&mut board), Stage::Sleep(_)));
+        assert_eq!(1, board.samples);
+        assert_eq!(1, board.serves);
+        assert_eq!(1, history_len(&state));
+    }
+
+    fn live_record(avg: u16) -> Record<Hygrometer> {
+        let summary = Summary {
+            n: 1,
+            avg,
+            min: avg,
+            max: avg,
+            sensor: Hygrometer::HW390,
+            compensation: None,
+        };
+        Record { summary, battery: Some(BATTERY), fault: None }
+    }
+
+    #[test]
+    fn test_live_sample() {
+        let mut state = State::default();
+        let mut board = FakeBoard::new();
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        let live = live_record(2000);
+        let mut served = Served { connected: true, ..Served::default() };
+        served.live.push(1300, live).unwrap();
+
+        board.next_wake(60, Cause::Button);
+        board.served = Ok(served);
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+
+        assert_eq!(Some(240), board.next_sample);
+        assert_eq!(Some(300), board.interval);
+        assert_eq!(0, board.samples);
+        assert_eq!(2, history_len(&state));
+        assert_eq!(Some(live.summary), state.last_sample);
+    }
+
+    #[test]
+    fn test_live_samples_in_one_connection() {
+        let mut state = State::default();
+        let mut board = FakeBoard::new();
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        // Dry enough to water, were they sampled by the lifecycle.
+        let (first, second) = (live_record(2600), live_record(2650));
+        let mut served = Served { connected: true, ..Served::default() };
+        served.live.push(1300, first).unwrap();
+        served.live.push(1600, second).unwrap();
+
+        board.next_wake(60, Cause::Button);
+        board.served = Ok(served);
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+
+        assert_eq!(0, board.samples);
+        assert_eq!(None, board.watered);
+        assert!(!board.beeped(Pattern::LowMoisture));
+        assert_eq!(3, history_len(&state));
+        assert_eq!(Some(&first), state.history().get(1));
+        assert_eq!(Some(&second), state.history().get(2));
+        assert_eq!([Some(&1000), Some(&1300), Some(&1600)], [0, 1, 2].map(|i| state.stamps.get(i)));
+        assert_eq!(Some(second.summary), state.last_sample);
+        // Still due as scheduled by the sampling before the connection.
+        assert_eq!(240, state.jobs.due_in(&CONFIG.tasks(), Job::Sample, &board));
+    }
+
+    #[test]
+    fn test_reset_interlock() {
+        let mut state = State::default();
+        let limits = CONFIG.limits;
+        let water = irrigation::Decision {
+            command: Command::Water(1000),
+            reason: irrigation::Reason::BelowTarget,
+        };
+        for pulse in 0..=limits.no_effect_pulses as u32 {
+            let _ = state.interlock.guard(&limits, water, 20, pulse * limits.cooldown);
+        }
+        assert_eq!(Err(Fault::NoEffect), state.interlock.guard(&limits, water, 20, 10_000));
+        let mut board = FakeBoard::new();
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+
+        board.next_wake(60, Cause::Button);
+        let commands = Commands { reset_interlock: true, ..Commands::default() };
+        board.served = Ok(Served { connected: true, commands, ..Served::default() });
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+
+        assert_eq!(Ok(water), state.interlock.guard(&limits, water, 20, 20_000));
+    }
+
+    #[test]
+    fn test_reboot() {
+        let mut state = State::default();
+        let mut board = FakeBoard { cause: Cause::Button, ..FakeBoard::new() };
+        let commands = Commands { reboot: true, sample: true, ..Commands::default() };
+        board.served = Ok(Served { connected: true, synced: true, commands, ..Served::default() });
+
+        assert_eq!(REBOOT_SECS, Lifecycle::new(&CONFIG, &mut state).run(&mut board));
+        assert_eq!(0, board.samples);
+        assert!(board.beeped(Pattern::Sleep));
+    }
+
+    #[test]
+    fn test_adc_failure() {
+        let mut state = State::default();
+        let mut board = FakeBoard { avg: Err(Error::Adc), ..FakeBoard::new() };
+
+        assert_eq!(300, Lifecycle::new(&CONFIG, &mut state).run(&mut board));
+        assert!(board.beeped(Pattern::SensorFault));
+        assert_eq!(None, board.watered);
+        assert_eq!(0, history_len(&state));
+        assert_eq!(Some(5_000), board.window);
+
+        let broadcast = board.broadcast.unwrap();
+        assert_eq!(None, broadcast.moisture);
+        assert!(broadcast.status.contains(Flag::SensorFault));
+    }
+
+    #[test]
+    fn test_battery_failure() {
+        let mut state = State::default();
+        let mut board = FakeBoard { battery: Err(Error::Adc), ..FakeBoard::new() };
+
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        assert_eq!(1, board.samples);
+        assert_eq!(1, history_len(&state));
+        assert_eq!(None, state.history().get(0).unwrap().battery);
+
+        board.next_wake(300, Cause::Timer);
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        assert_eq!(1, board.battery_reads);
+        assert_eq!(2, history_len(&state));
+        assert_eq!(Some(BATTERY), state.history().get(1).unwrap().battery);
+    }
+
+    #[test]
+    fn test_radio_failure() {
+        let mut state = State::default();
+        let mut board =
+            FakeBoard { cause: Cause::Button, served: Err(Error::Radio), ..FakeBoard::new() };
+
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        assert_eq!(1, board.radio_errors);
+        assert_eq!(Some(60_000), board.window);
+    }
+
+    #[test]
+    fn test_button_skips_sampling() {
+        let mut state = State::default();
+        let mut board = FakeBoard::new();
+
+        let secs = Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        board.next_wake(60, Cause::Button);
+        assert_eq!(secs - 60, Lifecycle::new(&CONFIG, &mut state).run(&mut board));
+
+        assert_eq!((0, 0), (board.battery_reads, board.samples));
+        assert_eq!(Some(60_000), board.window);
+        assert_eq!(0, state.advertiser.pending());
+    }
+
+    #[test]
+    fn test_undelivered_history_after_radio_failure() {
+        let mut state = State::default();
+        // Nobody connects on the first wake.
+        let mut board = FakeBoard { served: Ok(Served::default()), ..FakeBoard::new() };
+
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+        board.next_wake(60, Cause::Button);
+        board.served = Err(Error::Radio);
+        Lifecycle::new(&CONFIG, &mut state).run(&mut board);
+
+        assert_eq!(1, state.advertiser.pending());
+    }
+
+    #[test]
+    fn test_pump_fault() {
+        let mut state = State::default();
+        let mut board = FakeBoard { avg: Ok(2500), ..FakeBoard::new() };
+        let config =
+            Config { limits: irrigation::Limits { daily_budget_ml: 0, ..CONFIG.limits }, ..CONFIG };
+
+        Lifecycle::new(&config, &mut state).run(&mut board);
+        assert_eq!(None, board.watered);
+        assert!(board.beeped(Pattern::PumpFault));
+
+        let mut syncer = state.history().sync();
+        let mut buffer = [0u8; 32];
+        let n = syncer.write(&mut buffer).unwrap();
+        let record = crate::serde::deserialize::<Record<Hygrometer>>(&buffer[..n]).unwrap();
+        assert_eq!(Some(Fault::BudgetExhausted), record.fault);
+        // The pulse held back does not delay the next one.
+        let summary = state.last_sample.unwrap();
+        let decision = state.irrigator.decide(&config.watering, &summary, board.now + 60);
+        assert_eq!(Command::Water(config.watering.pulse_ms), decision.command);
+    }
+}
@@ -0,0 +1,169 @@
+//! # Alarm patterns
+//!
+//! Named buzzer sequences, so the alarm tells what's going on instead of
+//! chirping the same way for everything. Each [`Pattern`] is a timeline of
+//! on/off [`Step`]s, repeated a number of times, which [`play`] executes
+//! through a pair of closures driving the output pin and the delay.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::pattern::{self, Pattern};
+//! let mut set_output = |on: bool| println!("alarm {}", if on { "on" } else { "off" });
+//! let mut delay = |ms: u32| println!("wait {ms}ms");
+//! pattern::play(Pattern::Connected, &mut set_output, &mut delay);
+//! ```
+
+/// A single segment of a timeline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    /// Output high for the given time, in milliseconds.
+    On(u32),
+    /// Output low for the given time, in milliseconds.
+    Off(u32),
+}
+
+/// Named alarm sequences.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// Device just woke up.
+    Boot,
+    /// Soil moisture dropped below the dry threshold.
+    LowMoisture,
+    /// Battery needs replacing or recharging.
+    LowBattery,
+    /// The sensor is not returning readings.
+    SensorFault,
+    /// Irrigation has been halted by the pump interlocks.
+    PumpFault,
+    /// A client connected to the device.
+    Connected,
+    /// Device is about to go to deep sleep.
+    Sleep,
+}
+
+impl Pattern {
+    /// Returns the steps of the pattern and how many times they are played.
+    pub fn timeline(&self) -> (&'static [Step], u8) {
+        use Step::*;
+        match self {
+            Pattern::Boot => (&[Off(15), On(10), Off(25), On(10)], 5),
+            Pattern::LowMoisture => (&[On(300), Off(200)], 3),
+            Pattern::LowBattery => (&[On(50), Off(100), On(50), Off(500)], 2),
+            Pattern::SensorFault => (&[On(30), Off(30)], 10),
+            Pattern::PumpFault => (&[On(500), Off(100), On(100), Off(300)], 2),
+            Pattern::Connected => (&[On(20), Off(40), On(60)], 1),
+            Pattern::Sleep => (&[On(100)], 1),
+        }
+    }
+
+    /// Total time it takes to play the pattern, in milliseconds.
+    pub fn duration_ms(&self) -> u32 {
+        let (steps, repeat) = self.timeline();
+        let once: u32 = steps
+            .iter()
+            .map(|step| match step {
+                Step::On(ms) | Step::Off(ms) => ms,
+            })
+            .sum();
+        once * repeat as u32
+    }
+}
+
+/// Plays a pattern, leaving the output low once done.
+pub fn play(pattern: Pattern, set_output: &mut impl FnMut(bool), delay: &mut impl FnMut(u32)) {
+    let (steps, repeat) = pattern.timeline();
+    for _ in 0..repeat {
+        for step in steps {
+            match *step {
+                Step::On(ms) => {
+                    set_output(true);
+                    delay(ms);
+                }
+                Step::Off(ms) => {
+                    set_output(false);
+                    delay(ms);
+                }
+            }
+        }
+    }
+    set_output(false);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    /// Records the output level over time, merging consecutive delays at the
+    /// same level.
+    struct Recorder {
+        level: bool,
+        timeline: [(bool, u32); 64],
+        len: usize,
+    }
+
+    impl Recorder {
+        fn new() -> Self {
+            Self { level: false, timeline: [(false, 0); 64], len: 0 }
+        }
+
+        fn record(&mut self, pattern: Pattern) -> &[(bool, u32)] {
+            let level = core::cell::Cell::new(false);
+            let mut set_output = |on: bool| level.set(on);
+            let mut delay = |ms: u32| {
+                let level = level.get();
+                if self.len > 0 && self.timeline[self.len - 1].0 == level {
+                    self.timeline[self.len - 1].1 += ms;
+                } else {
+                    self.timeline[self.len] = (level, ms);
+                    self.len += 1;
+                }
+            };
+            play(pattern, &mut set_output, &mut delay);
+            self.level = level.get();
+            &self.timeline[..self.len]
+        }
+    }
+
+    #[test]
+    fn test_boot_timeline() {
+        let mut sut = Recorder::new();
+
+        let expected = [
+            (false, 15),
+            (true, 10),
+            (false, 25),
+            (true, 10),
+            (false, 15),
+            (true, 10),
+            (false, 25),
+            (true, 10),
+        ];
+        assert_eq!(&expected, &sut.record(Pattern::Boot)[..8]);
+        assert_eq!(20, sut.len);
+        assert!(!sut.level);
+    }
+
+    #[test_case(Pattern::LowMoisture, &[(true, 300), (false, 200), (true, 300), (false, 200), (true, 300), (false, 200)])]
+    #[test_case(Pattern::Connected, &[(true, 20), (false, 40), (true, 60)])]
+    #[test_case(Pattern::Sleep, &[(true, 100)])]
+    fn test_timeline(pattern: Pattern, expected: &[(bool, u32)]) {
+        let mut sut = Recorder::new();
+
+        assert_eq!(expected, sut.record(pattern));
+        assert!(!sut.level);
+    }
+
+    #[test_case(Pattern::Boot, 300)]
+    #[test_case(Pattern::LowBattery, 1400)]
+    #[test_case(Pattern::SensorFault, 600)]
+    #[test_case(Pattern::Sleep, 100)]
+    fn test_duration(pattern: Pattern, expected: u32) {
+        assert_eq!(expected, pattern.duration_ms());
+
+        let mut total = 0;
+        play(pattern, &mut |_| {}, &mut |ms| total += ms);
+        assert_eq!(expected, total);
+    }
+}
@@ -0,0 +1,45 @@
+//! Built-in profiles for common bonsai species.
+//!
+//! Seasonal shifts are listed as spring, summer, autumn and winter: most
+//! species need more water during the summer growth, and much less while
+//! dormant in winter.
+
+use super::Profile;
+
+pub const JUNIPER: Profile = Profile::new("juniper", 30, 50, 20, 70, [0, 5, 0, -10]);
+pub const PINE: Profile = Profile::new("pine", 25, 45, 15, 65, [0, 5, 0, -10]);
+pub const FICUS: Profile = Profile::new("ficus", 45, 65, 30, 80, [0, 5, 0, -5]);
+pub const CHINESE_ELM: Profile = Profile::new("chinese-elm", 40, 60, 25, 80, [0, 5, 0, -5]);
+pub const MAPLE: Profile = Profile::new("maple", 50, 70, 35, 85, [5, 10, 0, -10]);
+pub const AZALEA: Profile = Profile::new("azalea", 55, 75, 40, 90, [5, 5, 0, -5]);
+
+/// All built-in profiles.
+pub const CATALOG: &[Profile] = &[JUNIPER, PINE, FICUS, CHINESE_ELM, MAPLE, AZALEA];
+
+/// Finds a built-in profile by name.
+pub fn find(name: &str) -> Option<&'static Profile> {
+    CATALOG.iter().find(|profile| profile.name() == name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::plant::Season;
+
+    #[test]
+    fn test_find() {
+        assert_eq!(Some(&MAPLE), find("maple"));
+        assert_eq!(None, find("cactus"));
+    }
+
+    #[test]
+    fn test_catalog_is_valid() {
+        for profile in CATALOG {
+            assert!(profile.is_valid(), "{}", profile.name());
+            for season in [Season::Spring, Season::Summer, Season::Autumn, Season::Winter] {
+                let (min, max) = profile.ideal(season);
+                assert!(min <= max, "{} {season:?}", profile.name());
+            }
+        }
+    }
+}
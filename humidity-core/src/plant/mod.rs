@@ -0,0 +1,261 @@
+//! # Plant profiles
+//!
+//! Different species want very different moisture ranges. A [`Profile`]
+//! describes the ideal moisture range of a plant, the thresholds at which to
+//! raise an alert, and how both shift along the seasons. A built-in [`catalog`]
+//! covers common bonsai species.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::plant::{catalog, Season};
+//! let profile = catalog::find("juniper").unwrap();
+//! println!("{}: {:?}", profile.name(), profile.status(42, Season::Summer));
+//! ```
+
+use crate::{
+    irrigation,
+    serde::{self, Deserializable, Serializable},
+};
+
+pub mod catalog;
+
+/// Maximum length of a profile name, in bytes.
+pub const NAME_LEN: usize = 16;
+
+/// Seasons of the year, which shift moisture needs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    /// Returns the season for a month, from 1 to 12, in the northern hemisphere.
+    pub fn from_month(month: u8) -> Self {
+        match month {
+            3..=5 => Season::Spring,
+            6..=8 => Season::Summer,
+            9..=11 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+}
+
+/// How a moisture reading compares to what the plant wants.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    TooDry,
+    Ok,
+    TooWet,
+}
+
+/// Moisture needs of a plant species. Moisture values go from 0 to 100.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Profile {
+    name: [u8; NAME_LEN],
+    /// Lower bound of the ideal moisture range.
+    pub ideal_min: u8,
+    /// Upper bound of the ideal moisture range.
+    pub ideal_max: u8,
+    /// Moisture below which to raise an alert.
+    pub too_dry: u8,
+    /// Moisture above which to raise an alert.
+    pub too_wet: u8,
+    /// Shift applied to all thresholds on each [`Season`].
+    pub seasons: [i8; 4],
+}
+
+impl Profile {
+    /// Creates a profile, truncating the name to [`NAME_LEN`] bytes, without
+    /// splitting a character.
+    pub const fn new(
+        name: &str,
+        ideal_min: u8,
+        ideal_max: u8,
+        too_dry: u8,
+        too_wet: u8,
+        seasons: [i8; 4],
+    ) -> Self {
+        let bytes = name.as_bytes();
+        let mut len = if bytes.len() < NAME_LEN { bytes.len() } else { NAME_LEN };
+        // Continuation bytes of UTF-8 start with 0b10.
+        while len < bytes.len() && bytes[len] & 0xc0 == 0x80 {
+            len -= 1;
+        }
+        let mut name = [0u8; NAME_LEN];
+        let mut i = 0;
+        while i < len {
+            name[i] = bytes[i];
+            i += 1;
+        }
+        Self { name, ideal_min, ideal_max, too_dry, too_wet, seasons }
+    }
+
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|b| *b == 0).unwrap_or(NAME_LEN);
+        core::str::from_utf8(&self.name[..len]).unwrap_or_default()
+    }
+
+    /// Returns the ideal moisture range for the season.
+    pub fn ideal(&self, season: Season) -> (u8, u8) {
+        (self.adjust(self.ideal_min, season), self.adjust(self.ideal_max, season))
+    }
+
+    /// Compares a moisture reading against the ideal range for the season.
+    pub fn status(&self, moisture: u8, season: Season) -> Status {
+        let (min, max) = self.ideal(season);
+        if moisture < min {
+            Status::TooDry
+        } else if moisture > max {
+            Status::TooWet
+        } else {
+            Status::Ok
+        }
+    }
+
+    /// Returns whether a moisture reading is far enough from the ideal range to
+    /// raise an alert.
+    pub fn alert(&self, moisture: u8, season: Season) -> Option<Status> {
+        if moisture < self.adjust(self.too_dry, season) {
+            Some(Status::TooDry)
+        } else if moisture > self.adjust(self.too_wet, season) {
+            Some(Status::TooWet)
+        } else {
+            None
+        }
+    }
+
+    /// Derives the watering thresholds for the season, aiming for the middle of
+    /// the ideal range, and keeping the timings from `base`.
+    pub fn watering(&self, season: Season, base: irrigation::Profile) -> irrigation::Profile {
+        let (min, max) = self.ideal(season);
+        irrigation::Profile { dry: min, target: min + (max - min) / 2, ..base }
+    }
+
+    fn adjust(&self, value: u8, season: Season) -> u8 {
+        let shift = self.seasons[season as usize] as i16;
+        (value as i16 + shift).clamp(0, 100) as u8
+    }
+
+    fn is_valid(&self) -> bool {
+        core::str::from_utf8(&self.name).is_ok()
+            && self.too_dry <= self.ideal_min
+            && self.ideal_min <= self.ideal_max
+            && self.ideal_max <= self.too_wet
+            && self.too_wet <= 100
+    }
+}
+
+impl Serializable for Profile {
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        let mut n = ser.write_bytes(&self.name)?;
+        n += ser.write_u8(self.ideal_min)?;
+        n += ser.write_u8(self.ideal_max)?;
+        n += ser.write_u8(self.too_dry)?;
+        n += ser.write_u8(self.too_wet)?;
+        for shift in self.seasons {
+            n += ser.write_u8(shift as u8)?;
+        }
+        Ok(n)
+    }
+}
+
+impl Deserializable for Profile {
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        let name = de.read_bytes::<NAME_LEN>()?;
+        let ideal_min = de.read_u8()?;
+        let ideal_max = de.read_u8()?;
+        let too_dry = de.read_u8()?;
+        let too_wet = de.read_u8()?;
+        let mut seasons = [0i8; 4];
+        for shift in seasons.iter_mut() {
+            *shift = de.read_u8()? as i8;
+        }
+
+        let profile = Self { name, ideal_min, ideal_max, too_dry, too_wet, seasons };
+        if !profile.is_valid() {
+            return Err(serde::Error::Other);
+        }
+        Ok(profile)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    const SUT: Profile = Profile::new("test", 40, 60, 25, 80, [0, 5, 0, -10]);
+
+    #[test]
+    fn test_name() {
+        assert_eq!("test", SUT.name());
+        assert_eq!(
+            "a-very-long-name",
+            Profile::new("a-very-long-name-indeed", 0, 0, 0, 0, [0; 4]).name()
+        );
+        assert_eq!("a-long-name-éb", Profile::new("a-long-name-ébène", 0, 0, 0, 0, [0; 4]).name());
+    }
+
+    #[test_case(Season::Spring, (40, 60))]
+    #[test_case(Season::Summer, (45, 65))]
+    #[test_case(Season::Winter, (30, 50))]
+    fn test_ideal(season: Season, expected: (u8, u8)) {
+        assert_eq!(expected, SUT.ideal(season));
+    }
+
+    #[test_case(39, Season::Spring, Status::TooDry)]
+    #[test_case(40, Season::Spring, Status::Ok)]
+    #[test_case(60, Season::Spring, Status::Ok)]
+    #[test_case(61, Season::Spring, Status::TooWet)]
+    #[test_case(42, Season::Summer, Status::TooDry)]
+    #[test_case(55, Season::Winter, Status::TooWet)]
+    fn test_status(moisture: u8, season: Season, expected: Status) {
+        assert_eq!(expected, SUT.status(moisture, season));
+    }
+
+    #[test_case(24, Season::Spring, Some(Status::TooDry))]
+    #[test_case(30, Season::Spring, None)]
+    #[test_case(81, Season::Spring, Some(Status::TooWet))]
+    #[test_case(75, Season::Winter, Some(Status::TooWet))]
+    fn test_alert(moisture: u8, season: Season, expected: Option<Status>) {
+        assert_eq!(expected, SUT.alert(moisture, season));
+    }
+
+    #[test]
+    fn test_watering() {
+        let base = irrigation::Profile {
+            dry: 0,
+            target: 0,
+            hysteresis: 5,
+            min_interval: 600,
+            pulse_ms: 3000,
+        };
+
+        let expected = irrigation::Profile { dry: 45, target: 55, ..base };
+        assert_eq!(expected, SUT.watering(Season::Summer, base));
+    }
+
+    #[test]
+    fn profile_serde() {
+        let mut buffer = [0u8; 60];
+        let n = serde::serialize(&SUT, &mut buffer).unwrap();
+
+        let output = serde::deserialize::<Profile>(&buffer[..n]).unwrap();
+        assert_eq!(SUT, output);
+    }
+
+    #[test]
+    fn profile_serde_rejects_invalid_ranges() {
+        let input = Profile::new("invalid", 60, 40, 25, 80, [0; 4]);
+
+        let mut buffer = [0u8; 60];
+        let n = serde::serialize(&input, &mut buffer).unwrap();
+
+        assert_eq!(Err(serde::Error::Other), serde::deserialize::<Profile>(&buffer[..n]));
+    }
+}
@@ -0,0 +1,281 @@
+//! # Power budget
+//!
+//! Estimates how long a battery lasts for a given device configuration. The
+//! energy model splits each wake into [`Phase`]s, each drawing a constant
+//! current for a given time, as described by a [`Chip`], and spreads the
+//! radio over the wakes which advertise. [`ESP32S3`] and [`ESP32C6`] provide
+//! typical figures for the supported boards, taken from their datasheets, so
+//! estimates are only as good as how close a board gets to them.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::power::{self, Config, ESP32C6};
+//! let config = Config {
+//!     interval_s: 300,
+//!     samples: 64,
+//!     sample_us: 100,
+//!     warmup_ms: 1000,
+//!     sensor_ua: 5_000,
+//!     advertise_every: 6,
+//!     advertise_ms: 5_000,
+//!     connected_ms: 0,
+//! };
+//! let estimate = power::estimate(&ESP32C6, &config);
+//! println!("{}uA, {} days on 2000mAh", estimate.average_ua(), estimate.days(2000));
+//! ```
+
+use crate::serde::{self, Deserializable, Serializable};
+
+/// Parts of a wake cycle, each drawing a different current.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Phase {
+    Boot,
+    Sampling,
+    RadioInit,
+    Advertising,
+    Connected,
+    DeepSleep,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Phase::Boot,
+        Phase::Sampling,
+        Phase::RadioInit,
+        Phase::Advertising,
+        Phase::Connected,
+        Phase::DeepSleep,
+    ];
+}
+
+/// Current drawn by a chip on each phase, in microamps, and how long the fixed
+/// phases last, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chip {
+    /// CPU running, radio off.
+    pub active_ua: u32,
+    /// Bringing up the radio.
+    pub radio_init_ua: u32,
+    /// Advertising, waiting for a connection.
+    pub advertising_ua: u32,
+    /// Serving a connected client.
+    pub connected_ua: u32,
+    /// Deep sleep, keeping the RTC memory.
+    pub sleep_ua: u32,
+    /// Time from wake-up to running the firmware.
+    pub boot_ms: u32,
+    /// Time to bring up the radio.
+    pub radio_init_ms: u32,
+}
+
+pub const ESP32S3: Chip = Chip {
+    active_ua: 45_000,
+    radio_init_ua: 95_000,
+    advertising_ua: 60_000,
+    connected_ua: 70_000,
+    sleep_ua: 8,
+    boot_ms: 250,
+    radio_init_ms: 400,
+};
+
+pub const ESP32C6: Chip = Chip {
+    active_ua: 27_000,
+    radio_init_ua: 70_000,
+    advertising_ua: 45_000,
+    connected_ua: 50_000,
+    sleep_ua: 9,
+    boot_ms: 200,
+    radio_init_ms: 300,
+};
+
+/// Chip a device is built for, as it reports it in its diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Model {
+    Esp32S3,
+    Esp32C6,
+}
+
+impl Model {
+    /// Typical figures of the chip.
+    pub fn chip(&self) -> &'static Chip {
+        match self {
+            Model::Esp32S3 => &ESP32S3,
+            Model::Esp32C6 => &ESP32C6,
+        }
+    }
+}
+
+impl Serializable for Model {
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        ser.write_u8(*self as u8)
+    }
+}
+
+impl Deserializable for Model {
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        match de.read_u8()? {
+            0 => Ok(Self::Esp32S3),
+            1 => Ok(Self::Esp32C6),
+            _ => Err(serde::Error::Other),
+        }
+    }
+}
+
+/// Device settings which affect power consumption.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    /// Time between wakes, in seconds.
+    pub interval_s: u32,
+    /// Samples taken on each wake.
+    pub samples: u8,
+    /// Time taken by each sample, in microseconds.
+    pub sample_us: u32,
+    /// Time the sensor warms up before sampling, in milliseconds.
+    pub warmup_ms: u32,
+    /// Current drawn by the sensor while powered, in microamps.
+    pub sensor_ua: u32,
+    /// Advertise every this many wakes, zero to never advertise.
+    pub advertise_every: u8,
+    /// Advertising window, in milliseconds.
+    pub advertise_ms: u32,
+    /// Expected time serving a client on each advertising window, in
+    /// milliseconds.
+    pub connected_ms: u32,
+}
+
+/// Charge drawn on each phase over a full cycle of wakes, one of which
+/// advertises.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    /// Charge per phase, in microamp-milliseconds.
+    charge: [u64; 6],
+    /// Length of the cycle, in milliseconds.
+    cycle_ms: u64,
+}
+
+impl Estimate {
+    /// Average current drawn, in microamps.
+    pub fn average_ua(&self) -> u32 {
+        (self.total() / self.cycle_ms.max(1)) as u32
+    }
+
+    /// Share of the total charge drawn on a phase, from 0 to 100.
+    pub fn share(&self, phase: Phase) -> u8 {
+        (self.charge[phase as usize] * 100 / self.total().max(1)) as u8
+    }
+
+    /// Days a fully charged battery lasts, given its capacity in milliamp-hours.
+    pub fn days(&self, capacity_mah: u32) -> u32 {
+        const DAY_MS: u128 = 24 * 60 * 60 * 1000;
+        let capacity = capacity_mah as u128 * 1000 * 60 * 60 * 1000;
+        (capacity * self.cycle_ms as u128 / (self.total().max(1) as u128 * DAY_MS)) as u32
+    }
+
+    fn total(&self) -> u64 {
+        self.charge.iter().sum()
+    }
+}
+
+/// Estimates the charge drawn by a chip running with the given settings.
+pub fn estimate(chip: &Chip, config: &Config) -> Estimate {
+    let wakes = config.advertise_every.max(1) as u64;
+    let radio = config.advertise_every > 0;
+
+    let sampling_ms =
+        config.warmup_ms as u64 + config.samples as u64 * config.sample_us as u64 / 1000;
+    let (radio_init_ms, advertise_ms, connected_ms) = if radio {
+        (chip.radio_init_ms as u64, config.advertise_ms as u64, config.connected_ms as u64)
+    } else {
+        (0, 0, 0)
+    };
+
+    let cycle_ms = wakes * config.interval_s as u64 * 1000;
+    let awake_ms =
+        wakes * (chip.boot_ms as u64 + sampling_ms) + radio_init_ms + advertise_ms + connected_ms;
+    let sleep_ms = cycle_ms.saturating_sub(awake_ms);
+
+    let mut charge = [0u64; 6];
+    charge[Phase::Boot as usize] = wakes * chip.boot_ms as u64 * chip.active_ua as u64;
+    charge[Phase::Sampling as usize] =
+        wakes * sampling_ms * (chip.active_ua as u64 + config.sensor_ua as u64);
+    charge[Phase::RadioInit as usize] = radio_init_ms * chip.radio_init_ua as u64;
+    charge[Phase::Advertising as usize] = advertise_ms * chip.advertising_ua as u64;
+    charge[Phase::Connected as usize] = connected_ms * chip.connected_ua as u64;
+    charge[Phase::DeepSleep as usize] = sleep_ms * chip.sleep_ua as u64;
+
+    Estimate { charge, cycle_ms: cycle_ms.max(awake_ms) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    const CHIP: Chip = Chip {
+        active_ua: 10_000,
+        radio_init_ua: 50_000,
+        advertising_ua: 20_000,
+        connected_ua: 30_000,
+        sleep_ua: 10,
+        boot_ms: 100,
+        radio_init_ms: 200,
+    };
+
+    const CONFIG: Config = Config {
+        interval_s: 600,
+        samples: 100,
+        sample_us: 100,
+        warmup_ms: 990,
+        sensor_ua: 0,
+        advertise_every: 2,
+        advertise_ms: 5_000,
+        connected_ms: 0,
+    };
+
+    #[test]
+    fn test_estimate() {
+        let sut = estimate(&CHIP, &CONFIG);
+
+        assert_eq!(119, sut.average_ua());
+        assert_eq!(347, sut.days(1000));
+    }
+
+    #[test_case(Phase::Boot, 1)]
+    #[test_case(Phase::Sampling, 13)]
+    #[test_case(Phase::RadioInit, 6)]
+    #[test_case(Phase::Advertising, 69)]
+    #[test_case(Phase::Connected, 0)]
+    #[test_case(Phase::DeepSleep, 8)]
+    fn test_share(phase: Phase, expected: u8) {
+        assert_eq!(expected, estimate(&CHIP, &CONFIG).share(phase));
+    }
+
+    #[test]
+    fn test_never_advertising() {
+        let config = Config { advertise_every: 0, ..CONFIG };
+
+        let sut = estimate(&CHIP, &config);
+        assert_eq!(0, sut.share(Phase::Advertising));
+        assert_eq!(28, sut.average_ua());
+    }
+
+    #[test]
+    fn test_advertising_less_often_lasts_longer() {
+        let often = estimate(&ESP32S3, &CONFIG);
+        let rarely = estimate(&ESP32S3, &Config { advertise_every: 12, ..CONFIG });
+
+        assert!(rarely.days(2000) > often.days(2000));
+    }
+
+    #[test]
+    fn test_awake_longer_than_interval() {
+        let config = Config { interval_s: 1, ..CONFIG };
+
+        let sut = estimate(&CHIP, &config);
+        assert_eq!(0, sut.share(Phase::DeepSleep));
+        assert!(sut.average_ua() > CHIP.active_ua);
+    }
+}
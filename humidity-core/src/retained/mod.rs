@@ -0,0 +1,261 @@
+//! # Retained memory
+//!
+//! State kept in RTC memory across deep sleeps holds garbage after a cold boot,
+//! or when a firmware update changed its layout. [`Retained`] stores a magic
+//! number, a layout version, the size of the payload and a checksum next to
+//! it, and resets the payload to its default whenever any of them does not
+//! match. The checksum is updated every time a [`RetainedMut`] borrow ends, or
+//! is sealed along the way.
+//!
+//! The checksum goes through the raw bytes of the payload, so that it is never
+//! read as a `T` before it matched: a flipped enum discriminant or `Option` tag
+//! in garbage memory would not be a valid value. Padding bytes count too. They
+//! are zeroed on reset, and every write ends with a new checksum, over the
+//! bytes as they then are. The payload must be plain data: no pointers, nor
+//! anything else which does not make sense after a reset.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::retained::Retained;
+//! static mut WAKES: Retained<u32, 1> = Retained::new();
+//! let wakes = unsafe { &mut *core::ptr::addr_of_mut!(WAKES) };
+//! if !wakes.validate() {
+//!     println!("cold boot");
+//! }
+//! *wakes.get_mut() += 1;
+//! assert_eq!(1, *wakes.get());
+//! ```
+
+use core::{
+    mem::{size_of, MaybeUninit},
+    ops::{Deref, DerefMut},
+};
+
+const MAGIC: u32 = 0x484d_4454;
+
+/// Payload of type `T` with its integrity check. Bump `VERSION` whenever the
+/// meaning of the payload changes without changing its size.
+#[repr(C)]
+pub struct Retained<T, const VERSION: u16> {
+    magic: u32,
+    version: u16,
+    size: u32,
+    checksum: u32,
+    value: MaybeUninit<T>,
+}
+
+impl<T: Default, const VERSION: u16> Default for Retained<T, VERSION> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Default, const VERSION: u16> Retained<T, VERSION> {
+    /// Creates an invalid container, initialised on first use.
+    pub const fn new() -> Self {
+        Self { magic: 0, version: 0, size: 0, checksum: 0, value: MaybeUninit::uninit() }
+    }
+
+    /// Checks the payload, resetting it to its default when invalid. Returns
+    /// whether it was valid, that is whether it survived since the last wake.
+    pub fn validate(&mut self) -> bool {
+        let valid = self.magic == MAGIC
+            && self.version == VERSION
+            && self.size == size_of::<T>() as u32
+            && self.checksum == self.compute_checksum();
+        if !valid {
+            self.reset();
+        }
+        valid
+    }
+
+    /// Borrows the payload, validating it first.
+    pub fn get(&mut self) -> &T {
+        self.validate();
+        // SAFETY: validate initialises the value when it was not.
+        unsafe { self.value.assume_init_ref() }
+    }
+
+    /// Mutably borrows the payload, validating it first. The checksum is
+    /// updated once the borrow ends.
+    pub fn get_mut(&mut self) -> RetainedMut<'_, T, VERSION> {
+        self.validate();
+        RetainedMut { owner: self }
+    }
+
+    fn reset(&mut self) {
+        // Zero everything first so padding does not hold garbage.
+        // SAFETY: the pointer is valid for a single T, and any byte pattern is a
+        // valid MaybeUninit<T>.
+        unsafe { self.value.as_mut_ptr().write_bytes(0, 1) };
+        self.value.write(T::default());
+        self.magic = MAGIC;
+        self.version = VERSION;
+        self.size = size_of::<T>() as u32;
+        self.seal();
+    }
+
+    fn seal(&mut self) {
+        self.checksum = self.compute_checksum();
+    }
+
+    /// FNV-1a over the bytes of the payload.
+    fn compute_checksum(&self) -> u32 {
+        let ptr = self.value.as_ptr() as *const u8;
+        (0..size_of::<T>()).fold(0x811c_9dc5, |hash: u32, i| {
+            // SAFETY: within the bounds of the payload. Volatile reads keep the
+            // compiler from assuming anything about contents it did not write.
+            let byte = unsafe { ptr.add(i).read_volatile() };
+            (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+        })
+    }
+}
+
+/// Mutable borrow of a [`Retained`] payload, updating its checksum when
+/// dropped.
+pub struct RetainedMut<'a, T: Default, const VERSION: u16> {
+    owner: &'a mut Retained<T, VERSION>,
+}
+
+impl<T: Default, const VERSION: u16> RetainedMut<'_, T, VERSION> {
+    /// Updates the checksum without ending the borrow, so that changes made so
+    /// far survive should the borrow never end, as on a panic.
+    pub fn seal(&mut self) {
+        self.owner.seal();
+    }
+}
+
+impl<T: Default, const VERSION: u16> Deref for RetainedMut<'_, T, VERSION> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the owner was validated when borrowed.
+        unsafe { self.owner.value.assume_init_ref() }
+    }
+}
+
+impl<T: Default, const VERSION: u16> DerefMut for RetainedMut<'_, T, VERSION> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the owner was validated when borrowed.
+        unsafe { self.owner.value.assume_init_mut() }
+    }
+}
+
+impl<T: Default, const VERSION: u16> Drop for RetainedMut<'_, T, VERSION> {
+    fn drop(&mut self) {
+        self.owner.seal();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Default, Clone, Copy, PartialEq)]
+    #[repr(C)]
+    struct State {
+        wakes: u32,
+        last: [u8; 4],
+    }
+
+    fn corrupt<T, const VERSION: u16>(sut: &mut Retained<T, VERSION>, offset: usize) {
+        let ptr = sut as *mut Retained<T, VERSION> as *mut u8;
+        unsafe { *ptr.add(offset) ^= 0xff };
+    }
+
+    fn offset_of_value<T, const VERSION: u16>(sut: &Retained<T, VERSION>) -> usize {
+        sut.value.as_ptr() as usize - sut as *const Retained<T, VERSION> as usize
+    }
+
+    #[test]
+    fn test_cold_boot() {
+        let mut sut = Retained::<State, 1>::new();
+
+        assert!(!sut.validate());
+        assert_eq!(&State::default(), sut.get());
+        assert!(sut.validate());
+    }
+
+    #[test]
+    fn test_changes_survive() {
+        let mut sut = Retained::<State, 1>::new();
+
+        {
+            let mut state = sut.get_mut();
+            state.wakes += 1;
+            state.last = [1, 2, 3, 4];
+        }
+        sut.get_mut().wakes += 1;
+
+        assert!(sut.validate());
+        assert_eq!(&State { wakes: 2, last: [1, 2, 3, 4] }, sut.get());
+    }
+
+    #[test]
+    fn test_sealed_changes_survive_a_borrow_never_ending() {
+        let mut sut = Retained::<State, 1>::new();
+
+        let mut state = sut.get_mut();
+        state.wakes = 10;
+        state.seal();
+        core::mem::forget(state);
+
+        assert!(sut.validate());
+        assert_eq!(10, sut.get().wakes);
+    }
+
+    #[test]
+    fn test_garbage_is_reset() {
+        let mut sut = Retained::<State, 1>::new();
+        sut.get_mut().wakes = 10;
+
+        let offset = offset_of_value(&sut);
+        corrupt(&mut sut, offset + 5);
+
+        assert!(!sut.validate());
+        assert_eq!(&State::default(), sut.get());
+    }
+
+    #[test]
+    fn test_invalid_value_is_reset() {
+        #[derive(Debug, Default, PartialEq)]
+        #[repr(C)]
+        struct Tagged {
+            wakes: Option<u32>,
+        }
+        let mut sut = Retained::<Tagged, 1>::new();
+        sut.get_mut().wakes = Some(10);
+
+        // Not a valid Option tag, never to be read as one.
+        let offset = offset_of_value(&sut);
+        corrupt(&mut sut, offset);
+
+        assert!(!sut.validate());
+        assert_eq!(None, sut.get().wakes);
+    }
+
+    #[test]
+    fn test_bad_header_is_reset() {
+        let mut sut = Retained::<State, 1>::new();
+        sut.get_mut().wakes = 10;
+
+        corrupt(&mut sut, 0);
+
+        assert!(!sut.validate());
+        assert_eq!(0, sut.get().wakes);
+    }
+
+    #[test]
+    fn test_version_change_is_reset() {
+        let mut sut = Retained::<State, 1>::new();
+        sut.get_mut().wakes = 10;
+
+        // Same memory, as seen by a firmware with a newer layout version.
+        let mut sut =
+            unsafe { core::mem::transmute::<Retained<State, 1>, Retained<State, 2>>(sut) };
+
+        assert!(!sut.validate());
+        assert_eq!(0, sut.get().wakes);
+    }
+}
@@ -0,0 +1,420 @@
+//! # Remote procedure calls
+//!
+//! Commands sent to the device over a single pair of characteristics, rather
+//! than a characteristic each: clients write requests to one, and the device
+//! notifies the responses on the other.
+//!
+//! A request is made of an [`Opcode`], an id chosen by the client, and the
+//! arguments of the command. Responses repeat the id, followed by a [`Code`]
+//! and the results, so that clients match them with their requests. Commands
+//! returning several items, such as a range of the history, get a response
+//! with [`Code::More`] for each item, and end with [`Code::Done`].
+//!
+//! Frames fit in a notification of the default ATT MTU, so clients need not
+//! negotiate a larger one.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::{
+//!     rpc::{Opcode, Request, Response, FRAME_LEN},
+//!     sensors::Hygrometer,
+//! };
+//! let mut frame = [0u8; FRAME_LEN];
+//! let n = Request::SetTime(1_700_000_000).encode(7, &mut frame).unwrap();
+//! assert_eq!(Ok((7, Request::SetTime(1_700_000_000))), Request::decode(&frame[..n]));
+//!
+//! let pong = Response::<Hygrometer>::Pong(42);
+//! let n = pong.encode(7, &mut frame).unwrap();
+//! assert_eq!(Ok((7, pong)), Response::decode(Opcode::Ping, &frame[..n]));
+//! ```
+
+pub use server::{Commands, Frame, Server};
+
+use crate::{
+    battery::Battery,
+    power::Model,
+    sample::Record,
+    sensors::Sensor,
+    serde::{self, Deserializable, Deserializer, Serializable, Serializer},
+    shared::Status,
+};
+
+mod server;
+
+/// Largest size of a request or a response, the payload of a notification
+/// with the default ATT MTU.
+pub const FRAME_LEN: usize = 20;
+
+/// Commands the device takes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Opcode {
+    Ping = 0x01,
+    GetHistoryRange = 0x02,
+    ClearHistory = 0x03,
+    SetTime = 0x04,
+    TriggerSample = 0x05,
+    Reboot = 0x06,
+    GetDiagnostics = 0x07,
+    ResetInterlock = 0x08,
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Error> {
+        match value {
+            0x01 => Ok(Self::Ping),
+            0x02 => Ok(Self::GetHistoryRange),
+            0x03 => Ok(Self::ClearHistory),
+            0x04 => Ok(Self::SetTime),
+            0x05 => Ok(Self::TriggerSample),
+            0x06 => Ok(Self::Reboot),
+            0x07 => Ok(Self::GetDiagnostics),
+            0x08 => Ok(Self::ResetInterlock),
+            _ => Err(Error::ErrOpcode(value)),
+        }
+    }
+}
+
+/// Outcome of a request, sent with every response.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Code {
+    /// The command completed, with the last of the results if any.
+    Done = 0x00,
+    /// One of several results, more follow.
+    More = 0x01,
+    /// The device does not know the command.
+    ErrOpcode = 0x80,
+    /// The request is cut short.
+    ErrLength = 0x81,
+    /// An argument is out of range.
+    ErrArgument = 0x82,
+    /// The device failed to carry the command out.
+    ErrInternal = 0x83,
+}
+
+impl Code {
+    pub fn is_err(&self) -> bool {
+        *self as u8 & 0x80 != 0
+    }
+}
+
+impl TryFrom<u8> for Code {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Error> {
+        match value {
+            0x00 => Ok(Self::Done),
+            0x01 => Ok(Self::More),
+            0x80 => Ok(Self::ErrOpcode),
+            0x81 => Ok(Self::ErrLength),
+            0x82 => Ok(Self::ErrArgument),
+            0x83 => Ok(Self::ErrInternal),
+            _ => Err(Error::ErrCode(value)),
+        }
+    }
+}
+
+/// Errors that can happen while encoding or decoding a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The frame is cut short, or the buffer too small for it.
+    ErrLength,
+    /// A request with an unknown opcode.
+    ErrOpcode(u8),
+    /// A response with an unknown code.
+    ErrCode(u8),
+    /// A response with results the request does not return.
+    ErrResults,
+}
+
+impl From<serde::Error> for Error {
+    fn from(_: serde::Error) -> Self {
+        Self::ErrLength
+    }
+}
+
+impl Error {
+    /// Code answering a request that could not be decoded.
+    pub fn code(&self) -> Code {
+        match self {
+            Self::ErrOpcode(_) => Code::ErrOpcode,
+            Self::ErrLength => Code::ErrLength,
+            Self::ErrCode(_) | Self::ErrResults => Code::ErrInternal,
+        }
+    }
+}
+
+/// A command, with its arguments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Request {
+    /// Checks the device answers, which returns its clock.
+    Ping,
+    /// Reads records of the history, from the oldest one kept.
+    GetHistoryRange { start: u16, count: u16 },
+    /// Drops every record of the history.
+    ClearHistory,
+    /// Sets the time, in seconds since the Unix epoch.
+    SetTime(u32),
+    /// Samples the soil again, rather than waiting for the next measurement.
+    TriggerSample,
+    /// Restarts the device.
+    Reboot,
+    /// Reads the state of the device.
+    GetDiagnostics,
+    /// Lets the pump run again after it was halted for having no effect, once
+    /// the reservoir was refilled.
+    ResetInterlock,
+}
+
+impl Request {
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            Self::Ping => Opcode::Ping,
+            Self::GetHistoryRange { .. } => Opcode::GetHistoryRange,
+            Self::ClearHistory => Opcode::ClearHistory,
+            Self::SetTime(_) => Opcode::SetTime,
+            Self::TriggerSample => Opcode::TriggerSample,
+            Self::Reboot => Opcode::Reboot,
+            Self::GetDiagnostics => Opcode::GetDiagnostics,
+            Self::ResetInterlock => Opcode::ResetInterlock,
+        }
+    }
+
+    /// Encodes the request frame, and returns its size.
+    pub fn encode(&self, id: u8, out: &mut [u8]) -> Result<usize, Error> {
+        let mut ser = Serializer::new(out);
+        let mut n = ser.write_u8(self.opcode() as u8)?;
+        n += ser.write_u8(id)?;
+        match *self {
+            Self::GetHistoryRange { start, count } => {
+                n += ser.write_u16(start)?;
+                n += ser.write_u16(count)?;
+            }
+            Self::SetTime(unix) => n += ser.write_u32(unix)?,
+            _ => {}
+        }
+        Ok(n)
+    }
+
+    /// Decodes a request frame into its id and the request, ignoring trailing
+    /// bytes.
+    pub fn decode(frame: &[u8]) -> Result<(u8, Self), Error> {
+        let [opcode, id, ..] = *frame else {
+            return Err(Error::ErrLength);
+        };
+        let mut de = Deserializer::new(&frame[2..]);
+        let request = match Opcode::try_from(opcode)? {
+            Opcode::Ping => Self::Ping,
+            Opcode::GetHistoryRange => {
+                Self::GetHistoryRange { start: de.read_u16()?, count: de.read_u16()? }
+            }
+            Opcode::ClearHistory => Self::ClearHistory,
+            Opcode::SetTime => Self::SetTime(de.read_u32()?),
+            Opcode::TriggerSample => Self::TriggerSample,
+            Opcode::Reboot => Self::Reboot,
+            Opcode::GetDiagnostics => Self::GetDiagnostics,
+            Opcode::ResetInterlock => Self::ResetInterlock,
+        };
+        Ok((id, request))
+    }
+}
+
+/// State of the device, as of the advertising window.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Diagnostics {
+    /// Time on the device clock, in seconds.
+    pub now: u32,
+    /// Advertisements so far.
+    pub sequence: u32,
+    /// Records in the history.
+    pub records: u16,
+    pub status: Status,
+    /// Last battery reading, if any.
+    pub battery: Option<Battery>,
+    /// Chip of the device, if it tells.
+    pub model: Option<Model>,
+}
+
+impl Serializable for Diagnostics {
+    fn serialize(&self, ser: &mut Serializer) -> Result<usize, serde::Error> {
+        let mut n = ser.write_u32(self.now)?;
+        n += ser.write_u32(self.sequence)?;
+        n += ser.write_u16(self.records)?;
+        n += ser.write_u8(self.status.bits())?;
+        n += self.battery.serialize(ser)?;
+        n += self.model.serialize(ser)?;
+        Ok(n)
+    }
+}
+
+impl Deserializable for Diagnostics {
+    fn deserialize(de: &mut Deserializer) -> Result<Self, serde::Error> {
+        Ok(Self {
+            now: de.read_u32()?,
+            sequence: de.read_u32()?,
+            records: de.read_u16()?,
+            status: Status::from_bits(de.read_u8()?),
+            battery: Option::deserialize(de)?,
+            model: Option::deserialize(de)?,
+        })
+    }
+}
+
+/// An answer to a request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Response<S: Sensor> {
+    /// The command completed, without results.
+    Done,
+    /// Answers a [`Request::Ping`] with the device clock, in seconds.
+    Pong(u32),
+    /// One of the records of a range, oldest first.
+    Record(Record<S>),
+    Diagnostics(Diagnostics),
+    /// The command was not carried out.
+    Failed(Code),
+}
+
+impl<S: Sensor> Response<S> {
+    pub fn code(&self) -> Code {
+        match self {
+            Self::Done | Self::Pong(_) | Self::Diagnostics(_) => Code::Done,
+            Self::Record(_) => Code::More,
+            Self::Failed(code) => *code,
+        }
+    }
+
+    /// Encodes the response frame, and returns its size.
+    pub fn encode(&self, id: u8, out: &mut [u8]) -> Result<usize, Error> {
+        let mut ser = Serializer::new(out);
+        let mut n = ser.write_u8(id)?;
+        n += ser.write_u8(self.code() as u8)?;
+        n += match self {
+            Self::Done | Self::Failed(_) => 0,
+            Self::Pong(now) => ser.write_u32(*now)?,
+            Self::Record(record) => record.serialize(&mut ser)?,
+            Self::Diagnostics(diagnostics) => diagnostics.serialize(&mut ser)?,
+        };
+        Ok(n)
+    }
+
+    /// Decodes a response frame into its id and the response, knowing the
+    /// command it answers.
+    pub fn decode(opcode: Opcode, frame: &[u8]) -> Result<(u8, Self), Error> {
+        let [id, code, ..] = *frame else {
+            return Err(Error::ErrLength);
+        };
+        let mut de = Deserializer::new(&frame[2..]);
+        let response = match (Code::try_from(code)?, opcode) {
+            (code, _) if code.is_err() => Self::Failed(code),
+            (Code::Done, Opcode::Ping) => Self::Pong(de.read_u32()?),
+            (Code::Done, Opcode::GetDiagnostics) => {
+                Self::Diagnostics(Diagnostics::deserialize(&mut de)?)
+            }
+            (Code::Done, _) => Self::Done,
+            (Code::More, Opcode::GetHistoryRange) => Self::Record(Record::deserialize(&mut de)?),
+            (Code::More, _) => return Err(Error::ErrResults),
+            (code, _) => return Err(Error::ErrCode(code as u8)),
+        };
+        Ok((id, response))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        battery::Chemistry,
+        irrigation::Fault,
+        sample::Summary,
+        sensors::{Compensation, Hygrometer},
+    };
+    use test_case::test_case;
+
+    const BATTERY: Battery = Battery { millivolts: 3700, chemistry: Chemistry::LiIon };
+
+    #[test_case(Request::Ping, &[0x01, 9] ; "ping")]
+    #[test_case(Request::GetHistoryRange { start: 2, count: 300 }, &[0x02, 9, 2, 0, 0x2c, 0x01] ; "history range")]
+    #[test_case(Request::ClearHistory, &[0x03, 9] ; "clear history")]
+    #[test_case(Request::SetTime(0x01020304), &[0x04, 9, 4, 3, 2, 1] ; "set time")]
+    #[test_case(Request::TriggerSample, &[0x05, 9] ; "trigger sample")]
+    #[test_case(Request::Reboot, &[0x06, 9] ; "reboot")]
+    #[test_case(Request::GetDiagnostics, &[0x07, 9] ; "diagnostics")]
+    #[test_case(Request::ResetInterlock, &[0x08, 9] ; "reset interlock")]
+    fn test_request(request: Request, expected: &[u8]) {
+        let mut frame = [0u8; FRAME_LEN];
+        let n = request.encode(9, &mut frame).unwrap();
+
+        assert_eq!(expected, &frame[..n]);
+        assert_eq!(Ok((9, request)), Request::decode(&frame[..n]));
+    }
+
+    #[test_case(&[] => Err(Error::ErrLength) ; "empty")]
+    #[test_case(&[0x04, 1, 0, 0] => Err(Error::ErrLength) ; "short arguments")]
+    #[test_case(&[0x42, 1] => Err(Error::ErrOpcode(0x42)) ; "unknown opcode")]
+    #[test_case(&[0x01, 1, 0xff] => Ok((1, Request::Ping)) ; "trailing bytes")]
+    fn test_decode_request(frame: &[u8]) -> Result<(u8, Request), Error> {
+        Request::decode(frame)
+    }
+
+    #[test]
+    fn test_largest_record_fits() {
+        let compensation = Some(Compensation { temperature: -500, coefficient: 12 });
+        let summary =
+            Summary { n: 64, avg: 1, min: 1, max: 1, sensor: Hygrometer::HW390, compensation };
+        let sut = Response::Record(Record {
+            summary,
+            battery: Some(BATTERY),
+            fault: Some(Fault::NoEffect),
+        });
+        let mut frame = [0u8; FRAME_LEN];
+
+        let n = sut.encode(3, &mut frame).unwrap();
+
+        assert_eq!(FRAME_LEN, n);
+        assert_eq!(Ok((3, sut)), Response::decode(Opcode::GetHistoryRange, &frame));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut status = Status::empty();
+        status.insert(crate::shared::Flag::Dry);
+        let diagnostics = Diagnostics {
+            now: 3600,
+            sequence: 12,
+            records: 5,
+            status,
+            battery: Some(BATTERY),
+            model: Some(Model::Esp32C6),
+        };
+        let sut = Response::<Hygrometer>::Diagnostics(diagnostics);
+        let mut frame = [0u8; FRAME_LEN];
+
+        let n = sut.encode(1, &mut frame).unwrap();
+
+        assert_eq!(Ok((1, sut)), Response::decode(Opcode::GetDiagnostics, &frame[..n]));
+    }
+
+    #[test_case(Opcode::Reboot, &[5, 0x00] => Ok((5, Response::Done)) ; "done")]
+    #[test_case(Opcode::Ping, &[5, 0x81] => Ok((5, Response::Failed(Code::ErrLength))) ; "failed")]
+    #[test_case(Opcode::Ping, &[5, 0x00] => Err(Error::ErrLength) ; "missing results")]
+    #[test_case(Opcode::Ping, &[5, 0x01] => Err(Error::ErrResults) ; "unexpected results")]
+    #[test_case(Opcode::Ping, &[5, 0x7f] => Err(Error::ErrCode(0x7f)) ; "unknown code")]
+    fn test_decode_response(
+        opcode: Opcode,
+        frame: &[u8],
+    ) -> Result<(u8, Response<Hygrometer>), Error> {
+        Response::decode(opcode, frame)
+    }
+
+    #[test]
+    fn test_small_buffer() {
+        let mut frame = [0u8; 4];
+
+        assert_eq!(Err(Error::ErrLength), Request::SetTime(1).encode(1, &mut frame));
+        assert_eq!(Err(Error::ErrLength), Response::<Hygrometer>::Pong(1).encode(1, &mut frame));
+    }
+}
@@ -0,0 +1,340 @@
+use crate::{
+    historical::Historical,
+    lifecycle::{Content, HISTORY_LEN},
+    power::Model,
+    sample::Record,
+    sensors::Sensor,
+};
+
+use super::{Code, Diagnostics, Request, Response, FRAME_LEN};
+
+/// Commands changing the device, requested by a client. They are carried out
+/// once the client disconnects, as the radio keeps the device busy until then.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Commands {
+    pub clear_history: bool,
+    /// Time set by the client, in seconds since the Unix epoch, with the time
+    /// of the device clock it was set at.
+    pub time: Option<(u32, u32)>,
+    pub sample: bool,
+    pub reboot: bool,
+    pub reset_interlock: bool,
+}
+
+/// An encoded response, to notify.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    data: [u8; FRAME_LEN],
+    len: usize,
+}
+
+impl Frame {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+enum Pending<S: Sensor> {
+    Idle,
+    Once(u8, Response<S>),
+    /// Records left to send, by position in the history.
+    Range {
+        id: u8,
+        next: usize,
+        end: usize,
+    },
+}
+
+/// Answers the requests of a client during an advertising window. Requests
+/// are taken one at a time: a new one drops what is left of the previous one.
+pub struct Server<'a, S: Sensor> {
+    history: &'a Historical<HISTORY_LEN, Record<S>>,
+    diagnostics: Diagnostics,
+    commands: Commands,
+    pending: Pending<S>,
+}
+
+impl<'a, S: Sensor + Copy> Server<'a, S> {
+    /// Serves the content, `now` being the time on the device clock.
+    pub fn new(content: &Content<'a, S>, now: u32) -> Self {
+        let diagnostics = Diagnostics {
+            now,
+            sequence: content.sequence,
+            records: content.history.len() as u16,
+            status: content.broadcast.status,
+            battery: content.battery,
+            model: None,
+        };
+        Self {
+            history: content.history,
+            diagnostics,
+            commands: Commands::default(),
+            pending: Pending::Idle,
+        }
+    }
+
+    /// Moves the time of the device clock on, for the requests taken from then.
+    pub fn set_now(&mut self, now: u32) {
+        self.diagnostics.now = now;
+    }
+
+    /// Tells the clients which chip the device runs on.
+    pub fn set_model(&mut self, model: Model) {
+        self.diagnostics.model = Some(model);
+    }
+
+    /// Commands to carry out after the connection.
+    pub fn commands(&self) -> Commands {
+        self.commands
+    }
+
+    /// Takes a request frame, written by the client.
+    pub fn request(&mut self, frame: &[u8]) {
+        let (id, request) = match Request::decode(frame) {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                let id = frame.get(1).copied().unwrap_or_default();
+                self.pending = Pending::Once(id, Response::Failed(err.code()));
+                return;
+            }
+        };
+        let response = match request {
+            Request::Ping => Response::Pong(self.diagnostics.now),
+            Request::GetHistoryRange { start, count } => {
+                let (start, len) = (start as usize, self.history.len());
+                if start > len {
+                    Response::Failed(Code::ErrArgument)
+                } else {
+                    let end = len.min(start + count as usize);
+                    self.pending = Pending::Range { id, next: start, end };
+                    return;
+                }
+            }
+            Request::ClearHistory => {
+                self.commands.clear_history = true;
+                Response::Done
+            }
+            Request::SetTime(unix) => {
+                self.commands.time = Some((self.diagnostics.now, unix));
+                Response::Done
+            }
+            Request::TriggerSample => {
+                self.commands.sample = true;
+                Response::Done
+            }
+            Request::Reboot => {
+                self.commands.reboot = true;
+                Response::Done
+            }
+            Request::GetDiagnostics => Response::Diagnostics(self.diagnostics),
+            Request::ResetInterlock => {
+                self.commands.reset_interlock = true;
+                Response::Done
+            }
+        };
+        self.pending = Pending::Once(id, response);
+    }
+
+    /// Returns the next response to notify, if any.
+    pub fn response(&mut self) -> Option<Frame> {
+        let (id, response) = match self.pending {
+            Pending::Idle => return None,
+            Pending::Once(id, response) => {
+                self.pending = Pending::Idle;
+                (id, response)
+            }
+            Pending::Range { id, next, end } if next < end => {
+                self.pending = Pending::Range { id, next: next + 1, end };
+                match self.history.get(next) {
+                    Some(record) => (id, Response::Record(*record)),
+                    None => (id, Response::Failed(Code::ErrInternal)),
+                }
+            }
+            Pending::Range { id, .. } => {
+                self.pending = Pending::Idle;
+                (id, Response::Done)
+            }
+        };
+        let mut data = [0u8; FRAME_LEN];
+        let len = response
+            .encode(id, &mut data)
+            .or_else(|_| Response::<S>::Failed(Code::ErrInternal).encode(id, &mut data))
+            .unwrap_or_default();
+        Some(Frame { data, len })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        battery::{Battery, Chemistry},
+        rpc::Opcode,
+        sample::Summary,
+        sensors::Hygrometer,
+        shared::Broadcast,
+        time::WallClock,
+    };
+
+    const BATTERY: Battery = Battery { millivolts: 3700, chemistry: Chemistry::LiIon };
+    static STAMPS: Historical<HISTORY_LEN, u32> = Historical::new();
+
+    fn record(avg: u16) -> Record<Hygrometer> {
+        let summary = Summary {
+            n: 1,
+            avg,
+            min: avg,
+            max: avg,
+            sensor: Hygrometer::HW390,
+            compensation: None,
+        };
+        Record { summary, battery: Some(BATTERY), fault: None }
+    }
+
+    fn content(history: &Historical<HISTORY_LEN, Record<Hygrometer>>) -> Content<'_, Hygrometer> {
+        Content {
+            history,
+            stamps: &STAMPS,
+            clock: WallClock::new(),
+            last_sample: None,
+            battery: Some(BATTERY),
+            broadcast: Broadcast::default(),
+            sequence: 4,
+            next_sample: 300,
+            interval: 300,
+        }
+    }
+
+    type Responses = [Option<Response<Hygrometer>>; 4];
+
+    /// Sends a request, and decodes up to four of its responses.
+    fn call(sut: &mut Server<Hygrometer>, request: Request) -> Responses {
+        let mut frame = [0u8; FRAME_LEN];
+        let n = request.encode(1, &mut frame).unwrap();
+        sut.request(&frame[..n]);
+        let mut responses = [None; 4];
+        for (response, frame) in responses.iter_mut().zip(core::iter::from_fn(|| sut.response())) {
+            let (id, decoded) = Response::decode(request.opcode(), frame.as_bytes()).unwrap();
+            assert_eq!(1, id);
+            *response = Some(decoded);
+        }
+        responses
+    }
+
+    fn expected(responses: &[Response<Hygrometer>]) -> Responses {
+        let mut expected = [None; 4];
+        expected.iter_mut().zip(responses).for_each(|(slot, response)| *slot = Some(*response));
+        expected
+    }
+
+    #[test]
+    fn test_ping() {
+        let history = Historical::new();
+        let content = content(&history);
+        let mut sut = Server::new(&content, 120);
+
+        assert_eq!(expected(&[Response::Pong(120)]), call(&mut sut, Request::Ping));
+        assert_eq!(None, sut.response());
+    }
+
+    #[test]
+    fn test_history_range() {
+        let mut history = Historical::new();
+        (1..=3).for_each(|avg| history.store(record(avg)));
+        let content = content(&history);
+        let mut sut = Server::new(&content, 0);
+
+        assert_eq!(
+            expected(&[Response::Record(record(2)), Response::Record(record(3)), Response::Done]),
+            call(&mut sut, Request::GetHistoryRange { start: 1, count: 10 })
+        );
+        assert_eq!(
+            expected(&[Response::Done]),
+            call(&mut sut, Request::GetHistoryRange { start: 3, count: 1 })
+        );
+        assert_eq!(
+            expected(&[Response::Failed(Code::ErrArgument)]),
+            call(&mut sut, Request::GetHistoryRange { start: 4, count: 1 })
+        );
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut history = Historical::new();
+        history.store(record(1));
+        let content = content(&history);
+        let mut sut = Server::new(&content, 60);
+        sut.set_model(Model::Esp32S3);
+
+        let diagnostics = Diagnostics {
+            now: 60,
+            sequence: 4,
+            records: 1,
+            status: Default::default(),
+            battery: Some(BATTERY),
+            model: Some(Model::Esp32S3),
+        };
+        assert_eq!(
+            expected(&[Response::Diagnostics(diagnostics)]),
+            call(&mut sut, Request::GetDiagnostics)
+        );
+    }
+
+    #[test]
+    fn test_commands() {
+        let history = Historical::new();
+        let content = content(&history);
+        let mut sut = Server::new(&content, 0);
+        sut.set_now(30);
+
+        for request in [
+            Request::ClearHistory,
+            Request::SetTime(1_700_000_000),
+            Request::TriggerSample,
+            Request::ResetInterlock,
+        ] {
+            assert_eq!(expected(&[Response::Done]), call(&mut sut, request));
+        }
+
+        let commands = Commands {
+            clear_history: true,
+            time: Some((30, 1_700_000_000)),
+            sample: true,
+            reboot: false,
+            reset_interlock: true,
+        };
+        assert_eq!(commands, sut.commands());
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        let history = Historical::new();
+        let content = content(&history);
+        let mut sut = Server::new(&content, 0);
+
+        sut.request(&[0x42, 8]);
+        let frame = sut.response().unwrap();
+        assert_eq!(
+            Ok((8, Response::<Hygrometer>::Failed(Code::ErrOpcode))),
+            Response::decode(Opcode::Ping, frame.as_bytes())
+        );
+
+        sut.request(&[0x04, 9, 1]);
+        assert_eq!(&[9, Code::ErrLength as u8], sut.response().unwrap().as_bytes());
+        assert_eq!(Commands::default(), sut.commands());
+    }
+
+    #[test]
+    fn test_new_request_drops_previous_one() {
+        let mut history = Historical::new();
+        (1..=3).for_each(|avg| history.store(record(avg)));
+        let content = content(&history);
+        let mut sut = Server::new(&content, 5);
+        let mut frame = [0u8; FRAME_LEN];
+        let n = Request::GetHistoryRange { start: 0, count: 3 }.encode(1, &mut frame).unwrap();
+        sut.request(&frame[..n]);
+        sut.response();
+
+        assert_eq!(expected(&[Response::Pong(5)]), call(&mut sut, Request::Ping));
+    }
+}
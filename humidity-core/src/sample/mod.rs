@@ -1,12 +1,18 @@
 //! # Sampling results
 //!
 //! Establish a common ground to work with the results of a sampling operation.
-//! Uses [`Summary`] to hold the results of a sampling operation.
+//! Uses [`Summary`] to hold the results of a sampling operation, and [`Record`]
+//! to hold everything measured during a wake, and [`Rollup`] to aggregate the
+//! samples of a longer period.
 
+pub use record::Record;
+pub use rollup::Rollup;
 pub use summary::Summary;
 
 use crate::sensors;
 
+mod record;
+mod rollup;
 mod summary;
 
 pub fn perform_sampling<SENSOR: sensors::Sensor>(
@@ -15,6 +21,7 @@ pub fn perform_sampling<SENSOR: sensors::Sensor>(
     warmup_delay: &mut impl FnMut(),
     adc_read: &mut impl FnMut() -> u16,
     sensor: SENSOR,
+    temperature: Option<i16>,
 ) -> Summary<SENSOR> {
     let mut sum = 0u32;
     let mut min = u16::MAX;
@@ -37,6 +44,9 @@ pub fn perform_sampling<SENSOR: sensors::Sensor>(
     }
     toggle_sensor();
 
-    let avg = sum.div_ceil(n as u32) as u16;
-    Summary::<SENSOR> { n, avg, min, max, sensor }
+    // No readings at all summarise as zeros, instead of dividing by zero.
+    let avg = sum.div_ceil((n as u32).max(1)) as u16;
+    let min = min.min(max);
+    let compensation = temperature.map(|temperature| sensor.compensation(temperature));
+    Summary::<SENSOR> { n, avg, min, max, sensor, compensation }
 }
@@ -0,0 +1,83 @@
+use crate::{
+    battery::{Battery, Chemistry},
+    irrigation::Fault,
+    sensors,
+    serde::{self, Deserializable, Serializable},
+};
+
+use super::Summary;
+
+const UNKNOWN_BATTERY: Battery = Battery { millivolts: 0, chemistry: Chemistry::LiIon };
+
+/// Everything measured during a single wake, as stored in the history.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Record<SENSOR>
+where
+    SENSOR: sensors::Sensor,
+{
+    /// Soil moisture sampling results.
+    pub summary: Summary<SENSOR>,
+    /// Battery reading, unless the battery could not be read since boot.
+    /// Written as a reading of 0 mV when unknown, so that records keep their
+    /// size and still fit an RPC frame.
+    pub battery: Option<Battery>,
+    /// Fault preventing irrigation, if any.
+    pub fault: Option<Fault>,
+}
+
+impl<S> Serializable for Record<S>
+where
+    S: sensors::Sensor,
+{
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        let mut n = self.summary.serialize(ser)?;
+        n += self.battery.unwrap_or(UNKNOWN_BATTERY).serialize(ser)?;
+        n += self.fault.serialize(ser)?;
+        Ok(n)
+    }
+}
+
+impl<S> Deserializable for Record<S>
+where
+    S: sensors::Sensor,
+{
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        let summary = Summary::deserialize(de)?;
+        let battery = Some(Battery::deserialize(de)?).filter(|battery| battery.millivolts != 0);
+        let fault = Option::deserialize(de)?;
+        Ok(Self { summary, battery, fault })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sensors::Hygrometer;
+    use test_case::test_case;
+
+    const BATTERY: Battery = Battery { millivolts: 3870, chemistry: Chemistry::LiIon };
+
+    #[test_case(Some(BATTERY), None)]
+    #[test_case(Some(BATTERY), Some(Fault::NoEffect))]
+    #[test_case(None, None ; "unknown battery")]
+    fn record_serde(battery: Option<Battery>, fault: Option<Fault>) {
+        let input = Record::<Hygrometer> {
+            summary: Summary {
+                n: 64,
+                avg: 1400,
+                min: 1390,
+                max: 1411,
+                sensor: Hygrometer::HW390,
+                compensation: None,
+            },
+            battery,
+            fault,
+        };
+
+        let mut buffer = [0u8; 60];
+        let n = serde::serialize(&input, &mut buffer).unwrap();
+
+        let output = serde::deserialize::<Record<Hygrometer>>(&buffer[..n]).unwrap();
+        assert_eq!(input, output);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::serde::{self, Deserializable, Serializable};
+
+/// Soil moisture over a period, aggregated from the samples taken during it.
+/// Moisture values go from 0 to 100.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rollup {
+    /// Samples taken over the period.
+    pub n: u16,
+    pub min: u8,
+    pub max: u8,
+    sum: u32,
+}
+
+impl Default for Rollup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rollup {
+    pub const fn new() -> Self {
+        Self { n: 0, min: u8::MAX, max: u8::MIN, sum: 0 }
+    }
+
+    /// Adds the moisture of a sample.
+    pub fn add(&mut self, moisture: u8) {
+        self.n = self.n.saturating_add(1);
+        self.min = self.min.min(moisture);
+        self.max = self.max.max(moisture);
+        self.sum += moisture as u32;
+    }
+
+    /// Returns the average moisture, or nothing when no sample was taken.
+    pub fn avg(&self) -> Option<u8> {
+        (self.n > 0).then(|| (self.sum / self.n as u32) as u8)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+}
+
+impl Serializable for Rollup {
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        let mut n = ser.write_u16(self.n)?;
+        n += ser.write_u8(self.min)?;
+        n += ser.write_u8(self.max)?;
+        n += ser.write_u32(self.sum)?;
+        Ok(n)
+    }
+}
+
+impl Deserializable for Rollup {
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        let n = de.read_u16()?;
+        let min = de.read_u8()?;
+        let max = de.read_u8()?;
+        let sum = de.read_u32()?;
+        Ok(Self { n, min, max, sum })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_empty() {
+        let sut = Rollup::new();
+
+        assert!(sut.is_empty());
+        assert_eq!(None, sut.avg());
+    }
+
+    #[test]
+    fn test_add() {
+        let mut sut = Rollup::new();
+
+        [40, 35, 48].iter().for_each(|moisture| sut.add(*moisture));
+
+        assert_eq!((3, 35, 48), (sut.n, sut.min, sut.max));
+        assert_eq!(Some(41), sut.avg());
+    }
+
+    #[test]
+    fn test_serde() {
+        let mut input = Rollup::new();
+        [40, 35, 48].iter().for_each(|moisture| input.add(*moisture));
+
+        let mut buffer = [0u8; 8];
+        let n = serde::serialize(&input, &mut buffer).unwrap();
+
+        assert_eq!(8, n);
+        assert_eq!(Ok(input), serde::deserialize::<Rollup>(&buffer[..n]));
+    }
+}
@@ -19,6 +19,33 @@ where
     pub max: u16,
     /// Sensor model.
     pub sensor: SENSOR,
+    /// Temperature compensation, when the soil temperature was known.
+    pub compensation: Option<sensors::Compensation>,
+}
+
+impl<S> Summary<S>
+where
+    S: sensors::Sensor,
+{
+    /// Returns the average reading with the temperature drift removed.
+    pub fn compensated_avg(&self) -> u16 {
+        match &self.compensation {
+            Some(compensation) => compensation.apply(self.avg),
+            None => self.avg,
+        }
+    }
+
+    /// Returns the percentage the compensated average falls within the calibrated
+    /// boundaries of the sensor.
+    pub fn percentage(&self) -> f32 {
+        let value = self.compensated_avg().clamp(self.sensor.low(), self.sensor.high());
+        self.sensor.percentage(value)
+    }
+
+    /// Returns the soil moisture from 0 (as dry as air) to 100 (as wet as water).
+    pub fn moisture(&self) -> u8 {
+        100 - (self.percentage() * 100.0 + 0.5) as u8
+    }
 }
 
 impl<S> Serializable for Summary<S>
@@ -31,6 +58,7 @@ where
         n += ser.write_u16(self.min)?;
         n += ser.write_u16(self.max)?;
         n += self.sensor.serialize(ser)?;
+        n += self.compensation.serialize(ser)?;
         Ok(n)
     }
 }
@@ -45,7 +73,8 @@ where
         let min = de.read_u16()?;
         let max = de.read_u16()?;
         let sensor = S::deserialize(de)?;
-        Ok(Self { n, avg, min, max, sensor })
+        let compensation = Option::deserialize(de)?;
+        Ok(Self { n, avg, min, max, sensor, compensation })
     }
 }
 
@@ -55,10 +84,20 @@ mod test {
 
     use super::*;
 
-    #[test]
-    fn sample_result_serde() {
-        let input =
-            Summary::<Hygrometer> { n: 1, avg: 990, min: 813, max: 1238, sensor: Hygrometer::YL69 };
+    use sensors::{Compensation, Sensor};
+    use test_case::test_case;
+
+    #[test_case(None)]
+    #[test_case(Some(Compensation { temperature: 2650, coefficient: -5 }))]
+    fn sample_result_serde(compensation: Option<Compensation>) {
+        let input = Summary::<Hygrometer> {
+            n: 1,
+            avg: 990,
+            min: 813,
+            max: 1238,
+            sensor: Hygrometer::YL69,
+            compensation,
+        };
 
         let mut buffer = [0u8; 60];
         let n = serde::serialize(&input, &mut buffer).unwrap();
@@ -66,4 +105,49 @@ mod test {
         let output = serde::deserialize::<Summary<Hygrometer>>(&buffer[..n]).unwrap();
         assert_eq!(input, output);
     }
+
+    #[test_case(None, 0.3809524)]
+    #[test_case(Some(2000), 0.3809524; "at reference")]
+    #[test_case(Some(3000), 0.35238096; "warmer")]
+    fn test_percentage(temperature: Option<i16>, expected: f32) {
+        let sensor = Hygrometer::HW390;
+        let sut = Summary::<Hygrometer> {
+            n: 1,
+            avg: 1400,
+            min: 1400,
+            max: 1400,
+            sensor,
+            compensation: temperature.map(|t| sensor.compensation(t)),
+        };
+        assert_eq!(expected, sut.percentage());
+    }
+
+    #[test_case(1000, 100)]
+    #[test_case(1400, 62)]
+    #[test_case(2050, 0)]
+    fn test_moisture(avg: u16, expected: u8) {
+        let sut = Summary::<Hygrometer> {
+            n: 1,
+            avg,
+            min: avg,
+            max: avg,
+            sensor: Hygrometer::HW390,
+            compensation: None,
+        };
+        assert_eq!(expected, sut.moisture());
+    }
+
+    #[test]
+    fn test_percentage_clamps_to_boundaries() {
+        let sensor = Hygrometer::HW390;
+        let sut = Summary::<Hygrometer> {
+            n: 1,
+            avg: sensor.low(),
+            min: sensor.low(),
+            max: sensor.low(),
+            sensor,
+            compensation: Some(sensor.compensation(4000)),
+        };
+        assert_eq!(0.0, sut.percentage());
+    }
 }
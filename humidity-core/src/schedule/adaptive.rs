@@ -0,0 +1,200 @@
+use crate::battery::LOW_BATTERY_PERCENTAGE;
+
+const HOUR: u32 = 60 * 60;
+/// Shortest time a change of moisture is measured over, in seconds, so that
+/// readings taken moments apart, as when a client asks for one, do not make a
+/// small change look fast.
+const MIN_ELAPSED: u32 = 15 * 60;
+/// Smallest change of moisture taken into account, as readings jitter by a
+/// point.
+const MIN_CHANGE: u8 = 2;
+
+/// State of charge below which the interval is stretched further.
+const CRITICAL_BATTERY_PERCENTAGE: u8 = LOW_BATTERY_PERCENTAGE / 2;
+
+/// Bounds and thresholds for the adaptive interval, all times in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Policy {
+    /// Shortest interval, used while moisture changes quickly.
+    pub min: u32,
+    /// Interval under normal conditions.
+    pub base: u32,
+    /// Longest interval, regardless of the conditions.
+    pub max: u32,
+    /// Moisture change per hour considered fast, from 0 to 100.
+    pub fast_change: u8,
+    /// Hour of the day, from 0 to 23 and in UTC like the device clock, at which
+    /// the night starts.
+    pub night_start: u8,
+    /// Hour of the day, from 0 to 23, at which the night ends.
+    pub night_end: u8,
+}
+
+impl Policy {
+    fn is_night(&self, hour: u8) -> bool {
+        if self.night_start <= self.night_end {
+            (self.night_start..self.night_end).contains(&hour)
+        } else {
+            hour >= self.night_start || hour < self.night_end
+        }
+    }
+}
+
+/// Conditions observed during the current wake.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Inputs {
+    /// Soil moisture, from 0 to 100.
+    pub moisture: u8,
+    /// Battery state of charge, from 0 to 100.
+    pub battery: u8,
+    /// Hour of the day, from 0 to 23, when the wall clock time is known.
+    pub hour: Option<u8>,
+    /// Whether the plant has just been watered.
+    pub watered: bool,
+}
+
+/// Picks the next measurement interval, meant to be kept across deep sleeps
+/// to compare successive readings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adaptive {
+    last: Option<(u32, u8)>,
+}
+
+impl Default for Adaptive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Adaptive {
+    pub const fn new() -> Self {
+        Self { last: None }
+    }
+
+    /// Returns how long to sleep, in seconds, given the conditions observed at
+    /// `now`, in seconds.
+    pub fn next_interval(&mut self, policy: &Policy, inputs: &Inputs, now: u32) -> u32 {
+        let changing_fast = self.change_per_hour(inputs.moisture, now) >= policy.fast_change as u32;
+        self.last = Some((now, inputs.moisture));
+
+        let interval = if inputs.watered || changing_fast {
+            policy.min
+        } else {
+            let mut interval = policy.base;
+            if inputs.hour.is_some_and(|hour| policy.is_night(hour)) {
+                interval *= 2;
+            }
+            interval
+        };
+
+        let interval = match inputs.battery {
+            battery if battery <= CRITICAL_BATTERY_PERCENTAGE => interval.saturating_mul(4),
+            battery if battery <= LOW_BATTERY_PERCENTAGE => interval.saturating_mul(2),
+            _ => interval,
+        };
+        interval.clamp(policy.min, policy.max)
+    }
+
+    fn change_per_hour(&self, moisture: u8, now: u32) -> u32 {
+        let Some((then, previous)) = self.last else {
+            return 0;
+        };
+        let change = moisture.abs_diff(previous);
+        if change < MIN_CHANGE {
+            return 0;
+        }
+        change as u32 * HOUR / now.wrapping_sub(then).max(MIN_ELAPSED)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    const POLICY: Policy =
+        Policy { min: 60, base: 900, max: 3600, fast_change: 5, night_start: 22, night_end: 7 };
+
+    fn inputs(moisture: u8) -> Inputs {
+        Inputs { moisture, battery: 80, hour: None, watered: false }
+    }
+
+    #[test]
+    fn test_base_interval() {
+        let mut sut = Adaptive::new();
+
+        assert_eq!(900, sut.next_interval(&POLICY, &inputs(40), 0));
+        assert_eq!(900, sut.next_interval(&POLICY, &inputs(40), 900));
+    }
+
+    #[test]
+    fn test_faster_while_drying_quickly() {
+        let mut sut = Adaptive::new();
+
+        sut.next_interval(&POLICY, &inputs(40), 0);
+        assert_eq!(60, sut.next_interval(&POLICY, &inputs(38), 900));
+        assert_eq!(900, sut.next_interval(&POLICY, &inputs(38), 960));
+    }
+
+    #[test_case(41, 900; "jitter")]
+    #[test_case(42, 60; "change")]
+    fn test_readings_close_together(moisture: u8, expected: u32) {
+        let mut sut = Adaptive::new();
+
+        sut.next_interval(&POLICY, &inputs(40), 0);
+        assert_eq!(expected, sut.next_interval(&POLICY, &inputs(moisture), 5));
+    }
+
+    #[test]
+    fn test_short_span_bounds_change() {
+        let policy = Policy { fast_change: 10, ..POLICY };
+        let mut sut = Adaptive::new();
+
+        sut.next_interval(&policy, &inputs(40), 0);
+        assert_eq!(900, sut.next_interval(&policy, &inputs(38), 5));
+    }
+
+    #[test]
+    fn test_faster_after_watering() {
+        let mut sut = Adaptive::new();
+
+        let watered = Inputs { watered: true, ..inputs(40) };
+        assert_eq!(60, sut.next_interval(&POLICY, &watered, 0));
+    }
+
+    #[test_case(Some(23), 1800; "night")]
+    #[test_case(Some(3), 1800; "early morning")]
+    #[test_case(Some(7), 900; "morning")]
+    #[test_case(Some(14), 900; "afternoon")]
+    #[test_case(None, 900; "unknown time")]
+    fn test_slower_overnight(hour: Option<u8>, expected: u32) {
+        let mut sut = Adaptive::new();
+
+        assert_eq!(expected, sut.next_interval(&POLICY, &Inputs { hour, ..inputs(40) }, 0));
+    }
+
+    #[test_case(20, 1800; "low")]
+    #[test_case(10, 3600; "critical")]
+    #[test_case(5, 3600; "bounded")]
+    fn test_slower_on_low_battery(battery: u8, expected: u32) {
+        let mut sut = Adaptive::new();
+
+        assert_eq!(expected, sut.next_interval(&POLICY, &Inputs { battery, ..inputs(40) }, 0));
+    }
+
+    #[test]
+    fn test_night_and_low_battery_within_bounds() {
+        let mut sut = Adaptive::new();
+
+        let inputs = Inputs { battery: 20, hour: Some(1), ..inputs(40) };
+        assert_eq!(3600, sut.next_interval(&POLICY, &inputs, 0));
+    }
+
+    #[test]
+    fn test_watering_on_low_battery() {
+        let mut sut = Adaptive::new();
+
+        let inputs = Inputs { battery: 20, watered: true, ..inputs(40) };
+        assert_eq!(120, sut.next_interval(&POLICY, &inputs, 0));
+    }
+}
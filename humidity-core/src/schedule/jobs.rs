@@ -0,0 +1,238 @@
+use super::Clock;
+
+/// Periodic tasks the device performs on wake.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Job {
+    /// Sample the soil moisture.
+    Sample,
+    /// Advertise and serve clients.
+    Advertise,
+    /// Roll up recent samples.
+    Rollup,
+    /// Measure the battery voltage.
+    BatteryCheck,
+}
+
+/// A set of [`Job`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Jobs(u8);
+
+impl Jobs {
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub fn insert(&mut self, job: Job) {
+        self.0 |= 1 << job as u8;
+    }
+
+    pub fn contains(&self, job: Job) -> bool {
+        self.0 & (1 << job as u8) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// A job and how often it runs, in seconds.
+pub type Task = (Job, u32);
+
+/// Seconds until a job is due, zero once it is.
+fn remaining(next_due: Option<u32>, now: u32) -> u32 {
+    match next_due {
+        // Compares times allowing the clock to wrap around.
+        Some(next_due) if next_due.wrapping_sub(now) < u32::MAX / 2 => next_due.wrapping_sub(now),
+        _ => 0,
+    }
+}
+
+/// Keeps track of when each [`Task`] is next due, meant to be kept across deep
+/// sleeps. All times are in seconds, as given by a [`Clock`].
+///
+/// Only the due times are kept, so the state stays valid in zeroed memory; the
+/// tasks are passed on every call, always in the same order. Every task is due
+/// on the first wake. Tasks which were missed several times, because the device
+/// slept for longer than their period, only run once and are rescheduled one
+/// period after the current time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scheduler<const N: usize> {
+    next_due: [Option<u32>; N],
+}
+
+impl<const N: usize> Default for Scheduler<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Scheduler<N> {
+    pub const fn new() -> Self {
+        Self { next_due: [None; N] }
+    }
+
+    /// Returns the jobs due now, and schedules their next run.
+    pub fn run_due(&mut self, tasks: &[Task; N], clock: &impl Clock) -> Jobs {
+        let now = clock.now();
+        let mut due = Jobs::empty();
+        for ((job, period), next_due) in tasks.iter().zip(self.next_due.iter_mut()) {
+            if remaining(*next_due, now) == 0 {
+                due.insert(*job);
+                *next_due = Some(now.wrapping_add(*period));
+            }
+        }
+        due
+    }
+
+    /// Overrides when a job next runs, `after` seconds from now.
+    pub fn reschedule(&mut self, tasks: &[Task; N], job: Job, after: u32, clock: &impl Clock) {
+        let now = clock.now();
+        for ((task, _), next_due) in tasks.iter().zip(self.next_due.iter_mut()) {
+            if *task == job {
+                *next_due = Some(now.wrapping_add(after));
+            }
+        }
+    }
+
+    /// Returns how long until a job is due, in seconds.
+    pub fn due_in(&self, tasks: &[Task; N], job: Job, clock: &impl Clock) -> u32 {
+        let now = clock.now();
+        tasks
+            .iter()
+            .zip(self.next_due.iter())
+            .find(|((task, _), _)| *task == job)
+            .map_or(0, |(_, next_due)| remaining(*next_due, now))
+    }
+
+    /// Returns how long to sleep until the nearest job is due, in seconds.
+    pub fn next_wake(&self, clock: &impl Clock) -> u32 {
+        let now = clock.now();
+        self.next_due.iter().map(|next_due| remaining(*next_due, now)).min().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::Cell;
+
+    const MINUTE: u32 = 60;
+    const HOUR: u32 = 60 * MINUTE;
+
+    struct FakeClock(Cell<u32>);
+
+    impl FakeClock {
+        fn advance(&self, secs: u32) {
+            self.0.set(self.0.get().wrapping_add(secs));
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> u32 {
+            self.0.get()
+        }
+    }
+
+    const TASKS: [Task; 4] = [
+        (Job::Sample, 5 * MINUTE),
+        (Job::Advertise, 30 * MINUTE),
+        (Job::Rollup, HOUR),
+        (Job::BatteryCheck, 24 * HOUR),
+    ];
+
+    fn jobs(list: &[Job]) -> Jobs {
+        let mut jobs = Jobs::empty();
+        list.iter().for_each(|job| jobs.insert(*job));
+        jobs
+    }
+
+    #[test]
+    fn test_everything_due_on_first_wake() {
+        let clock = FakeClock(Cell::new(1000));
+        let mut sut = Scheduler::new();
+
+        let expected = jobs(&[Job::Sample, Job::Advertise, Job::Rollup, Job::BatteryCheck]);
+        assert_eq!(expected, sut.run_due(&TASKS, &clock));
+        assert_eq!(5 * MINUTE, sut.next_wake(&clock));
+    }
+
+    #[test]
+    fn test_a_day_of_wakes() {
+        let clock = FakeClock(Cell::new(0));
+        let mut sut = Scheduler::new();
+        sut.run_due(&TASKS, &clock);
+
+        let (mut samples, mut adverts, mut rollups, mut battery_checks) = (0, 0, 0, 0);
+        while clock.now() < 24 * HOUR {
+            clock.advance(sut.next_wake(&clock));
+            let due = sut.run_due(&TASKS, &clock);
+            assert!(!due.is_empty());
+            samples += due.contains(Job::Sample) as u32;
+            adverts += due.contains(Job::Advertise) as u32;
+            rollups += due.contains(Job::Rollup) as u32;
+            battery_checks += due.contains(Job::BatteryCheck) as u32;
+        }
+
+        assert_eq!((288, 48, 24, 1), (samples, adverts, rollups, battery_checks));
+    }
+
+    #[test]
+    fn test_woken_early_runs_nothing() {
+        let clock = FakeClock(Cell::new(0));
+        let mut sut = Scheduler::new();
+        sut.run_due(&TASKS, &clock);
+
+        clock.advance(2 * MINUTE);
+        assert_eq!(Jobs::empty(), sut.run_due(&TASKS, &clock));
+        assert_eq!(3 * MINUTE, sut.next_wake(&clock));
+    }
+
+    #[test]
+    fn test_missed_jobs_run_once() {
+        let clock = FakeClock(Cell::new(0));
+        let mut sut = Scheduler::new();
+        sut.run_due(&TASKS, &clock);
+
+        clock.advance(2 * HOUR + MINUTE);
+        assert_eq!(jobs(&[Job::Sample, Job::Advertise, Job::Rollup]), sut.run_due(&TASKS, &clock));
+        assert_eq!(5 * MINUTE, sut.next_wake(&clock));
+    }
+
+    #[test]
+    fn test_reschedule() {
+        let clock = FakeClock(Cell::new(0));
+        let mut sut = Scheduler::new();
+        sut.run_due(&TASKS, &clock);
+
+        sut.reschedule(&TASKS, Job::Sample, MINUTE, &clock);
+        assert_eq!(MINUTE, sut.next_wake(&clock));
+        clock.advance(MINUTE);
+        assert_eq!(jobs(&[Job::Sample]), sut.run_due(&TASKS, &clock));
+    }
+
+    #[test]
+    fn test_due_in() {
+        let clock = FakeClock(Cell::new(0));
+        let mut sut = Scheduler::new();
+        assert_eq!(0, sut.due_in(&TASKS, Job::Advertise, &clock));
+        sut.run_due(&TASKS, &clock);
+
+        clock.advance(MINUTE);
+        assert_eq!(4 * MINUTE, sut.due_in(&TASKS, Job::Sample, &clock));
+        assert_eq!(29 * MINUTE, sut.due_in(&TASKS, Job::Advertise, &clock));
+        clock.advance(5 * MINUTE);
+        assert_eq!(0, sut.due_in(&TASKS, Job::Sample, &clock));
+    }
+
+    #[test]
+    fn test_clock_wrap_around() {
+        let clock = FakeClock(Cell::new(u32::MAX - MINUTE));
+        let mut sut = Scheduler::new();
+        sut.run_due(&TASKS, &clock);
+
+        assert_eq!(5 * MINUTE, sut.next_wake(&clock));
+        clock.advance(5 * MINUTE);
+        assert_eq!(jobs(&[Job::Sample]), sut.run_due(&TASKS, &clock));
+    }
+}
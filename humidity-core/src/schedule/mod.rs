@@ -0,0 +1,53 @@
+//! # Scheduling
+//!
+//! Decides how long the device sleeps between wakes. [`Adaptive`] picks the
+//! next measurement interval from how fast moisture is changing, the battery
+//! level and the time of day: sampling faster while the soil dries quickly or
+//! right after watering, and slower overnight or when running out of battery.
+//!
+//! A [`Scheduler`] runs several periodic [`Job`]s, each with its own period,
+//! and tells which ones are due on each wake and how long to sleep until the
+//! nearest one. It is meant to live in memory retained across deep sleeps.
+//!
+//! ```rust
+//! use humidity_core::schedule::{Clock, Job, Scheduler, Task};
+//! struct Uptime(u32);
+//! impl Clock for Uptime {
+//!     fn now(&self) -> u32 {
+//!         self.0
+//!     }
+//! }
+//! const TASKS: [Task; 2] = [(Job::Sample, 300), (Job::Advertise, 1800)];
+//! let mut scheduler = Scheduler::new();
+//! let due = scheduler.run_due(&TASKS, &Uptime(0));
+//! assert!(due.contains(Job::Sample) && due.contains(Job::Advertise));
+//! assert_eq!(300, scheduler.next_wake(&Uptime(0)));
+//! ```
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::schedule::{Adaptive, Inputs, Policy};
+//! let policy = Policy {
+//!     min: 60,
+//!     base: 900,
+//!     max: 3600,
+//!     fast_change: 5,
+//!     night_start: 22,
+//!     night_end: 7,
+//! };
+//! let mut adaptive = Adaptive::new();
+//! let inputs = Inputs { moisture: 40, battery: 80, hour: Some(14), watered: false };
+//! println!("sleep for {}s", adaptive.next_interval(&policy, &inputs, 0));
+//! ```
+
+pub use adaptive::{Adaptive, Inputs, Policy};
+pub use jobs::{Job, Jobs, Scheduler, Task};
+
+mod adaptive;
+mod jobs;
+
+/// Source of the current time, in seconds.
+pub trait Clock {
+    fn now(&self) -> u32;
+}
@@ -0,0 +1,170 @@
+//! Decoding and compensation of Bosch BME280 measurements.
+//!
+//! The I2C transfers are left to the firmware, which reads the calibration
+//! blocks once after boot, and the measurement block after each conversion.
+//! Compensation follows the fixed point formulas from the datasheet.
+
+use super::{AirSensor, Climate, Error};
+
+/// Default I2C address of the sensor (SDO pin pulled low).
+pub const ADDRESS: u8 = 0x76;
+/// Register holding the chip identifier.
+pub const REG_CHIP_ID: u8 = 0xD0;
+/// Expected contents of [`REG_CHIP_ID`].
+pub const CHIP_ID: u8 = 0x60;
+/// First register of the temperature and pressure calibration block.
+pub const REG_CALIB_TP: u8 = 0x88;
+/// First register of the humidity calibration block.
+pub const REG_CALIB_H: u8 = 0xE1;
+/// First register of the measurement block.
+pub const REG_DATA: u8 = 0xF7;
+
+/// Factory calibration parameters, unique to each sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p2: i16,
+    p3: i16,
+    p4: i16,
+    p5: i16,
+    p6: i16,
+    p7: i16,
+    p8: i16,
+    p9: i16,
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+impl Calibration {
+    /// Builds the calibration from the chip identifier and the register dumps
+    /// starting at [`REG_CALIB_TP`] (26 bytes) and [`REG_CALIB_H`] (7 bytes).
+    pub fn new(chip_id: u8, tp: &[u8; 26], h: &[u8; 7]) -> Result<Self, Error> {
+        if chip_id != CHIP_ID {
+            return Err(Error::ErrChipId);
+        }
+
+        let u16_at = |i: usize| u16::from_le_bytes([tp[i], tp[i + 1]]);
+        let i16_at = |i: usize| i16::from_le_bytes([tp[i], tp[i + 1]]);
+        Ok(Self {
+            t1: u16_at(0),
+            t2: i16_at(2),
+            t3: i16_at(4),
+            p1: u16_at(6),
+            p2: i16_at(8),
+            p3: i16_at(10),
+            p4: i16_at(12),
+            p5: i16_at(14),
+            p6: i16_at(16),
+            p7: i16_at(18),
+            p8: i16_at(20),
+            p9: i16_at(22),
+            h1: tp[25],
+            h2: i16::from_le_bytes([h[0], h[1]]),
+            h3: h[2],
+            h4: ((h[3] as i8 as i16) << 4) | (h[4] & 0x0F) as i16,
+            h5: ((h[5] as i8 as i16) << 4) | (h[4] >> 4) as i16,
+            h6: h[6] as i8,
+        })
+    }
+
+    /// Compensates the 8 byte measurement block starting at [`REG_DATA`].
+    pub fn compensate(&self, data: &[u8; 8]) -> Climate {
+        let adc_p = (data[0] as i32) << 12 | (data[1] as i32) << 4 | (data[2] as i32) >> 4;
+        let adc_t = (data[3] as i32) << 12 | (data[4] as i32) << 4 | (data[5] as i32) >> 4;
+        let adc_h = (data[6] as i32) << 8 | data[7] as i32;
+
+        let t_fine = self.t_fine(adc_t);
+        Climate {
+            temperature: ((t_fine * 5 + 128) >> 8) as i16,
+            humidity: ((self.humidity(adc_h, t_fine) * 100) >> 10) as u16,
+            pressure: Some(self.pressure(adc_p, t_fine) >> 8),
+            sensor: AirSensor::BME280,
+        }
+    }
+
+    fn t_fine(&self, adc_t: i32) -> i32 {
+        let t1 = self.t1 as i32;
+        let var1 = (((adc_t >> 3) - (t1 << 1)) * self.t2 as i32) >> 11;
+        let var2 = (((((adc_t >> 4) - t1) * ((adc_t >> 4) - t1)) >> 12) * self.t3 as i32) >> 14;
+        var1 + var2
+    }
+
+    /// Returns pressure in Pascal as Q24.8 fixed point.
+    fn pressure(&self, adc_p: i32, t_fine: i32) -> u32 {
+        let mut var1 = t_fine as i64 - 128000;
+        let mut var2 = var1 * var1 * self.p6 as i64;
+        var2 += (var1 * self.p5 as i64) << 17;
+        var2 += (self.p4 as i64) << 35;
+        var1 = ((var1 * var1 * self.p3 as i64) >> 8) + ((var1 * self.p2 as i64) << 12);
+        var1 = (((1i64 << 47) + var1) * self.p1 as i64) >> 33;
+        if var1 == 0 {
+            return 0;
+        }
+
+        let mut p = 1048576 - adc_p as i64;
+        p = (((p << 31) - var2) * 3125) / var1;
+        let var1 = (self.p9 as i64 * (p >> 13) * (p >> 13)) >> 25;
+        let var2 = (self.p8 as i64 * p) >> 19;
+        (((p + var1 + var2) >> 8) + ((self.p7 as i64) << 4)) as u32
+    }
+
+    /// Returns relative humidity in %RH as Q22.10 fixed point.
+    fn humidity(&self, adc_h: i32, t_fine: i32) -> u32 {
+        let mut v = t_fine - 76800;
+        v = ((((adc_h << 14) - ((self.h4 as i32) << 20) - (self.h5 as i32 * v)) + 16384) >> 15)
+            * (((((((v * self.h6 as i32) >> 10) * (((v * self.h3 as i32) >> 11) + 32768)) >> 10)
+                + 2097152)
+                * self.h2 as i32
+                + 8192)
+                >> 14);
+        v -= ((((v >> 15) * (v >> 15)) >> 7) * self.h1 as i32) >> 4;
+        (v.clamp(0, 419430400) >> 12) as u32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Temperature and pressure trimming values are the worked example from the
+    // datasheet, humidity trimming values come from a sensor on the bench.
+    const CALIB_TP: [u8; 26] = [
+        0x70, 0x6B, 0x43, 0x67, 0x18, 0xFC, 0x7D, 0x8E, 0x43, 0xD6, 0xD0, 0x0B, 0x27, 0x0B, 0x8C,
+        0x00, 0xF9, 0xFF, 0x8C, 0x3C, 0xF8, 0xC6, 0x70, 0x17, 0x00, 0x4B,
+    ];
+    const CALIB_H: [u8; 7] = [0x6A, 0x01, 0x00, 0x13, 0x29, 0x03, 0x1E];
+
+    #[test]
+    fn test_calibration_parsing() {
+        let sut = Calibration::new(CHIP_ID, &CALIB_TP, &CALIB_H).unwrap();
+        assert_eq!((27504, 26435, -1000), (sut.t1, sut.t2, sut.t3));
+        assert_eq!((36477, -10685, 6000), (sut.p1, sut.p2, sut.p9));
+        assert_eq!((75, 362, 0, 313, 50, 30), (sut.h1, sut.h2, sut.h3, sut.h4, sut.h5, sut.h6));
+    }
+
+    #[test]
+    fn test_calibration_wrong_chip() {
+        assert_eq!(Err(Error::ErrChipId), Calibration::new(0x58, &CALIB_TP, &CALIB_H));
+    }
+
+    #[test]
+    fn test_compensate() {
+        let sut = Calibration::new(CHIP_ID, &CALIB_TP, &CALIB_H).unwrap();
+        let data = [0x65, 0x5A, 0xC0, 0x7E, 0xED, 0x00, 0x6D, 0x2A];
+
+        let expected = Climate {
+            temperature: 2508,
+            humidity: 4355,
+            pressure: Some(100653),
+            sensor: AirSensor::BME280,
+        };
+        assert_eq!(expected, sut.compensate(&data));
+    }
+}
@@ -0,0 +1,88 @@
+//! All about air climate sensors.
+
+use crate::serde::{self, Deserializable, Serializable};
+
+/// Represents a variety of air temperature and humidity sensors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum AirSensor {
+    /// Models the Sensirion SHT3x family (SHT30, SHT31, SHT35).
+    SHT3x,
+    /// Models the Bosch BME280, which also measures barometric pressure.
+    BME280,
+}
+
+/// A driver-agnostic air climate reading.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Climate {
+    /// Temperature in centi-degrees Celsius, `2150` means 21.50 ºC.
+    pub temperature: i16,
+    /// Relative humidity in centi-percent, `4512` means 45.12 %RH.
+    pub humidity: u16,
+    /// Barometric pressure in Pascal, when the sensor supports it.
+    pub pressure: Option<u32>,
+    /// Sensor model.
+    pub sensor: AirSensor,
+}
+
+impl Serializable for AirSensor {
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        ser.write_u8(*self as u8)
+    }
+}
+
+impl Deserializable for AirSensor {
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        match de.read_u8()? {
+            0 => Ok(Self::SHT3x),
+            1 => Ok(Self::BME280),
+            _ => Err(serde::Error::Other),
+        }
+    }
+}
+
+impl Serializable for Climate {
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        let mut n = ser.write_i16(self.temperature)?;
+        n += ser.write_u16(self.humidity)?;
+        match self.pressure {
+            Some(pressure) => {
+                n += ser.write_u8(1)?;
+                n += ser.write_u32(pressure)?;
+            }
+            None => n += ser.write_u8(0)?,
+        }
+        n += self.sensor.serialize(ser)?;
+        Ok(n)
+    }
+}
+
+impl Deserializable for Climate {
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        let temperature = de.read_i16()?;
+        let humidity = de.read_u16()?;
+        let pressure = match de.read_u8()? {
+            0 => None,
+            1 => Some(de.read_u32()?),
+            _ => return Err(serde::Error::Other),
+        };
+        let sensor = AirSensor::deserialize(de)?;
+        Ok(Self { temperature, humidity, pressure, sensor })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(Climate { temperature: 2150, humidity: 4512, pressure: None, sensor: AirSensor::SHT3x })]
+    #[test_case(Climate { temperature: -512, humidity: 9000, pressure: Some(100653), sensor: AirSensor::BME280 })]
+    fn climate_serde(input: Climate) {
+        let mut buffer = [0u8; 60];
+        let n = serde::serialize(&input, &mut buffer).unwrap();
+
+        let output = serde::deserialize::<Climate>(&buffer[..n]).unwrap();
+        assert_eq!(input, output);
+    }
+}
@@ -0,0 +1,60 @@
+//! Temperature compensation of sensor readings.
+
+use crate::serde::{self, Deserializable, Serializable};
+
+/// Temperature at which sensors are calibrated, in centi-degrees Celsius.
+pub const REFERENCE_TEMPERATURE: i16 = 2000;
+
+/// Describes how a reading was corrected for the soil temperature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Compensation {
+    /// Soil temperature in centi-degrees Celsius at the time of sampling.
+    pub temperature: i16,
+    /// Drift of the sensor output, in ADC counts per degree Celsius.
+    pub coefficient: i16,
+}
+
+impl Compensation {
+    /// Removes the temperature drift from a raw reading, bringing it back to
+    /// what the sensor would read at [`REFERENCE_TEMPERATURE`].
+    pub fn apply(&self, value: u16) -> u16 {
+        // Centi-degrees times counts per degree, divided once at the end so
+        // that fractions of a degree still count.
+        let delta = self.temperature as i32 - REFERENCE_TEMPERATURE as i32;
+        let drift = self.coefficient as i32 * delta / 100;
+        (value as i32 - drift).clamp(u16::MIN as i32, u16::MAX as i32) as u16
+    }
+}
+
+impl Serializable for Compensation {
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        let mut n = ser.write_i16(self.temperature)?;
+        n += ser.write_i16(self.coefficient)?;
+        Ok(n)
+    }
+}
+
+impl Deserializable for Compensation {
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        let temperature = de.read_i16()?;
+        let coefficient = de.read_i16()?;
+        Ok(Self { temperature, coefficient })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(2000, 3, 1400, 1400; "at reference")]
+    #[test_case(3000, 3, 1400, 1370; "warmer")]
+    #[test_case(500, 3, 1400, 1445; "colder")]
+    #[test_case(3000, -5, 1400, 1450; "negative coefficient")]
+    #[test_case(3000, 3, 10, 0; "saturates low")]
+    #[test_case(2005, 40, 1400, 1398; "fraction of a degree")]
+    fn test_apply(temperature: i16, coefficient: i16, input: u16, expected: u16) {
+        let sut = Compensation { temperature, coefficient };
+        assert_eq!(expected, sut.apply(input));
+    }
+}
@@ -29,6 +29,14 @@ impl Sensor for Hygrometer {
             Hygrometer::HW390 => 2050,
         }
     }
+
+    /// The ADC drift per degree Celsius, measured against the reference temperature.
+    fn temperature_coefficient(&self) -> i16 {
+        match self {
+            Hygrometer::YL69 => -5,
+            Hygrometer::HW390 => 3,
+        }
+    }
 }
 
 impl Serializable for Hygrometer {
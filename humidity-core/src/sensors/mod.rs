@@ -1,16 +1,24 @@
 //! # Sensors and calibrations
 //!
-//! Defines the [`Sensor`] trait for all sensors to implement
+//! Defines the [`Sensor`] trait for all soil sensors to implement, and the
+//! [`Climate`] reading shared by all air climate sensors.
+//!
+//! Soil sensors drift with temperature, each model declares its drift as part
+//! of its calibration, and a [`Compensation`] can be applied to readings when
+//! the soil temperature is known.
 //!
 //! ## TODO
 //! - Introduce a mechanism to override calibrations, specially to support different
-//! calibrations based on different environments.
+//!   calibrations based on different environments.
 //!
 //! ## Supported sensors
 //!
 //! - Hygrometers
 //!   - [`Hygrometer::YL69`]
 //!   - [`Hygrometer::HW390`]
+//! - Air climate sensors, see [`sht3x`] and [`bme280`] for the I2C decoding.
+//!   - [`AirSensor::SHT3x`]
+//!   - [`AirSensor::BME280`]
 //!
 //! ## Examples
 //!
@@ -20,12 +28,28 @@
 //! println!("sensor reading: {}", sensor.percentage(1200));
 //! ```
 
+pub use climate::{AirSensor, Climate};
+pub use compensation::{Compensation, REFERENCE_TEMPERATURE};
 pub use hygrometer::Hygrometer;
 
 use crate::serde;
 
+pub mod bme280;
+pub mod sht3x;
+
+mod climate;
+mod compensation;
 mod hygrometer;
 
+/// Errors that can happen while decoding raw sensor data.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The checksum sent by the sensor does not match the data.
+    ErrChecksum,
+    /// The sensor did not identify itself as the expected chip.
+    ErrChipId,
+}
+
 /// Defines common behaviour for all sensors, such as getting the calibrated low
 /// and high values, and provides a function to compute where a value
 /// fits within the calibrated boundaries.
@@ -34,6 +58,15 @@ pub trait Sensor: serde::Serializable + serde::Deserializable {
     fn low(&self) -> u16;
     /// Returns the calibrated high reading.
     fn high(&self) -> u16;
+    /// Returns how much the reading drifts, in ADC counts per degree Celsius.
+    fn temperature_coefficient(&self) -> i16 {
+        0
+    }
+    /// Returns the compensation to apply to readings taken at the given
+    /// temperature, in centi-degrees Celsius.
+    fn compensation(&self, temperature: i16) -> Compensation {
+        Compensation { temperature, coefficient: self.temperature_coefficient() }
+    }
     /// Given a value, returns the percentage it falls within the calibrated boundaries.
     fn percentage(&self, value: u16) -> f32 {
         (value - self.low()) as f32 / (self.high() - self.low()) as f32
@@ -0,0 +1,71 @@
+//! Decoding of Sensirion SHT3x measurements.
+//!
+//! The I2C transfer itself is left to the firmware: write one of the measurement
+//! commands to [`ADDRESS`], wait for the conversion and read back 6 bytes, which
+//! are then handed over to [`decode`].
+
+use super::{AirSensor, Climate, Error};
+
+/// Default I2C address of the sensor (ADDR pin pulled low).
+pub const ADDRESS: u8 = 0x44;
+/// Single shot measurement, high repeatability, without clock stretching.
+pub const MEASURE_HIGH_REPEATABILITY: [u8; 2] = [0x24, 0x00];
+/// Maximum conversion time for [`MEASURE_HIGH_REPEATABILITY`], in milliseconds.
+pub const MEASURE_DURATION_MS: u32 = 16;
+
+/// Decodes a measurement response: temperature word, its CRC, humidity word
+/// and its CRC.
+pub fn decode(raw: &[u8; 6]) -> Result<Climate, Error> {
+    let temperature = word(&raw[0..3])?;
+    let humidity = word(&raw[3..6])?;
+
+    Ok(Climate {
+        temperature: (-4500 + 17500 * temperature as i32 / 65535) as i16,
+        humidity: (10000 * humidity as u32 / 65535) as u16,
+        pressure: None,
+        sensor: AirSensor::SHT3x,
+    })
+}
+
+/// CRC-8 used by Sensirion: polynomial `0x31`, initialization `0xFF`.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0xFFu8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x31 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+fn word(chunk: &[u8]) -> Result<u16, Error> {
+    if crc8(&chunk[..2]) != chunk[2] {
+        return Err(Error::ErrChecksum);
+    }
+    Ok(u16::from_be_bytes([chunk[0], chunk[1]]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn test_crc8_datasheet_example() {
+        assert_eq!(0x92, crc8(&[0xBE, 0xEF]));
+    }
+
+    #[test_case([0x66, 0x21, 0x39, 0x7C, 0x5A, 0xAB], 2481, 4857)]
+    #[test_case([0x4A, 0x3D, 0x6F, 0xA1, 0xE2, 0x29], 574, 6323)]
+    fn test_decode(raw: [u8; 6], temperature: i16, humidity: u16) {
+        let expected = Climate { temperature, humidity, pressure: None, sensor: AirSensor::SHT3x };
+        assert_eq!(Ok(expected), decode(&raw));
+    }
+
+    #[test_case([0x66, 0x21, 0x38, 0x7C, 0x5A, 0xAB]; "temperature")]
+    #[test_case([0x66, 0x21, 0x39, 0x7C, 0x5B, 0xAB]; "humidity")]
+    fn test_decode_bad_checksum(raw: [u8; 6]) {
+        assert_eq!(Err(Error::ErrChecksum), decode(&raw));
+    }
+}
@@ -18,6 +18,18 @@ impl<'input> Deserializer<'input> {
         self.read::<2>().map(u16::from_le_bytes)
     }
 
+    pub fn read_i16(&mut self) -> Result<i16, Error> {
+        self.read::<2>().map(i16::from_le_bytes)
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, Error> {
+        self.read::<4>().map(u32::from_le_bytes)
+    }
+
+    pub fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        self.read::<N>()
+    }
+
     fn read<const N: usize>(&mut self) -> Result<[u8; N], Error> {
         let buffer = &self.input[self.pos..];
         if buffer.len() < N {
@@ -27,6 +27,43 @@ where
     fn deserialize(de: &mut Deserializer) -> Result<Self, Error>;
 }
 
+impl<T> Serializable for Option<T>
+where
+    T: Serializable,
+{
+    fn serialize(&self, ser: &mut Serializer) -> Result<usize, Error> {
+        match self {
+            Some(value) => Ok(ser.write_u8(1)? + value.serialize(ser)?),
+            None => ser.write_u8(0),
+        }
+    }
+}
+
+impl<T> Deserializable for Option<T>
+where
+    T: Deserializable,
+{
+    fn deserialize(de: &mut Deserializer) -> Result<Self, Error> {
+        match de.read_u8()? {
+            0 => Ok(None),
+            1 => T::deserialize(de).map(Some),
+            _ => Err(Error::Other),
+        }
+    }
+}
+
+impl Serializable for u32 {
+    fn serialize(&self, ser: &mut Serializer) -> Result<usize, Error> {
+        ser.write_u32(*self)
+    }
+}
+
+impl Deserializable for u32 {
+    fn deserialize(de: &mut Deserializer) -> Result<Self, Error> {
+        de.read_u32()
+    }
+}
+
 pub fn serialize<T>(value: &T, out: &mut [u8]) -> Result<usize, Error>
 where
     T: Serializable,
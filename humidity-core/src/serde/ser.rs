@@ -10,14 +10,26 @@ impl<'output> Serializer<'output> {
         Self { out, pos: 0 }
     }
 
+    pub fn write_u32(&mut self, value: u32) -> Result<usize, Error> {
+        self.write(&value.to_le_bytes())
+    }
+
     pub fn write_u16(&mut self, value: u16) -> Result<usize, Error> {
         self.write(&value.to_le_bytes())
     }
 
+    pub fn write_i16(&mut self, value: i16) -> Result<usize, Error> {
+        self.write(&value.to_le_bytes())
+    }
+
     pub fn write_u8(&mut self, value: u8) -> Result<usize, Error> {
         self.write(&value.to_le_bytes())
     }
 
+    pub fn write_bytes(&mut self, value: &[u8]) -> Result<usize, Error> {
+        self.write(value)
+    }
+
     fn write(&mut self, value: &[u8]) -> Result<usize, Error> {
         if self.out[self.pos..].len() < value.len() {
             return Err(Error::ErrBufferSmall);
@@ -0,0 +1,313 @@
+//! # Device settings
+//!
+//! Settings a client can change over the air without reflashing the device:
+//! the sampling interval, the number of readings per sampling, the sensor model
+//! and an optional calibration of the sensor.
+//!
+//! A [`DeviceConfig`] is serialized with a leading [`VERSION`], so devices
+//! reject settings laid out by a client they do not understand, and checked by
+//! [`DeviceConfig::validate`] before being applied, as a bad interval or
+//! calibration would leave the device sampling nonsense, or not at all.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::{sensors::Hygrometer, settings::{Calibration, DeviceConfig}};
+//! let settings = DeviceConfig {
+//!     interval: 10 * 60,
+//!     samples: 32,
+//!     sensor: Hygrometer::HW390,
+//!     calibration: Some(Calibration { low: 1100, high: 2300 }),
+//! };
+//! let mut data = [0u8; 16];
+//! let n = settings.encode(&mut data).unwrap();
+//! assert_eq!(Ok(settings), DeviceConfig::decode(&data[..n]));
+//! ```
+
+use crate::{
+    lifecycle::Config,
+    sensors::{Hygrometer, Sensor},
+    serde::{self, Deserializable, Serializable},
+};
+
+/// Version of the serialized layout, bumped on incompatible changes.
+pub const VERSION: u8 = 1;
+/// Largest serialized size of a [`DeviceConfig`].
+pub const DEVICE_CONFIG_LEN: usize = 1 + 4 + 1 + 1 + 1 + 4;
+
+/// Shortest sampling interval, in seconds, as sampling drains the battery.
+pub const MIN_INTERVAL: u32 = 60;
+/// Longest sampling interval, in seconds.
+pub const MAX_INTERVAL: u32 = 24 * 60 * 60;
+/// Highest reading of the 12-bit ADC.
+const ADC_MAX: u16 = 4095;
+/// Narrowest calibration range, below which percentages are mostly noise.
+const MIN_SPAN: u16 = 100;
+
+/// Errors that can happen while decoding or validating settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The settings could not be read or written.
+    ErrEncoding,
+    /// The settings were laid out by an unknown version.
+    ErrVersion(u8),
+    /// The interval is out of [`MIN_INTERVAL`] and [`MAX_INTERVAL`].
+    ErrInterval,
+    /// No reading would be taken.
+    ErrSamples,
+    /// The calibration is out of the ADC range, or too narrow.
+    ErrCalibration,
+}
+
+/// ADC readings of the sensor in water and in air, measured on the device,
+/// overriding the ones of the sensor model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    /// Reading in water.
+    pub low: u16,
+    /// Reading in air.
+    pub high: u16,
+}
+
+/// Settings of the device, changeable by clients.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceConfig {
+    /// Sampling interval under normal conditions, in seconds.
+    pub interval: u32,
+    /// Readings averaged on each sampling.
+    pub samples: u8,
+    pub sensor: Hygrometer,
+    pub calibration: Option<Calibration>,
+}
+
+impl Calibration {
+    /// Maps a reading onto the range of the sensor model, so summaries keep
+    /// their meaning for anyone knowing only the model.
+    pub fn correct<S: Sensor>(&self, sensor: &S, reading: u16) -> u16 {
+        let (low, high) = (self.low as i32, self.high as i32);
+        let (model_low, model_high) = (sensor.low() as i32, sensor.high() as i32);
+        let reading = (reading as i32).clamp(low, high);
+        let corrected = model_low + (reading - low) * (model_high - model_low) / (high - low);
+        corrected as u16
+    }
+}
+
+impl DeviceConfig {
+    /// Checks the settings can be applied.
+    pub fn validate(&self) -> Result<(), Error> {
+        if !(MIN_INTERVAL..=MAX_INTERVAL).contains(&self.interval) {
+            return Err(Error::ErrInterval);
+        }
+        if self.samples == 0 {
+            return Err(Error::ErrSamples);
+        }
+        if let Some(Calibration { low, high }) = self.calibration {
+            if high > ADC_MAX || high < low.saturating_add(MIN_SPAN) {
+                return Err(Error::ErrCalibration);
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the settings to the lifecycle configuration, keeping the
+    /// adaptive interval bounds around the new interval.
+    pub fn apply(&self, config: &Config) -> Config {
+        let mut config = *config;
+        config.measure.base = self.interval;
+        config.measure.min = config.measure.min.min(self.interval);
+        config.measure.max = config.measure.max.max(self.interval);
+        config
+    }
+
+    /// Corrects a reading with the calibration, if any.
+    pub fn correct(&self, reading: u16) -> u16 {
+        match &self.calibration {
+            Some(calibration) => calibration.correct(&self.sensor, reading),
+            None => reading,
+        }
+    }
+
+    /// Serializes the settings, version first.
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, Error> {
+        serde::serialize(self, out).map_err(|_| Error::ErrEncoding)
+    }
+
+    /// Deserializes settings, rejecting unknown versions and invalid values.
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        let mut de = serde::Deserializer::new(data);
+        let version = de.read_u8().map_err(|_| Error::ErrEncoding)?;
+        if version != VERSION {
+            return Err(Error::ErrVersion(version));
+        }
+        let settings = Self::deserialize_fields(&mut de).map_err(|_| Error::ErrEncoding)?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn deserialize_fields(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        let interval = de.read_u32()?;
+        let samples = de.read_u8()?;
+        let sensor = Hygrometer::deserialize(de)?;
+        let calibration = Option::deserialize(de)?;
+        Ok(Self { interval, samples, sensor, calibration })
+    }
+}
+
+impl Serializable for Calibration {
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        Ok(ser.write_u16(self.low)? + ser.write_u16(self.high)?)
+    }
+}
+
+impl Deserializable for Calibration {
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        Ok(Self { low: de.read_u16()?, high: de.read_u16()? })
+    }
+}
+
+impl Serializable for DeviceConfig {
+    fn serialize(&self, ser: &mut serde::Serializer) -> Result<usize, serde::Error> {
+        let mut n = ser.write_u8(VERSION)?;
+        n += ser.write_u32(self.interval)?;
+        n += ser.write_u8(self.samples)?;
+        n += self.sensor.serialize(ser)?;
+        n += self.calibration.serialize(ser)?;
+        Ok(n)
+    }
+}
+
+impl Deserializable for DeviceConfig {
+    fn deserialize(de: &mut serde::Deserializer) -> Result<Self, serde::Error> {
+        match de.read_u8()? {
+            VERSION => Self::deserialize_fields(de),
+            _ => Err(serde::Error::Other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{advertising, irrigation, schedule, wake};
+    use test_case::test_case;
+
+    const SETTINGS: DeviceConfig = DeviceConfig {
+        interval: 15 * 60,
+        samples: 64,
+        sensor: Hygrometer::HW390,
+        calibration: None,
+    };
+
+    #[test_case(SETTINGS; "defaults")]
+    #[test_case(DeviceConfig { calibration: Some(Calibration { low: 900, high: 2400 }), ..SETTINGS }; "calibrated")]
+    #[test_case(DeviceConfig { sensor: Hygrometer::YL69, samples: 1, interval: MAX_INTERVAL, ..SETTINGS }; "resistive")]
+    fn test_roundtrip(sut: DeviceConfig) {
+        let mut data = [0u8; DEVICE_CONFIG_LEN];
+        let n = sut.encode(&mut data).unwrap();
+
+        assert_eq!(Ok(sut), DeviceConfig::decode(&data[..n]));
+        assert_eq!(Ok(sut), serde::deserialize(&data[..n]));
+    }
+
+    #[test]
+    fn test_encode() {
+        let sut =
+            DeviceConfig { calibration: Some(Calibration { low: 900, high: 2400 }), ..SETTINGS };
+        let mut data = [0u8; DEVICE_CONFIG_LEN];
+
+        assert_eq!(Ok(DEVICE_CONFIG_LEN), sut.encode(&mut data));
+        assert_eq!([1, 0x84, 0x03, 0, 0, 64, 1, 1, 0x84, 0x03, 0x60, 0x09], data);
+        assert_eq!(Err(Error::ErrEncoding), sut.encode(&mut [0u8; 8]));
+    }
+
+    #[test_case(&[], Error::ErrEncoding; "empty")]
+    #[test_case(&[2, 0x84, 0x03, 0, 0, 64, 1, 0], Error::ErrVersion(2); "unknown version")]
+    #[test_case(&[1, 0x84, 0x03, 0, 0, 64, 1], Error::ErrEncoding; "truncated")]
+    #[test_case(&[1, 0x84, 0x03, 0, 0, 64, 7, 0], Error::ErrEncoding; "unknown sensor")]
+    #[test_case(&[1, 30, 0, 0, 0, 64, 1, 0], Error::ErrInterval; "too often")]
+    #[test_case(&[1, 0x84, 0x03, 0, 0, 0, 1, 0], Error::ErrSamples; "no samples")]
+    #[test_case(&[1, 0x84, 0x03, 0, 0, 64, 1, 1, 0x60, 0x09, 0x84, 0x03], Error::ErrCalibration; "inverted calibration")]
+    fn test_decode_errors(data: &[u8], expected: Error) {
+        assert_eq!(Err(expected), DeviceConfig::decode(data));
+    }
+
+    #[test_case(MIN_INTERVAL - 1, 64, None, Err(Error::ErrInterval))]
+    #[test_case(MAX_INTERVAL + 1, 64, None, Err(Error::ErrInterval))]
+    #[test_case(MIN_INTERVAL, 0, None, Err(Error::ErrSamples))]
+    #[test_case(MIN_INTERVAL, 1, Some((1000, 1099)), Err(Error::ErrCalibration))]
+    #[test_case(MIN_INTERVAL, 1, Some((1000, 4096)), Err(Error::ErrCalibration))]
+    #[test_case(MIN_INTERVAL, 1, Some((1000, 1100)), Ok(()))]
+    #[test_case(MAX_INTERVAL, u8::MAX, Some((0, 4095)), Ok(()))]
+    fn test_validate(
+        interval: u32,
+        samples: u8,
+        calibration: Option<(u16, u16)>,
+        expected: Result<(), Error>,
+    ) {
+        let calibration = calibration.map(|(low, high)| Calibration { low, high });
+        let sut = DeviceConfig { interval, samples, calibration, ..SETTINGS };
+
+        assert_eq!(expected, sut.validate());
+    }
+
+    #[test_case(900, 1000; "wet")]
+    #[test_case(2400, 2050; "dry")]
+    #[test_case(1650, 1525; "halfway")]
+    #[test_case(500, 1000; "clamped wet")]
+    #[test_case(3000, 2050; "clamped dry")]
+    fn test_correct(reading: u16, expected: u16) {
+        let sut =
+            DeviceConfig { calibration: Some(Calibration { low: 900, high: 2400 }), ..SETTINGS };
+
+        assert_eq!(expected, sut.correct(reading));
+    }
+
+    #[test]
+    fn test_correct_uncalibrated() {
+        assert_eq!(1234, SETTINGS.correct(1234));
+    }
+
+    #[test]
+    fn test_apply() {
+        let config = Config {
+            measure: schedule::Policy {
+                min: 60,
+                base: 15 * 60,
+                max: 60 * 60,
+                fast_change: 5,
+                night_start: 22,
+                night_end: 7,
+            },
+            battery_check: 24 * 60 * 60,
+            rollup: 60 * 60,
+            advertise: 30 * 60,
+            watering: irrigation::Profile {
+                dry: 35,
+                target: 60,
+                hysteresis: 5,
+                min_interval: 30 * 60,
+                pulse_ms: 3000,
+            },
+            limits: irrigation::Limits {
+                max_run_ms: 10_000,
+                daily_budget_ml: 250,
+                flow_ml_per_min: 100,
+                cooldown: 15 * 60,
+                no_effect_pulses: 3,
+                min_rise: 2,
+            },
+            advertising: advertising::Policy { backlog: 24, on_alert: true, on_button: true },
+            windows: wake::Windows { regular_ms: 5_000, extended_ms: 60_000 },
+        };
+
+        let actual = DeviceConfig { interval: 5 * 60, ..SETTINGS }.apply(&config);
+        assert_eq!(schedule::Policy { base: 5 * 60, ..config.measure }, actual.measure);
+        assert_eq!(config.watering, actual.watering);
+
+        let actual = DeviceConfig { interval: 2 * 60 * 60, ..SETTINGS }.apply(&config);
+        assert_eq!(
+            (60, 2 * 60 * 60, 2 * 60 * 60),
+            (actual.measure.min, actual.measure.base, actual.measure.max)
+        );
+    }
+}
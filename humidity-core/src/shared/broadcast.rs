@@ -0,0 +1,155 @@
+//! Readings broadcast in the manufacturer specific data of advertisements, so
+//! clients get them without connecting.
+
+/// Company identifier reserved by the Bluetooth SIG for tests, as the project
+/// has none of its own.
+pub const COMPANY_ID: u16 = 0xFFFF;
+/// Version of the payload layout, bumped on incompatible changes.
+pub const VERSION: u8 = 1;
+/// Encoded size of a [`Broadcast`], without the company identifier.
+pub const BROADCAST_LEN: usize = 4;
+
+/// Marks unknown readings.
+const UNKNOWN: u8 = 0xFF;
+
+/// Errors that can happen while decoding a broadcast.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The payload is shorter than a broadcast.
+    ErrLength,
+    /// The payload was encoded by an unknown firmware version.
+    ErrVersion(u8),
+}
+
+/// Something worth knowing about the device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Flag {
+    /// Moisture is below the dry threshold.
+    Dry,
+    /// The battery should be replaced or recharged soon.
+    LowBattery,
+    /// The sensor could not be read, or reads nothing.
+    SensorFault,
+    /// Watering is halted.
+    PumpFault,
+    /// The pump ran on this wake.
+    Watered,
+}
+
+/// A set of [`Flag`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Status(u8);
+
+impl Status {
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Restores a set from its [`bits`](Self::bits), ignoring unknown flags.
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn insert(&mut self, flag: Flag) {
+        self.0 |= 1 << flag as u8;
+    }
+
+    pub fn contains(&self, flag: Flag) -> bool {
+        self.0 & (1 << flag as u8) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// Latest readings of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Broadcast {
+    /// Soil moisture from 0 to 100, if sampled.
+    pub moisture: Option<u8>,
+    /// Battery charge from 0 to 100, if measured.
+    pub battery: Option<u8>,
+    pub status: Status,
+}
+
+impl Broadcast {
+    /// Encodes the payload of the manufacturer specific data, to follow the
+    /// [`COMPANY_ID`].
+    pub fn encode(&self) -> [u8; BROADCAST_LEN] {
+        [
+            VERSION,
+            self.status.0,
+            self.moisture.map_or(UNKNOWN, |moisture| moisture.min(100)),
+            self.battery.map_or(UNKNOWN, |battery| battery.min(100)),
+        ]
+    }
+
+    /// Decodes the payload of the manufacturer specific data, ignoring any
+    /// trailing bytes added by later versions.
+    pub fn decode(payload: &[u8]) -> Result<Self, Error> {
+        let [version, status, moisture, battery, ..] = *payload else {
+            return Err(Error::ErrLength);
+        };
+        if version != VERSION {
+            return Err(Error::ErrVersion(version));
+        }
+        let known = |value: u8| (value != UNKNOWN).then_some(value);
+        Ok(Self { moisture: known(moisture), battery: known(battery), status: Status(status) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    fn status(flags: &[Flag]) -> Status {
+        let mut status = Status::empty();
+        flags.iter().for_each(|flag| status.insert(*flag));
+        status
+    }
+
+    #[test]
+    fn test_encode() {
+        let sut = Broadcast {
+            moisture: Some(42),
+            battery: Some(87),
+            status: status(&[Flag::Dry, Flag::Watered]),
+        };
+
+        assert_eq!([0x01, 0x11, 42, 87], sut.encode());
+    }
+
+    #[test_case(Some(42), Some(87), &[]; "readings")]
+    #[test_case(None, Some(0), &[Flag::SensorFault]; "no sample")]
+    #[test_case(Some(100), None, &[Flag::LowBattery, Flag::PumpFault]; "no battery")]
+    fn test_roundtrip(moisture: Option<u8>, battery: Option<u8>, flags: &[Flag]) {
+        let sut = Broadcast { moisture, battery, status: status(flags) };
+
+        assert_eq!(Ok(sut), Broadcast::decode(&sut.encode()));
+    }
+
+    #[test]
+    fn test_decode_ignores_trailing_bytes() {
+        let actual = Broadcast::decode(&[0x01, 0x04, 0xFF, 50, 0xAA]);
+
+        let expected =
+            Broadcast { moisture: None, battery: Some(50), status: status(&[Flag::SensorFault]) };
+        assert_eq!(Ok(expected), actual);
+        assert!(expected.status.contains(Flag::SensorFault));
+        assert!(!expected.status.contains(Flag::Dry));
+    }
+
+    #[test_case(&[], Error::ErrLength)]
+    #[test_case(&[0x01, 0x00, 42], Error::ErrLength)]
+    #[test_case(&[0x02, 0x00, 42, 87], Error::ErrVersion(2))]
+    fn test_decode_errors(payload: &[u8], expected: Error) {
+        assert_eq!(Err(expected), Broadcast::decode(payload));
+    }
+}
@@ -3,3 +3,8 @@
 //! Avoids duplicated magic values in both projects.
 
 pub const BLE_DEVICE_NAME: &str = "humidity-monitor";
+
+pub use broadcast::{Broadcast, Flag, Status};
+
+pub mod broadcast;
+pub mod protocol;
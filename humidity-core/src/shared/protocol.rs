@@ -0,0 +1,301 @@
+//! GATT layout of the device: its services, their characteristics, and what
+//! each one carries. The firmware, the simulator and the client all build on
+//! these, rather than on UUIDs of their own.
+
+use core::fmt;
+
+/// Version of the layout and of the payloads, bumped on incompatible changes.
+/// Readable from the [`PROTOCOL_VERSION`] characteristic.
+pub const VERSION: u8 = 1;
+
+/// The Bluetooth base UUID, which 16-bit UUIDs are short for.
+const BASE_UUID: u128 = 0x00000000_0000_1000_8000_00805f9b34fb;
+
+/// A Bluetooth UUID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Uuid(u128);
+
+impl Uuid {
+    pub const fn from_u128(value: u128) -> Self {
+        Self(value)
+    }
+
+    /// Expands a 16-bit UUID assigned by the Bluetooth SIG.
+    pub const fn from_u16(value: u16) -> Self {
+        Self(BASE_UUID | (value as u128) << 96)
+    }
+
+    pub const fn as_u128(&self) -> u128 {
+        self.0
+    }
+
+    /// Returns the 16-bit form of UUIDs assigned by the Bluetooth SIG.
+    pub const fn as_u16(&self) -> Option<u16> {
+        if self.0 & !(0xffff << 96) == BASE_UUID {
+            Some((self.0 >> 96) as u16)
+        } else {
+            None
+        }
+    }
+
+    /// Parses either form of [`Display`](fmt::Display), in any case.
+    pub const fn parse(text: &str) -> Option<Self> {
+        let bytes = text.as_bytes();
+        if bytes.len() == 4 {
+            return match parse_hex(bytes, 0, 4) {
+                Some(value) => Some(Self::from_u16(value as u16)),
+                None => None,
+            };
+        }
+        if bytes.len() != 36 {
+            return None;
+        }
+        let mut value = 0u128;
+        let mut i = 0;
+        while i < bytes.len() {
+            if matches!(i, 8 | 13 | 18 | 23) {
+                if bytes[i] != b'-' {
+                    return None;
+                }
+            } else {
+                match hex_digit(bytes[i]) {
+                    Some(digit) => value = value << 4 | digit as u128,
+                    None => return None,
+                }
+            }
+            i += 1;
+        }
+        Some(Self(value))
+    }
+
+    /// Whether the text is a form of this UUID.
+    pub const fn matches(&self, text: &str) -> bool {
+        match Self::parse(text) {
+            Some(uuid) => uuid.0 == self.0,
+            None => false,
+        }
+    }
+}
+
+/// Formats 16-bit UUIDs as 4 hexadecimal digits, and others in full.
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(short) = self.as_u16() {
+            return write!(f, "{short:04x}");
+        }
+        let v = self.0;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            v >> 96,
+            (v >> 80) & 0xffff,
+            (v >> 64) & 0xffff,
+            (v >> 48) & 0xffff,
+            v & 0xffff_ffff_ffff
+        )
+    }
+}
+
+const fn hex_digit(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+const fn parse_hex(bytes: &[u8], start: usize, end: usize) -> Option<u32> {
+    let mut value = 0u32;
+    let mut i = start;
+    while i < end {
+        match hex_digit(bytes[i]) {
+            Some(digit) => value = value << 4 | digit as u32,
+            None => return None,
+        }
+        i += 1;
+    }
+    Some(value)
+}
+
+/// What a characteristic carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Payload {
+    /// The last [`Summary`](crate::sample::Summary), serialized, or nothing
+    /// before the first sample. New ones are notified as they are taken.
+    Summary,
+    /// A serialized [`Record`](crate::sample::Record) of the history on every
+    /// read, oldest first, then nothing once all were read. Records of the
+    /// samples taken while connected, up to
+    /// [`LIVE_LEN`](crate::lifecycle::LIVE_LEN) of them, are notified as they
+    /// are appended.
+    Records,
+    /// Encoded [`DeviceConfig`](crate::settings::DeviceConfig).
+    DeviceConfig,
+    /// Encoded [`Request`](crate::rpc::Request)s.
+    RpcRequest,
+    /// Encoded [`Response`](crate::rpc::Response)s, notified.
+    RpcResponse,
+    /// The [`VERSION`] of the protocol, a single byte.
+    Version,
+    /// Battery charge from 0 to 100, a single byte.
+    BatteryLevel,
+    /// Soil moisture in hundredths of a percent, as encoded by
+    /// [`ess::humidity`](crate::ess::humidity).
+    Humidity,
+    /// Unix times of the records of the history, corrected for the drift of the
+    /// device clock, as written by [`Times`](crate::time::Times): as many as
+    /// fit on every read, oldest first, then nothing once all were read.
+    Times,
+    /// The time, as encoded by [`cts`](crate::time::cts).
+    CurrentTime,
+}
+
+/// A characteristic, and how clients access it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Characteristic {
+    pub uuid: Uuid,
+    pub payload: Payload,
+    pub read: bool,
+    pub write: bool,
+    pub notify: bool,
+}
+
+impl Characteristic {
+    const fn read_only(uuid: Uuid, payload: Payload) -> Self {
+        Self { uuid, payload, read: true, write: false, notify: false }
+    }
+
+    const fn notified(uuid: Uuid, payload: Payload) -> Self {
+        Self { notify: true, ..Self::read_only(uuid, payload) }
+    }
+}
+
+/// A service, and its characteristics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Service {
+    pub uuid: Uuid,
+    pub characteristics: &'static [Characteristic],
+}
+
+pub const HUMIDITY: Characteristic =
+    Characteristic::notified(Uuid(0x987312e0_2354_11eb_9f10_fbc30a62cf50), Payload::Summary);
+pub const HISTORICAL: Characteristic =
+    Characteristic::notified(Uuid(0x987312e0_2354_11eb_9f10_fbc30a62cf60), Payload::Records);
+pub const DEVICE_CONFIG: Characteristic = Characteristic {
+    write: true,
+    ..Characteristic::read_only(Uuid(0x987312e0_2354_11eb_9f10_fbc30a62cf70), Payload::DeviceConfig)
+};
+pub const RPC_REQUEST: Characteristic = Characteristic {
+    uuid: Uuid(0x987312e0_2354_11eb_9f10_fbc30a62cf80),
+    payload: Payload::RpcRequest,
+    read: false,
+    write: true,
+    notify: false,
+};
+pub const RPC_RESPONSE: Characteristic =
+    Characteristic::notified(Uuid(0x987312e0_2354_11eb_9f10_fbc30a62cf90), Payload::RpcResponse);
+pub const PROTOCOL_VERSION: Characteristic =
+    Characteristic::read_only(Uuid(0x987312e0_2354_11eb_9f10_fbc30a62cfa0), Payload::Version);
+pub const HISTORY_TIMES: Characteristic =
+    Characteristic::read_only(Uuid(0x987312e0_2354_11eb_9f10_fbc30a62cfb0), Payload::Times);
+/// From the Battery Service.
+pub const BATTERY_LEVEL: Characteristic =
+    Characteristic::read_only(Uuid::from_u16(0x2a19), Payload::BatteryLevel);
+/// From the Environmental Sensing Service.
+pub const ESS_HUMIDITY: Characteristic =
+    Characteristic::read_only(Uuid::from_u16(0x2a6f), Payload::Humidity);
+/// From the Current Time Service, written by clients on connecting.
+pub const CURRENT_TIME: Characteristic = Characteristic {
+    write: true,
+    ..Characteristic::read_only(Uuid::from_u16(0x2a2b), Payload::CurrentTime)
+};
+/// Descriptor of [`ESS_HUMIDITY`], an encoded
+/// [`EsMeasurement`](crate::ess::EsMeasurement).
+pub const ES_MEASUREMENT: Uuid = Uuid::from_u16(0x290c);
+
+/// The service of the project.
+pub const HUMIDITY_SERVICE: Service = Service {
+    uuid: Uuid(0x937312e0_2354_11eb_9f10_fbc30a62cf00),
+    characteristics: &[
+        HUMIDITY,
+        HISTORICAL,
+        DEVICE_CONFIG,
+        RPC_REQUEST,
+        RPC_RESPONSE,
+        PROTOCOL_VERSION,
+        HISTORY_TIMES,
+    ],
+};
+pub const BATTERY_SERVICE: Service =
+    Service { uuid: Uuid::from_u16(0x180f), characteristics: &[BATTERY_LEVEL] };
+pub const ESS_SERVICE: Service =
+    Service { uuid: Uuid::from_u16(0x181a), characteristics: &[ESS_HUMIDITY] };
+pub const CTS_SERVICE: Service =
+    Service { uuid: Uuid::from_u16(0x1805), characteristics: &[CURRENT_TIME] };
+
+/// Every service of the device.
+pub const SERVICES: [Service; 4] = [HUMIDITY_SERVICE, BATTERY_SERVICE, ESS_SERVICE, CTS_SERVICE];
+
+/// Finds a characteristic of the device by UUID.
+pub fn find(uuid: Uuid) -> Option<&'static Characteristic> {
+    SERVICES
+        .iter()
+        .flat_map(|service| service.characteristics)
+        .find(|characteristic| characteristic.uuid == uuid)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("2a19" => Some(Uuid::from_u16(0x2a19)) ; "short")]
+    #[test_case("00002A19-0000-1000-8000-00805F9B34FB" => Some(Uuid::from_u16(0x2a19)) ; "base")]
+    #[test_case("987312e0-2354-11eb-9f10-fbc30a62cf50" => Some(HUMIDITY.uuid) ; "long")]
+    #[test_case("987312e0_2354-11eb-9f10-fbc30a62cf50" => None ; "misplaced hyphen")]
+    #[test_case("2a1g" => None ; "not hexadecimal")]
+    #[test_case("2a190" => None ; "length")]
+    fn test_parse(text: &str) -> Option<Uuid> {
+        Uuid::parse(text)
+    }
+
+    struct Buffer {
+        text: [u8; 36],
+        len: usize,
+    }
+
+    impl fmt::Write for Buffer {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.text.get_mut(self.len..end).ok_or(fmt::Error)?.copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    #[test_case(BATTERY_LEVEL.uuid, "2a19" ; "short")]
+    #[test_case(HUMIDITY_SERVICE.uuid, "937312e0-2354-11eb-9f10-fbc30a62cf00" ; "long")]
+    fn test_display(uuid: Uuid, expected: &str) {
+        use fmt::Write;
+        let mut sut = Buffer { text: [0; 36], len: 0 };
+
+        write!(sut, "{uuid}").unwrap();
+
+        assert_eq!(expected.as_bytes(), &sut.text[..sut.len]);
+        assert!(uuid.matches(expected));
+    }
+
+    #[test]
+    fn test_unique_uuids() {
+        let characteristics = SERVICES.iter().flat_map(|service| service.characteristics);
+        for (i, characteristic) in characteristics.clone().enumerate() {
+            assert_eq!(Some(characteristic), find(characteristic.uuid));
+            assert_eq!(
+                1,
+                characteristics.clone().skip(i).filter(|c| c.uuid == characteristic.uuid).count()
+            );
+        }
+        assert_eq!(None, find(ES_MEASUREMENT));
+    }
+}
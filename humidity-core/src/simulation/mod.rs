@@ -0,0 +1,282 @@
+//! # Soil simulation
+//!
+//! Produces realistic soil moisture over time, to exercise the irrigation and
+//! scheduling logic without a plant, and without waiting for it to dry.
+//!
+//! A [`Model`] tracks the water held by a pot of [`Soil`] under a daily
+//! [`Weather`] cycle. Water evaporates faster when it is warm, dry and sunny,
+//! and slower as the soil dries out, while watering above the field capacity
+//! drains away over the next hours. A [`Probe`] then turns moisture into the
+//! ADC readings a [`Sensor`](crate::sensors::Sensor) would output, with noise,
+//! temperature drift and a slow calibration drift.
+//!
+//! All computations are done in fixed point, so a simulation replays the same
+//! way on every target.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::{
+//!     sensors::Hygrometer,
+//!     simulation::{Model, Probe, Soil, Weather},
+//! };
+//! let mut model = Model::new(Soil::POTTING_MIX, Weather::INDOORS, 60);
+//! let mut probe = Probe::new(Hygrometer::HW390, 1);
+//! let now = 24 * 60 * 60;
+//! let summary = probe.sample(16, model.moisture(now), &model.weather.at(now), now);
+//! println!("after a day: {}%, read as {summary:?}", model.moisture(now));
+//! ```
+
+pub use probe::Probe;
+
+use crate::sensors::REFERENCE_TEMPERATURE;
+
+mod probe;
+
+const HOUR: u32 = 60 * 60;
+const DAY: u32 = 24 * HOUR;
+/// Water held by saturated soil, in millionths.
+const SATURATED: u32 = 1_000_000;
+/// Longest step of the integration, in seconds.
+const STEP: u32 = 10 * 60;
+
+/// Physical properties of the soil and its pot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Soil {
+    /// Water needed to saturate dry soil, in milliliters.
+    pub capacity_ml: u16,
+    /// Moisture the soil holds against gravity, in per-mille. Any water above
+    /// it drains away.
+    pub field_capacity: u16,
+    /// Share of the water above the field capacity draining each hour, in
+    /// per-mille.
+    pub drainage: u16,
+    /// Moisture lost each hour at field capacity, in per-mille, at the
+    /// reference temperature, 50 %RH and in daylight.
+    pub evaporation: u16,
+}
+
+impl Soil {
+    /// Bonsai substrate in a shallow pot, draining fast and drying within days.
+    pub const BONSAI: Soil =
+        Soil { capacity_ml: 150, field_capacity: 600, drainage: 600, evaporation: 12 };
+    /// Regular potting mix, holding water for about a week.
+    pub const POTTING_MIX: Soil =
+        Soil { capacity_ml: 1500, field_capacity: 750, drainage: 300, evaporation: 6 };
+}
+
+/// Daily cycle of the air around the plant, warmest and driest at 15:00 and
+/// coldest and most humid at 03:00, time 0 being midnight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weather {
+    /// Average temperature, in centi-degrees Celsius.
+    pub temperature: i16,
+    /// Difference between the average and the warmest temperature.
+    pub temperature_swing: i16,
+    /// Average relative humidity, in centi-percent.
+    pub humidity: u16,
+    /// Difference between the average and the most humid air.
+    pub humidity_swing: u16,
+    /// First hour of daylight.
+    pub sunrise: u8,
+    /// First hour of darkness.
+    pub sunset: u8,
+}
+
+/// The air around the plant at a given time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conditions {
+    /// Temperature, in centi-degrees Celsius.
+    pub temperature: i16,
+    /// Relative humidity, in centi-percent.
+    pub humidity: u16,
+    pub daylight: bool,
+}
+
+impl Conditions {
+    /// Conditions at which sensors read their calibrated values.
+    pub const REFERENCE: Conditions =
+        Conditions { temperature: REFERENCE_TEMPERATURE, humidity: 5000, daylight: true };
+}
+
+impl Weather {
+    pub const INDOORS: Weather = Weather {
+        temperature: 2100,
+        temperature_swing: 150,
+        humidity: 4500,
+        humidity_swing: 500,
+        sunrise: 7,
+        sunset: 20,
+    };
+    pub const SUMMER: Weather = Weather {
+        temperature: 2400,
+        temperature_swing: 600,
+        humidity: 5500,
+        humidity_swing: 2000,
+        sunrise: 6,
+        sunset: 21,
+    };
+
+    /// Returns the conditions at the given time, in seconds.
+    pub fn at(&self, now: u32) -> Conditions {
+        let time = now % DAY;
+        // Follows a triangle wave, from -1000 at 03:00 to 1000 at 15:00.
+        let distance = time.abs_diff(15 * HOUR);
+        let distance = distance.min(DAY - distance);
+        let wave = 1000 - (distance * 2000 / (12 * HOUR)) as i32;
+
+        let temperature = self.temperature as i32 + self.temperature_swing as i32 * wave / 1000;
+        let humidity = self.humidity as i32 - self.humidity_swing as i32 * wave / 1000;
+        let hour = (time / HOUR) as u8;
+        Conditions {
+            temperature: temperature.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+            humidity: humidity.clamp(0, 10_000) as u16,
+            daylight: (self.sunrise..self.sunset).contains(&hour),
+        }
+    }
+}
+
+/// Water held by a pot of soil over time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Model {
+    pub soil: Soil,
+    pub weather: Weather,
+    /// Water held, in millionths of saturation.
+    water: u32,
+    /// Time the model was last advanced to, in seconds.
+    last: u32,
+}
+
+impl Model {
+    /// Creates a model at time 0, with the given moisture from 0 to 100.
+    pub fn new(soil: Soil, weather: Weather, moisture: u8) -> Self {
+        Self { soil, weather, water: moisture.min(100) as u32 * SATURATED / 100, last: 0 }
+    }
+
+    /// Returns the moisture at the given time, from 0 (as dry as air) to 100
+    /// (as wet as water).
+    pub fn moisture(&mut self, now: u32) -> u8 {
+        self.advance(now);
+        ((self.water + SATURATED / 200) / (SATURATED / 100)) as u8
+    }
+
+    /// Pours water at the given time, any excess over saturation running off.
+    pub fn water(&mut self, now: u32, ml: u32) {
+        self.advance(now);
+        let poured = ml as u64 * SATURATED as u64 / self.soil.capacity_ml.max(1) as u64;
+        self.water = (self.water as u64 + poured).min(SATURATED as u64) as u32;
+    }
+
+    /// Integrates evaporation and drainage up to the given time. Going back in
+    /// time does nothing.
+    pub fn advance(&mut self, now: u32) {
+        while self.last < now {
+            let step = (now - self.last).min(STEP);
+            let conditions = self.weather.at(self.last);
+            let lost = self.evaporation(&conditions, step) + self.drainage(step);
+            self.water = self.water.saturating_sub(lost);
+            self.last += step;
+        }
+    }
+
+    fn evaporation(&self, conditions: &Conditions, step: u32) -> u32 {
+        // Doubles every 10 ºC above the reference temperature, roughly.
+        let temperature =
+            (1000 + conditions.temperature as i64 - REFERENCE_TEMPERATURE as i64).clamp(250, 3000);
+        // Grows with the vapour pressure deficit, 1000 at 50 %RH.
+        let humidity = ((10_000 - conditions.humidity.min(10_000) as i64) / 5).max(50);
+        let light = if conditions.daylight { 1000 } else { 300 };
+        // Slows down as the remaining water is held tighter by the soil.
+        let field_capacity = self.field_capacity().max(1) as i64;
+        let wetness = self.water.min(field_capacity as u32) as i64;
+
+        let per_hour = self.soil.evaporation as i64 * 1000 * temperature / 1000 * humidity / 1000
+            * light
+            / 1000;
+        (per_hour * wetness / field_capacity * step as i64 / HOUR as i64) as u32
+    }
+
+    fn drainage(&self, step: u32) -> u32 {
+        let excess = self.water.saturating_sub(self.field_capacity()) as u64;
+        (excess * self.soil.drainage as u64 / 1000 * step as u64 / HOUR as u64) as u32
+    }
+
+    fn field_capacity(&self) -> u32 {
+        self.soil.field_capacity.min(1000) as u32 * (SATURATED / 1000)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    const STILL: Weather = Weather {
+        temperature: REFERENCE_TEMPERATURE,
+        temperature_swing: 0,
+        humidity: 5000,
+        humidity_swing: 0,
+        sunrise: 0,
+        sunset: 24,
+    };
+
+    #[test_case(3 * HOUR, 1950, 5000, false; "night")]
+    #[test_case(9 * HOUR, 2100, 4500, true; "morning")]
+    #[test_case(15 * HOUR, 2250, 4000, true; "afternoon")]
+    #[test_case(21 * HOUR + 7 * DAY, 2100, 4500, false; "evening")]
+    fn test_weather(now: u32, temperature: i16, humidity: u16, daylight: bool) {
+        let sut = Weather::INDOORS;
+        assert_eq!(Conditions { temperature, humidity, daylight }, sut.at(now));
+    }
+
+    #[test]
+    fn test_evaporation_at_reference() {
+        let soil = Soil { drainage: 0, ..Soil::POTTING_MIX };
+        let mut sut = Model::new(soil, STILL, 75);
+
+        // 6 per-mille an hour at field capacity, slowing down as it dries.
+        assert_eq!(74, sut.moisture(2 * HOUR));
+        assert_eq!(51, sut.moisture(2 * DAY));
+        assert!(sut.moisture(30 * DAY) < 10);
+    }
+
+    #[test]
+    fn test_dries_faster_when_warm_and_dry() {
+        let hot = Weather { temperature: 3000, humidity: 2000, ..STILL };
+        let cold = Weather { temperature: 1000, humidity: 8000, sunset: 0, ..STILL };
+        let mut hot = Model::new(Soil::POTTING_MIX, hot, 70);
+        let mut normal = Model::new(Soil::POTTING_MIX, STILL, 70);
+        let mut cold = Model::new(Soil::POTTING_MIX, cold, 70);
+
+        let (hot, normal, cold) = (hot.moisture(DAY), normal.moisture(DAY), cold.moisture(DAY));
+        assert!(hot < normal, "{hot} < {normal}");
+        assert!(normal < cold, "{normal} < {cold}");
+    }
+
+    #[test]
+    fn test_watering_drains_to_field_capacity() {
+        let soil = Soil { evaporation: 0, ..Soil::BONSAI };
+        let mut sut = Model::new(soil, STILL, 40);
+
+        sut.water(0, 75);
+        assert_eq!(90, sut.moisture(0));
+        assert!(sut.moisture(2 * HOUR) < 75);
+        assert_eq!(60, sut.moisture(DAY));
+    }
+
+    #[test]
+    fn test_watering_runs_off_when_saturated() {
+        let mut sut = Model::new(Soil::BONSAI, STILL, 90);
+
+        sut.water(0, 1000);
+        assert_eq!(100, sut.moisture(0));
+    }
+
+    #[test]
+    fn test_does_not_go_back_in_time() {
+        let mut sut = Model::new(Soil::POTTING_MIX, Weather::SUMMER, 70);
+
+        let moisture = sut.moisture(DAY);
+        assert_eq!(moisture, sut.moisture(0));
+    }
+}
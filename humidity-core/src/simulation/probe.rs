@@ -0,0 +1,145 @@
+//! Simulated soil sensor readings.
+
+use crate::{
+    sample::Summary,
+    sensors::{Sensor, REFERENCE_TEMPERATURE},
+};
+
+use super::{Conditions, DAY};
+
+/// Highest reading of the 12 bit ADC.
+const ADC_MAX: u16 = 4095;
+
+/// Reads moisture as the ADC would through a sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Probe<S> {
+    pub sensor: S,
+    /// Largest deviation of a reading from the actual value, in ADC counts.
+    pub noise: u16,
+    /// Calibration drift, in ADC counts per day.
+    pub drift: i16,
+    /// Whether samples carry the compensation for the soil temperature, as when
+    /// a temperature probe is fitted.
+    pub compensate: bool,
+    /// State of the pseudo random noise generator, never zero.
+    seed: u32,
+}
+
+impl<S> Probe<S>
+where
+    S: Sensor + Copy,
+{
+    /// Creates a noisy probe without drift. The same seed always produces the
+    /// same noise.
+    pub fn new(sensor: S, seed: u32) -> Self {
+        Self { sensor, noise: 8, drift: 0, compensate: false, seed: seed.max(1) }
+    }
+
+    /// Returns a single reading, given the moisture from 0 to 100 and the time
+    /// in seconds.
+    pub fn read(&mut self, moisture: u8, conditions: &Conditions, now: u32) -> u16 {
+        let (low, high) = (self.sensor.low() as i32, self.sensor.high() as i32);
+        let ideal = low + (high - low) * (100 - moisture.min(100) as i32) / 100;
+        let temperature = self.sensor.temperature_coefficient() as i32
+            * (conditions.temperature as i32 - REFERENCE_TEMPERATURE as i32)
+            / 100;
+        let drift = (self.drift as i64 * now as i64 / DAY as i64) as i32;
+        let noise = match self.noise {
+            0 => 0,
+            noise => (self.next() % (2 * noise as u32 + 1)) as i32 - noise as i32,
+        };
+        (ideal + temperature + drift + noise).clamp(0, ADC_MAX as i32) as u16
+    }
+
+    /// Takes `n` readings and summarizes them, as the firmware does.
+    pub fn sample(&mut self, n: u8, moisture: u8, conditions: &Conditions, now: u32) -> Summary<S> {
+        let n = n.max(1);
+        let (mut sum, mut min, mut max) = (0u32, u16::MAX, u16::MIN);
+        for _ in 0..n {
+            let reading = self.read(moisture, conditions, now);
+            sum += reading as u32;
+            min = min.min(reading);
+            max = max.max(reading);
+        }
+        Summary {
+            n,
+            avg: (sum / n as u32) as u16,
+            min,
+            max,
+            sensor: self.sensor,
+            compensation: self.compensate.then(|| self.sensor.compensation(conditions.temperature)),
+        }
+    }
+
+    /// Steps the xorshift generator.
+    fn next(&mut self) -> u32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sensors::Hygrometer;
+    use test_case::test_case;
+
+    const WARM: Conditions = Conditions { temperature: 3000, ..Conditions::REFERENCE };
+
+    fn noiseless(sensor: Hygrometer) -> Probe<Hygrometer> {
+        Probe { noise: 0, ..Probe::new(sensor, 1) }
+    }
+
+    #[test_case(Hygrometer::HW390, 0, 2050)]
+    #[test_case(Hygrometer::HW390, 100, 1000)]
+    #[test_case(Hygrometer::HW390, 60, 1420)]
+    #[test_case(Hygrometer::YL69, 60, 953)]
+    fn test_read(sensor: Hygrometer, moisture: u8, expected: u16) {
+        let mut sut = noiseless(sensor);
+        assert_eq!(expected, sut.read(moisture, &Conditions::REFERENCE, 0));
+    }
+
+    #[test]
+    fn test_noise() {
+        let mut sut = Probe::new(Hygrometer::HW390, 42);
+
+        let summary = sut.sample(64, 60, &Conditions::REFERENCE, 0);
+        assert!(summary.min >= 1412 && summary.max <= 1428, "{summary:?}");
+        assert!(summary.min < summary.max);
+        assert_eq!(60, summary.moisture());
+
+        let mut again = Probe::new(Hygrometer::HW390, 42);
+        assert_eq!(summary, again.sample(64, 60, &Conditions::REFERENCE, 0));
+    }
+
+    #[test]
+    fn test_drift() {
+        let mut sut = Probe { drift: 5, ..noiseless(Hygrometer::HW390) };
+
+        assert_eq!(1420, sut.read(60, &Conditions::REFERENCE, 0));
+        assert_eq!(1490, sut.read(60, &Conditions::REFERENCE, 14 * DAY));
+    }
+
+    #[test]
+    fn test_temperature_compensation() {
+        let mut sut = noiseless(Hygrometer::HW390);
+
+        let raw = sut.sample(4, 60, &WARM, 0);
+        assert_eq!(1450, raw.avg);
+        assert_eq!(None, raw.compensation);
+
+        sut.compensate = true;
+        let compensated = sut.sample(4, 60, &WARM, 0);
+        assert_eq!(1450, compensated.avg);
+        assert_eq!(1420, compensated.compensated_avg());
+    }
+
+    #[test]
+    fn test_clamps_to_adc_range() {
+        let mut sut = Probe { drift: -100, ..noiseless(Hygrometer::YL69) };
+
+        assert_eq!(0, sut.read(100, &Conditions::REFERENCE, 10 * DAY));
+    }
+}
@@ -0,0 +1,152 @@
+//! # Current Time Service
+//!
+//! Encodes and decodes the Current Time characteristic of the Bluetooth
+//! Current Time Service, which clients write the time to. The time is taken as
+//! UTC: the device has no use for time zones.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::time::cts;
+//! let data = cts::encode(1_700_000_000);
+//! // 2023-11-14 22:13:20, a Tuesday.
+//! assert_eq!([0xe7, 0x07, 11, 14, 22, 13, 20, 2, 0, 0], data);
+//! assert_eq!(Ok(1_700_000_000), cts::decode(&data));
+//! ```
+
+/// Length of the Current Time characteristic.
+pub const CURRENT_TIME_LEN: usize = 10;
+
+/// Errors decoding the Current Time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    ErrLength,
+    /// A date out of range, or before the Unix epoch.
+    ErrDate,
+}
+
+const DAY: u32 = 86_400;
+
+/// Encodes a time, in seconds since the Unix epoch, with no fractions of
+/// seconds nor adjust reason.
+pub fn encode(unix: u32) -> [u8; CURRENT_TIME_LEN] {
+    let days = unix / DAY;
+    let secs = unix % DAY;
+    let (year, month, day) = civil_from_days(days);
+    let [year_lo, year_hi] = year.to_le_bytes();
+    // 1970-01-01 was a Thursday, and weeks start on Monday, numbered 1.
+    let day_of_week = ((days + 3) % 7 + 1) as u8;
+    [
+        year_lo,
+        year_hi,
+        month,
+        day,
+        (secs / 3600) as u8,
+        (secs / 60 % 60) as u8,
+        (secs % 60) as u8,
+        day_of_week,
+        0,
+        0,
+    ]
+}
+
+/// Decodes a time into seconds since the Unix epoch, ignoring the day of the
+/// week, fractions of seconds and adjust reason.
+pub fn decode(data: &[u8]) -> Result<u32, Error> {
+    let data: &[u8; CURRENT_TIME_LEN] = data.try_into().map_err(|_| Error::ErrLength)?;
+    let year = u16::from_le_bytes([data[0], data[1]]);
+    let [month, day, hours, minutes, seconds] = [data[2], data[3], data[4], data[5], data[6]];
+    if year < 1970
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hours > 23
+        || minutes > 59
+        || seconds > 59
+    {
+        return Err(Error::ErrDate);
+    }
+    let secs = hours as u32 * 3600 + minutes as u32 * 60 + seconds as u32;
+    days_from_civil(year, month, day)
+        .checked_mul(DAY)
+        .and_then(|days| days.checked_add(secs))
+        .ok_or(Error::ErrDate)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since the Unix epoch of a date, after Howard Hinnant's algorithm,
+/// with years starting in March so that leap days come last.
+fn days_from_civil(year: u16, month: u8, day: u8) -> u32 {
+    let year = year as u32 - (month <= 2) as u32;
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month = month as u32;
+    let day_of_year =
+        (153 * if month > 2 { month - 3 } else { month + 9 } + 2) / 5 + day as u32 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date of days since the Unix epoch, the inverse of [`days_from_civil`].
+fn civil_from_days(days: u32) -> (u16, u8, u8) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as u32;
+    (year as u16, month as u8, day as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0, [0xb2, 0x07, 1, 1, 0, 0, 0, 4, 0, 0] ; "epoch")]
+    #[test_case(951_782_400, [0xd0, 0x07, 2, 29, 0, 0, 0, 2, 0, 0] ; "leap day")]
+    #[test_case(1_709_251_199, [0xe8, 0x07, 2, 29, 23, 59, 59, 4, 0, 0] ; "end of leap day")]
+    #[test_case(4_102_444_800, [0x34, 0x08, 1, 1, 0, 0, 0, 5, 0, 0] ; "next century")]
+    #[test_case(u32::MAX, [0x3a, 0x08, 2, 7, 6, 28, 15, 7, 0, 0] ; "last")]
+    fn test_encode_decode(unix: u32, data: [u8; CURRENT_TIME_LEN]) {
+        assert_eq!(data, encode(unix));
+        assert_eq!(Ok(unix), decode(&data));
+    }
+
+    #[test]
+    fn test_every_day() {
+        for days in (0..u32::MAX / DAY).step_by(7) {
+            let unix = days * DAY + 12 * 3600;
+            assert_eq!(Ok(unix), decode(&encode(unix)));
+        }
+    }
+
+    #[test]
+    fn test_ignores_fractions_and_adjust_reason() {
+        assert_eq!(Ok(0), decode(&[0xb2, 0x07, 1, 1, 0, 0, 0, 0, 128, 1]));
+    }
+
+    #[test_case(&[0xb2, 0x07, 1, 1, 0, 0, 0, 4, 0] => Err(Error::ErrLength) ; "short")]
+    #[test_case(&[0xb1, 0x07, 12, 31, 0, 0, 0, 0, 0, 0] => Err(Error::ErrDate) ; "before epoch")]
+    #[test_case(&[0x3a, 0x08, 2, 7, 6, 28, 16, 0, 0, 0] => Err(Error::ErrDate) ; "overflow")]
+    #[test_case(&[0xe7, 0x07, 2, 29, 0, 0, 0, 0, 0, 0] => Err(Error::ErrDate) ; "not leap year")]
+    #[test_case(&[0xe7, 0x07, 13, 1, 0, 0, 0, 0, 0, 0] => Err(Error::ErrDate) ; "month")]
+    #[test_case(&[0xe7, 0x07, 1, 1, 24, 0, 0, 0, 0, 0] => Err(Error::ErrDate) ; "hours")]
+    #[test_case(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0] => Err(Error::ErrDate) ; "unknown")]
+    fn test_decode_invalid(data: &[u8]) -> Result<u32, Error> {
+        decode(data)
+    }
+}
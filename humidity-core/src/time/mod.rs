@@ -0,0 +1,255 @@
+//! # Wall-clock time
+//!
+//! The device clock only counts seconds since it was powered up, keeping on
+//! during deep sleep. Clients set the time now and then, over the
+//! [Current Time Service](cts) or RPC, and a [`WallClock`] turns device clock
+//! times into Unix times from the latest of those syncs.
+//!
+//! The RTC of the chips runs off an RC oscillator, off by up to a few percent.
+//! Successive syncs tell how far it ran fast or slow, which corrects every time
+//! converted from then on, including those of records stored earlier.
+//! [`Times`] gives the corrected time of each record of the history.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::time::WallClock;
+//! let mut clock = WallClock::new();
+//! clock.sync(1_000, 1_700_000_000);
+//! // The device clock ran 1% fast over a day.
+//! clock.sync(1_000 + 87_264, 1_700_086_400);
+//! assert_eq!(10_000, clock.ppm());
+//! assert_eq!(Some(1_700_172_800), clock.unix(1_000 + 2 * 87_264));
+//! ```
+
+use crate::{historical::Historical, serde};
+
+pub mod cts;
+
+/// Shortest time between two syncs to learn the drift from, in seconds, so
+/// the delay of the client writing the time does not weigh too much.
+const MIN_SPAN: u32 = 3600;
+/// Largest drift learned from a pair of syncs, in parts per million. Anything
+/// larger comes from a wrong time rather than the oscillator, and is ignored.
+const MAX_PPM: i64 = 50_000;
+/// Weight of the previous estimate, out of [`WEIGHT`], against the latest one.
+const WEIGHT: i64 = 4;
+
+/// Converts device clock times, in seconds, into Unix times, correcting the
+/// drift of the device clock. Meant to live in memory retained across deep
+/// sleeps.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WallClock {
+    /// Device clock and Unix times of the latest sync.
+    anchor: Option<(u32, u32)>,
+    /// Sync the drift is measured from, at least [`MIN_SPAN`] before the next.
+    reference: Option<(u32, u32)>,
+    /// Drift of the device clock, in parts per million, positive when fast.
+    ppm: i32,
+    learned: bool,
+}
+
+impl WallClock {
+    pub const fn new() -> Self {
+        Self { anchor: None, reference: None, ppm: 0, learned: false }
+    }
+
+    /// Sets the time, `at` being the time on the device clock when the client
+    /// gave the Unix time, and learns the drift since an earlier sync.
+    pub fn sync(&mut self, at: u32, unix: u32) {
+        match self.reference {
+            Some((reference_at, reference_unix)) => {
+                let real = unix.wrapping_sub(reference_unix) as i32 as i64;
+                let device = at.wrapping_sub(reference_at) as i32 as i64;
+                if real < 0 {
+                    // The time went backwards, the earlier one was wrong.
+                    self.reference = Some((at, unix));
+                } else if real >= MIN_SPAN as i64 {
+                    let measured = (device - real) * 1_000_000 / real;
+                    if measured.abs() <= MAX_PPM {
+                        let ppm = if self.learned {
+                            (self.ppm as i64 * (WEIGHT - 1) + measured) / WEIGHT
+                        } else {
+                            measured
+                        };
+                        self.ppm = ppm as i32;
+                        self.learned = true;
+                    }
+                    self.reference = Some((at, unix));
+                }
+            }
+            None => self.reference = Some((at, unix)),
+        }
+        self.anchor = Some((at, unix));
+    }
+
+    /// Drift of the device clock learned so far, in parts per million,
+    /// positive when running fast.
+    pub fn ppm(&self) -> i32 {
+        self.ppm
+    }
+
+    /// Returns the Unix time of a device clock time, before or after the latest
+    /// sync, or nothing before the first one.
+    pub fn unix(&self, at: u32) -> Option<u32> {
+        let (anchor_at, anchor_unix) = self.anchor?;
+        let device = at.wrapping_sub(anchor_at) as i32 as i64;
+        let real = device * 1_000_000 / (1_000_000 + self.ppm as i64);
+        Some((anchor_unix as i64 + real) as u32)
+    }
+}
+
+/// Writes the Unix times of the records of a history, oldest first, given the
+/// device clock times they were stored at. Times are 4 bytes each, little
+/// endian, and 0 when unknown; each write takes as many as fit.
+pub struct Times<'a, const SIZE: usize> {
+    stamps: &'a Historical<SIZE, u32>,
+    clock: WallClock,
+    pos: usize,
+}
+
+impl<'a, const SIZE: usize> Times<'a, SIZE> {
+    pub fn new(stamps: &'a Historical<SIZE, u32>, clock: WallClock) -> Self {
+        Self { stamps, clock, pos: 0 }
+    }
+
+    /// Sets the time, for the times written from then on, as when a client sets
+    /// it before reading them.
+    pub fn sync(&mut self, at: u32, unix: u32) {
+        self.clock.sync(at, unix);
+    }
+
+    /// Writes the next times, or nothing once all were written.
+    pub fn write(&mut self, out: &mut [u8]) -> Result<usize, serde::Error> {
+        let count = out.len() / 4;
+        let mut ser = serde::Serializer::new(out);
+        let mut n = 0;
+        for _ in 0..count {
+            let Some(at) = self.stamps.get(self.pos) else {
+                break;
+            };
+            n += ser.write_u32(self.clock.unix(*at).unwrap_or(0))?;
+            self.pos += 1;
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    const DAY: u32 = 86_400;
+    const UNIX: u32 = 1_700_000_000;
+
+    #[test]
+    fn test_unknown_before_sync() {
+        let sut = WallClock::new();
+
+        assert_eq!(None, sut.unix(0));
+    }
+
+    #[test]
+    fn test_unix_around_sync() {
+        let mut sut = WallClock::new();
+
+        sut.sync(500, UNIX);
+
+        assert_eq!(Some(UNIX + 60), sut.unix(560));
+        assert_eq!(Some(UNIX - 400), sut.unix(100));
+    }
+
+    #[test_case(DAY + 864, 10_000 ; "fast")]
+    #[test_case(DAY - 864, -10_000 ; "slow")]
+    #[test_case(DAY, 0 ; "exact")]
+    #[test_case(2 * DAY, 0 ; "wrong time")]
+    fn test_learn_drift(elapsed: u32, ppm: i32) {
+        let mut sut = WallClock::new();
+
+        sut.sync(0, UNIX);
+        sut.sync(elapsed, UNIX + DAY);
+
+        assert_eq!(ppm, sut.ppm());
+    }
+
+    #[test]
+    fn test_corrects_earlier_times() {
+        let mut sut = WallClock::new();
+        sut.sync(0, UNIX);
+        let stored = DAY / 2 + 432;
+        assert_eq!(Some(UNIX + stored), sut.unix(stored));
+
+        sut.sync(DAY + 864, UNIX + DAY);
+
+        assert_eq!(Some(UNIX + DAY / 2), sut.unix(stored));
+        assert_eq!(Some(UNIX + 2 * DAY), sut.unix(2 * (DAY + 864)));
+    }
+
+    #[test]
+    fn test_short_syncs_keep_reference() {
+        let mut sut = WallClock::new();
+        sut.sync(0, UNIX);
+
+        sut.sync(MIN_SPAN / 2, UNIX + MIN_SPAN / 2);
+        assert_eq!(0, sut.ppm());
+        sut.sync(DAY + 864, UNIX + DAY);
+
+        assert_eq!(10_000, sut.ppm());
+    }
+
+    #[test]
+    fn test_averages_drift() {
+        let mut sut = WallClock::new();
+        sut.sync(0, UNIX);
+        sut.sync(DAY + 864, UNIX + DAY);
+
+        sut.sync(2 * DAY + 864 + 1728, UNIX + 2 * DAY);
+
+        assert_eq!(12_500, sut.ppm());
+    }
+
+    #[test]
+    fn test_time_going_backwards() {
+        let mut sut = WallClock::new();
+        sut.sync(0, UNIX);
+
+        sut.sync(DAY, UNIX - DAY);
+        sut.sync(2 * DAY, UNIX);
+
+        assert_eq!(0, sut.ppm());
+        assert_eq!(Some(UNIX), sut.unix(2 * DAY));
+    }
+
+    #[test]
+    fn test_times() {
+        let mut stamps = Historical::<3, u32>::new();
+        [100, 200, 300, 400].iter().for_each(|at| stamps.store(*at));
+        let mut clock = WallClock::new();
+        clock.sync(400, UNIX);
+        let mut sut = Times::new(&stamps, clock);
+        let mut out = [0u8; 9];
+
+        assert_eq!(Ok(8), sut.write(&mut out));
+        assert_eq!((UNIX - 200).to_le_bytes(), out[..4]);
+        assert_eq!((UNIX - 100).to_le_bytes(), out[4..8]);
+        assert_eq!(Ok(4), sut.write(&mut out));
+        assert_eq!(UNIX.to_le_bytes(), out[..4]);
+        assert_eq!(Ok(0), sut.write(&mut out));
+    }
+
+    #[test]
+    fn test_unknown_times() {
+        let mut stamps = Historical::<3, u32>::new();
+        stamps.store(100);
+        stamps.store(200);
+        let mut sut = Times::new(&stamps, WallClock::new());
+        let mut out = [0xffu8; 4];
+
+        assert_eq!(Ok(4), sut.write(&mut out));
+        assert_eq!([0; 4], out);
+        sut.sync(300, UNIX);
+        assert_eq!(Ok(4), sut.write(&mut out));
+        assert_eq!((UNIX - 100).to_le_bytes(), out);
+    }
+}
@@ -0,0 +1,136 @@
+//! # Wake handling
+//!
+//! Decides what to do on a wake depending on what woke the device up. Timer
+//! wakes run whatever [`Job`]s the [`Scheduler`] has due, while a button press
+//! skips them all, leaving their due times untouched, and asks for an extended
+//! advertising window so a client has time to connect and sync.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use humidity_core::{
+//!     schedule::{Clock, Job, Scheduler, Task},
+//!     wake::{self, Cause, Windows},
+//! };
+//! struct Uptime(u32);
+//! impl Clock for Uptime {
+//!     fn now(&self) -> u32 {
+//!         self.0
+//!     }
+//! }
+//! const TASKS: [Task; 1] = [(Job::Sample, 300)];
+//! const WINDOWS: Windows = Windows { regular_ms: 5_000, extended_ms: 60_000 };
+//! let mut scheduler = Scheduler::new();
+//! let plan = wake::plan(Cause::Button, &mut scheduler, &TASKS, &Uptime(0), &WINDOWS);
+//! assert!(plan.jobs.is_empty());
+//! assert_eq!(60_000, plan.advertise_ms);
+//! ```
+//!
+//! [`Job`]: crate::schedule::Job
+
+use crate::schedule::{Clock, Jobs, Scheduler, Task};
+
+/// What woke the device up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cause {
+    /// Power on, reset, or any cause the device does not expect.
+    Reset,
+    /// The wake-up timer expired.
+    Timer,
+    /// The button was pressed.
+    Button,
+}
+
+/// How long to advertise, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Windows {
+    /// Window on regular wakes.
+    pub regular_ms: u32,
+    /// Window after a button press.
+    pub extended_ms: u32,
+}
+
+/// What to do on this wake.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plan {
+    /// Jobs to run.
+    pub jobs: Jobs,
+    /// Whether the user asked to sync by pressing the button.
+    pub button: bool,
+    /// How long to advertise for, when advertising.
+    pub advertise_ms: u32,
+}
+
+/// Plans the wake, taking the due jobs from the scheduler unless woken up by
+/// the button.
+pub fn plan<const N: usize>(
+    cause: Cause,
+    scheduler: &mut Scheduler<N>,
+    tasks: &[Task; N],
+    clock: &impl Clock,
+    windows: &Windows,
+) -> Plan {
+    match cause {
+        Cause::Button => {
+            Plan { jobs: Jobs::empty(), button: true, advertise_ms: windows.extended_ms }
+        }
+        Cause::Reset | Cause::Timer => Plan {
+            jobs: scheduler.run_due(tasks, clock),
+            button: false,
+            advertise_ms: windows.regular_ms,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::schedule::Job;
+    use test_case::test_case;
+
+    struct FakeClock(u32);
+
+    impl Clock for FakeClock {
+        fn now(&self) -> u32 {
+            self.0
+        }
+    }
+
+    const TASKS: [Task; 2] = [(Job::Sample, 300), (Job::BatteryCheck, 86_400)];
+    const WINDOWS: Windows = Windows { regular_ms: 5_000, extended_ms: 60_000 };
+
+    #[test_case(Cause::Reset)]
+    #[test_case(Cause::Timer)]
+    fn test_runs_due_jobs(cause: Cause) {
+        let mut scheduler = Scheduler::new();
+
+        let plan = plan(cause, &mut scheduler, &TASKS, &FakeClock(0), &WINDOWS);
+        assert!(plan.jobs.contains(Job::Sample));
+        assert!(plan.jobs.contains(Job::BatteryCheck));
+        assert!(!plan.button);
+        assert_eq!(5_000, plan.advertise_ms);
+    }
+
+    #[test]
+    fn test_button_skips_jobs() {
+        let mut scheduler = Scheduler::new();
+
+        let plan = plan(Cause::Button, &mut scheduler, &TASKS, &FakeClock(0), &WINDOWS);
+        assert!(plan.jobs.is_empty());
+        assert!(plan.button);
+        assert_eq!(60_000, plan.advertise_ms);
+    }
+
+    #[test]
+    fn test_button_keeps_due_jobs() {
+        let mut scheduler = Scheduler::new();
+        plan(Cause::Timer, &mut scheduler, &TASKS, &FakeClock(0), &WINDOWS);
+
+        plan(Cause::Button, &mut scheduler, &TASKS, &FakeClock(400), &WINDOWS);
+        assert_eq!(0, scheduler.next_wake(&FakeClock(400)));
+
+        let plan = plan(Cause::Timer, &mut scheduler, &TASKS, &FakeClock(400), &WINDOWS);
+        assert!(plan.jobs.contains(Job::Sample));
+        assert!(!plan.jobs.contains(Job::BatteryCheck));
+    }
+}
@@ -2,37 +2,126 @@ use bleps::{
     ad_structure::{
         create_advertising_data, AdStructure, BR_EDR_NOT_SUPPORTED, LE_GENERAL_DISCOVERABLE,
     },
-    att::Uuid,
     attribute::Attribute,
-    attribute_server::{AttributeServer, WorkResult},
+    attribute_server::{AttributeServer, AttributeServerError, NotificationData, WorkResult},
     event::EventType,
     no_rng::NoRng,
-    Ble, PollResult,
+    Ble, Error, PollResult,
 };
+#[cfg(feature = "bthome")]
+use embedded_storage::{ReadStorage, Storage};
 use esp_hal::delay::Delay;
+#[cfg(feature = "bthome")]
+use esp_hal::efuse::Efuse;
 use esp_println::println;
-use humidity_core::shared;
-
-pub fn start(ble: &mut Ble) {
-    println!("{:?}", ble.init());
-    println!("{:?}", ble.cmd_set_le_advertising_parameters());
-    println!(
-        "{:?}",
-        ble.cmd_set_le_advertising_data(
-            create_advertising_data(&[
-                AdStructure::Flags(LE_GENERAL_DISCOVERABLE | BR_EDR_NOT_SUPPORTED),
-                AdStructure::ServiceUuids16(&[Uuid::Uuid16(0x1809)]),
-                AdStructure::CompleteLocalName(shared::BLE_DEVICE_NAME),
-            ])
-            .unwrap()
-        )
+#[cfg(feature = "bthome")]
+use esp_storage::{FlashStorage, FlashStorageError};
+#[cfg(feature = "bthome")]
+use humidity_core::bthome::{self, Counter, Encryption, Measurement};
+use humidity_core::{lifecycle::Content, sensors::Hygrometer, shared};
+
+/// Bind key encrypting BTHome advertisements, from 32 hexadecimal digits set
+/// at build time. Left unencrypted when unset.
+#[cfg(feature = "bthome")]
+const BIND_KEY: Option<[u8; bthome::KEY_LEN]> = match option_env!("BTHOME_BIND_KEY") {
+    Some(hex) => match bthome::parse_key(hex) {
+        Some(key) => Some(key),
+        None => panic!("BTHOME_BIND_KEY must be 32 hexadecimal digits"),
+    },
+    None => None,
+};
+
+/// Where the next free block of encryption counters is kept: the start of the
+/// nvs partition of the default partition table, which nothing else uses.
+#[cfg(feature = "bthome")]
+const COUNTER_OFFSET: u32 = 0x9000;
+
+/// Starts advertising, with the encryption counter of the readings when
+/// encrypted.
+pub fn start(
+    ble: &mut Ble,
+    content: &Content<'_, Hygrometer>,
+    #[cfg(feature = "bthome")] counter: Option<u32>,
+) -> Result<(), Error> {
+    println!("{:?}", ble.init()?);
+    println!("{:?}", ble.cmd_set_le_advertising_parameters()?);
+    // Services are left out, the readings and the name already fill most of
+    // the 31 bytes of advertising data.
+    #[cfg(not(feature = "bthome"))]
+    let payload = content.broadcast.encode();
+    #[cfg(not(feature = "bthome"))]
+    let readings = || AdStructure::ManufacturerSpecificData {
+        company_identifier: shared::broadcast::COMPANY_ID,
+        payload: &payload,
+    };
+    #[cfg(feature = "bthome")]
+    let payload = bthome_payload(content, counter);
+    #[cfg(feature = "bthome")]
+    let readings =
+        || AdStructure::ServiceData16 { uuid: bthome::SERVICE_UUID, data: payload.as_bytes() };
+
+    let flags = || AdStructure::Flags(LE_GENERAL_DISCOVERABLE | BR_EDR_NOT_SUPPORTED);
+    // The name is dropped when encrypted readings leave no room for it.
+    let data = create_advertising_data(&[
+        flags(),
+        readings(),
+        AdStructure::CompleteLocalName(shared::BLE_DEVICE_NAME),
+    ])
+    .or_else(|_| create_advertising_data(&[flags(), readings()]))
+    .unwrap();
+    println!("{:?}", ble.cmd_set_le_advertising_data(data)?);
+    println!("{:?}", ble.cmd_set_le_advertise_enable(true)?);
+    Ok(())
+}
+
+/// Returns the counter to encrypt the next advertisement with, if encrypting.
+#[cfg(feature = "bthome")]
+pub fn next_counter(counter: &mut Counter) -> Result<Option<u32>, FlashStorageError> {
+    BIND_KEY.map(|_| counter.next(reserve_counters)).transpose()
+}
+
+/// Reads the next free block of encryption counters from flash, and stores
+/// the one after it.
+#[cfg(feature = "bthome")]
+fn reserve_counters() -> Result<u32, FlashStorageError> {
+    let mut flash = FlashStorage::new();
+    let mut bytes = [0u8; 4];
+    flash.read(COUNTER_OFFSET, &mut bytes)?;
+    // Erased flash reads as all ones.
+    let block = match u32::from_le_bytes(bytes) {
+        u32::MAX => 0,
+        block => block,
+    };
+    flash.write(COUNTER_OFFSET, &(block + 1).to_le_bytes())?;
+    Ok(block)
+}
+
+/// Encodes the latest readings as BTHome service data, counting packets with
+/// the advertisements, and encrypting them with the given counter.
+#[cfg(feature = "bthome")]
+fn bthome_payload(content: &Content<'_, Hygrometer>, counter: Option<u32>) -> bthome::Payload {
+    let measurement = Measurement::new(
+        content.last_sample.as_ref(),
+        content.broadcast.battery,
+        content.sequence as u8,
     );
-    println!("{:?}", ble.cmd_set_le_advertise_enable(true));
+    let encryption =
+        BIND_KEY.zip(counter).map(|(key, counter)| Encryption { key, mac: ble_address(), counter });
+    bthome::encode(&measurement, encryption.as_ref())
+}
+
+/// The public address the controller advertises with, derived from the base
+/// MAC address as ESP-IDF does: Bluetooth comes after the two Wi-Fi ones.
+#[cfg(feature = "bthome")]
+fn ble_address() -> [u8; 6] {
+    let mut mac = Efuse::get_mac_address();
+    mac[5] = mac[5].wrapping_add(2);
+    mac
 }
 
-pub fn wait_for_connection(ble: &mut Ble, delay: &mut Delay) -> bool {
+pub fn wait_for_connection(ble: &mut Ble, delay: &mut Delay, window_ms: u32) -> bool {
     let mut connected = false;
-    for _ in 0..50 {
+    for _ in 0..window_ms / 100 {
         if let Some(result) = ble.poll() {
             match result {
                 PollResult::Event(evt) => {
@@ -51,11 +140,15 @@ pub fn wait_for_connection(ble: &mut Ble, delay: &mut Delay) -> bool {
     connected
 }
 
+/// Serves the attributes until the client disconnects, sending notifications
+/// as they come.
 pub fn work_until_disconnect<'a>(
     ble: &'a mut Ble<'a>,
     gatt_attributes: &'a mut [Attribute<'a>],
     rng: &'a mut NoRng,
-) {
+    notification: &mut impl FnMut() -> Option<NotificationData>,
+) -> Result<(), AttributeServerError> {
     let mut srv = AttributeServer::new(ble, gatt_attributes, rng);
-    while let WorkResult::DidWork = srv.do_work().unwrap() {}
+    while let WorkResult::DidWork = srv.do_work_with_notification(notification())? {}
+    Ok(())
 }
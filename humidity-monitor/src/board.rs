@@ -0,0 +1,514 @@
+use bleps::{attribute_server::NotificationData, gatt, no_rng::NoRng, Ble, HciConnector};
+use core::cell::{Cell, RefCell};
+use esp_hal::{
+    analog::adc::{Adc, AdcPin},
+    clock::Clocks,
+    delay::Delay,
+    gpio::Output,
+    i2c::I2C,
+    peripherals::{ADC1, I2C0},
+    rng::Rng,
+    rtc_cntl::{get_wakeup_cause, Rtc, SleepSource},
+};
+use esp_wifi::{self, ble::controller::BleConnector, EspWifiInitFor};
+use fugit::MillisDurationU32;
+#[cfg(feature = "bthome")]
+use humidity_core::bthome::Counter;
+use humidity_core::{
+    battery::{Battery, Chemistry, Divider},
+    ess::{self, EsMeasurement},
+    lifecycle::{self, Content, Event, Live, Served},
+    pattern::{self, Pattern},
+    rpc,
+    sample::{self, Record, Summary},
+    schedule::Clock,
+    sensors::{sht3x, Hygrometer},
+    serde,
+    settings::{DeviceConfig, DEVICE_CONFIG_LEN},
+    shared::protocol,
+    time::cts,
+    wake::Cause,
+};
+
+use crate::{
+    blessed,
+    boards::{self, Radio},
+    storage,
+};
+
+const HYGROMETER_WARMUP: u32 = MillisDurationU32::millis(1000).to_millis();
+const BATTERY_DIVIDER: Divider = Divider::new(100_000, 100_000);
+const BATTERY_CHEMISTRY: Chemistry = Chemistry::LiIon;
+/// Time the readings of a sampling are taken over, after the warm-up.
+const MEASUREMENT_PERIOD_S: u32 = 1;
+
+// The `gatt!` macro only takes literal UUIDs, which must match the layout
+// shared with the clients.
+const _: () = {
+    assert!(protocol::HUMIDITY_SERVICE.uuid.matches("937312e0-2354-11eb-9f10-fbc30a62cf00"));
+    assert!(protocol::HUMIDITY.uuid.matches("987312e0-2354-11eb-9f10-fbc30a62cf50"));
+    assert!(protocol::HISTORICAL.uuid.matches("987312e0-2354-11eb-9f10-fbc30a62cf60"));
+    assert!(protocol::DEVICE_CONFIG.uuid.matches("987312e0-2354-11eb-9f10-fbc30a62cf70"));
+    assert!(protocol::RPC_REQUEST.uuid.matches("987312e0-2354-11eb-9f10-fbc30a62cf80"));
+    assert!(protocol::RPC_RESPONSE.uuid.matches("987312e0-2354-11eb-9f10-fbc30a62cf90"));
+    assert!(protocol::PROTOCOL_VERSION.uuid.matches("987312e0-2354-11eb-9f10-fbc30a62cfa0"));
+    assert!(protocol::HISTORY_TIMES.uuid.matches("987312e0-2354-11eb-9f10-fbc30a62cfb0"));
+    assert!(protocol::BATTERY_SERVICE.uuid.matches("180f"));
+    assert!(protocol::BATTERY_LEVEL.uuid.matches("2a19"));
+    assert!(protocol::ESS_SERVICE.uuid.matches("181a"));
+    assert!(protocol::ESS_HUMIDITY.uuid.matches("2a6f"));
+    assert!(protocol::ES_MEASUREMENT.matches("290c"));
+    assert!(protocol::CTS_SERVICE.uuid.matches("1805"));
+    assert!(protocol::CURRENT_TIME.uuid.matches("2a2b"));
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    Adc,
+    Radio,
+    Flash,
+}
+
+/// The selected board, with the sensor, the battery, the alarm and the pump.
+pub struct Board<'a, 'd> {
+    pub clocks: &'a Clocks<'d>,
+    pub rtc: &'a Rtc<'d>,
+    pub delay: &'a mut Delay,
+    pub hygrometer_enable: &'a mut Output<'d, boards::HygrometerEnable>,
+    pub alarm: &'a mut Output<'d, boards::Alarm>,
+    pub pump: &'a mut Output<'d, boards::Pump>,
+    pub adc1: &'a mut Adc<'d, ADC1>,
+    pub hygrometer_adc1_pin: &'a mut AdcPin<boards::HygrometerAdc, ADC1, boards::AdcCalibration>,
+    pub battery_adc1_pin: &'a mut AdcPin<boards::BatteryAdc, ADC1, boards::AdcCalibration>,
+    /// Bus of the SHT3x next to the pot, whose temperature stands in for the
+    /// one of the soil.
+    pub climate: &'a mut I2C<'d, I2C0>,
+    pub radio: Option<Radio>,
+    /// Settings of this wake. Ones written by a client apply from the next.
+    pub settings: &'a mut DeviceConfig,
+    /// Encryption counters of the advertisements.
+    #[cfg(feature = "bthome")]
+    pub counter: &'a mut Counter,
+}
+
+/// Seconds elapsed on the RTC, which keeps counting during deep sleep.
+impl Clock for Board<'_, '_> {
+    fn now(&self) -> u32 {
+        (self.rtc.get_time_ms() / 1000) as u32
+    }
+}
+
+impl lifecycle::Board for Board<'_, '_> {
+    type Sensor = Hygrometer;
+    type Error = Error;
+
+    fn wake_cause(&mut self) -> Cause {
+        match get_wakeup_cause() {
+            SleepSource::Timer => Cause::Timer,
+            SleepSource::Ext0 | SleepSource::Ext1 | SleepSource::Gpio => Cause::Button,
+            _ => Cause::Reset,
+        }
+    }
+
+    fn beep(&mut self, pattern: Pattern) {
+        let (alarm, delay) = (&mut *self.alarm, &mut *self.delay);
+        pattern::play(
+            pattern,
+            &mut |on| if on { alarm.set_high() } else { alarm.set_low() },
+            &mut |ms| delay.delay_millis(ms),
+        );
+    }
+
+    fn read_battery(&mut self) -> Result<Battery, Error> {
+        let millivolts = match self.adc1.read_oneshot(self.battery_adc1_pin) {
+            Ok(sample) => sample,
+            Err(err) => {
+                log::error!("adc failure: {err:?}");
+                return Err(Error::Adc);
+            }
+        };
+        Ok(Battery {
+            millivolts: BATTERY_DIVIDER.millivolts(millivolts),
+            chemistry: BATTERY_CHEMISTRY,
+        })
+    }
+
+    fn sample(&mut self) -> Result<Summary<Hygrometer>, Error> {
+        sample_hygrometer(
+            self.adc1,
+            self.hygrometer_adc1_pin,
+            self.hygrometer_enable,
+            self.delay,
+            self.climate,
+            *self.settings,
+        )
+    }
+
+    fn water(&mut self, pulse_ms: u32) {
+        self.pump.set_high();
+        self.delay.delay_millis(pulse_ms);
+        self.pump.set_low();
+    }
+
+    fn serve(
+        &mut self,
+        window_ms: u32,
+        content: &Content<'_, Hygrometer>,
+    ) -> Result<Served<Hygrometer>, Error> {
+        let radio = self.radio.take().ok_or(Error::Radio)?;
+        let timer = boards::radio_timer(radio.timer, self.clocks);
+        let wifi_init = match esp_wifi::initialize(
+            EspWifiInitFor::Ble,
+            timer,
+            Rng::new(radio.rng),
+            radio.radio_clk,
+            self.clocks,
+        ) {
+            Ok(init) => init,
+            Err(err) => {
+                log::error!("wifi initialization failure: {err:?}");
+                return Err(Error::Radio);
+            }
+        };
+
+        let mut bluetooth = radio.bt;
+        let connector = BleConnector::new(&wifi_init, &mut bluetooth);
+        let hci = HciConnector::new(connector, esp_wifi::current_millis);
+        let ble = &mut Ble::new(&hci);
+
+        #[cfg(not(feature = "bthome"))]
+        blessed::start(ble, content).map_err(|_| Error::Radio)?;
+        #[cfg(feature = "bthome")]
+        {
+            let counter = blessed::next_counter(self.counter).map_err(|err| {
+                log::error!("cannot reserve encryption counters: {err:?}");
+                Error::Flash
+            })?;
+            blessed::start(ble, content, counter).map_err(|_| Error::Radio)?;
+        }
+        if !blessed::wait_for_connection(ble, self.delay, window_ms) {
+            return Ok(Served::default());
+        }
+        lifecycle::Board::beep(self, Pattern::Connected);
+
+        let synced = Cell::new(false);
+        let mut hsync = content.history.sync();
+        let mut read_historical = |_offset: usize, data: &mut [u8]| match hsync.write(data) {
+            Ok(n) => {
+                synced.set(n == 0);
+                n
+            }
+            Err(err) => {
+                log::error!("cannot serialize historical data: {err:?}");
+                0
+            }
+        };
+        let last_sample = content.last_sample;
+        let mut read_last_sample = |_offset: usize, data: &mut [u8]| {
+            let Some(summary) = &last_sample else {
+                return 0;
+            };
+            match serde::serialize(summary, data) {
+                Ok(n) => n,
+                Err(err) => {
+                    log::error!("cannot serialize last sample: {err:?}");
+                    0
+                }
+            }
+        };
+        let mut read_ess_humidity = |_offset: usize, data: &mut [u8]| {
+            let Some(summary) = &last_sample else {
+                return 0;
+            };
+            let value = ess::humidity(summary);
+            data[..value.len()].copy_from_slice(&value);
+            value.len()
+        };
+        let interval = self.settings.interval;
+        let mut read_es_measurement = |_offset: usize, data: &mut [u8]| {
+            let Some(summary) = &last_sample else {
+                return 0;
+            };
+            let descriptor = EsMeasurement::new(summary, MEASUREMENT_PERIOD_S, interval).encode();
+            data[..descriptor.len()].copy_from_slice(&descriptor);
+            descriptor.len()
+        };
+        let settings = *self.settings;
+        let mut read_device_config = |_offset: usize, data: &mut [u8]| {
+            settings.encode(data).unwrap_or_else(|err| {
+                log::error!("cannot serialize settings: {err:?}");
+                0
+            })
+        };
+        // Invalid settings are logged and dropped, as writes cannot fail.
+        let written = Cell::new(None);
+        let mut write_device_config = |_offset: usize, data: &[u8]| match DeviceConfig::decode(
+            &data[..data.len().min(DEVICE_CONFIG_LEN)],
+        ) {
+            Ok(settings) => written.set(Some(settings)),
+            Err(err) => log::warn!("rejected settings: {err:?}"),
+        };
+        // Requests are answered right away, their responses notified from the
+        // attribute server loop.
+        let rtc = self.rtc;
+        let mut server = rpc::Server::new(content, self.now());
+        server.set_model(boards::MODEL);
+        let rpc = RefCell::new(server);
+        let mut write_rpc_request = |_offset: usize, data: &[u8]| {
+            let mut rpc = rpc.borrow_mut();
+            rpc.set_now((rtc.get_time_ms() / 1000) as u32);
+            rpc.request(data);
+        };
+        let mut read_rpc_response = |_offset: usize, _data: &mut [u8]| 0;
+        let mut read_protocol_version = |_offset: usize, data: &mut [u8]| {
+            data[0] = protocol::VERSION;
+            1
+        };
+        // The time written applies to the history read from then on already,
+        // and to the retained clock once disconnected.
+        let clock = Cell::new(content.clock);
+        let time = Cell::new(None);
+        let times = RefCell::new(content.times());
+        let mut read_history_times =
+            |_offset: usize, data: &mut [u8]| match times.borrow_mut().write(data) {
+                Ok(n) => n,
+                Err(err) => {
+                    log::error!("cannot serialize history times: {err:?}");
+                    0
+                }
+            };
+        let mut read_current_time = |_offset: usize, data: &mut [u8]| {
+            let now = (rtc.get_time_ms() / 1000) as u32;
+            let Some(unix) = clock.get().unix(now) else {
+                return 0;
+            };
+            let value = cts::encode(unix);
+            data[..value.len()].copy_from_slice(&value);
+            value.len()
+        };
+        let mut write_current_time = |_offset: usize, data: &[u8]| match cts::decode(data) {
+            Ok(unix) => {
+                let now = (rtc.get_time_ms() / 1000) as u32;
+                let mut synced = clock.get();
+                synced.sync(now, unix);
+                clock.set(synced);
+                times.borrow_mut().sync(now, unix);
+                time.set(Some((now, unix)));
+            }
+            Err(err) => log::warn!("rejected time: {err:?}"),
+        };
+        let battery = content.battery;
+        let mut read_battery_level = |_offset: usize, data: &mut [u8]| match &battery {
+            Some(battery) => {
+                data[0] = battery.percentage();
+                1
+            }
+            None => 0,
+        };
+
+        gatt!([
+            service {
+                uuid: "937312e0-2354-11eb-9f10-fbc30a62cf00",
+                characteristics: [
+                    characteristic {
+                        name: "humidity",
+                        uuid: "987312e0-2354-11eb-9f10-fbc30a62cf50",
+                        notify: true,
+                        read: read_last_sample,
+                    },
+                    characteristic {
+                        name: "historical",
+                        uuid: "987312e0-2354-11eb-9f10-fbc30a62cf60",
+                        notify: true,
+                        read: read_historical,
+                    },
+                    characteristic {
+                        name: "device_config",
+                        uuid: "987312e0-2354-11eb-9f10-fbc30a62cf70",
+                        read: read_device_config,
+                        write: write_device_config,
+                    },
+                    characteristic {
+                        name: "rpc_request",
+                        uuid: "987312e0-2354-11eb-9f10-fbc30a62cf80",
+                        write: write_rpc_request,
+                    },
+                    characteristic {
+                        name: "rpc_response",
+                        uuid: "987312e0-2354-11eb-9f10-fbc30a62cf90",
+                        notify: true,
+                        read: read_rpc_response,
+                    },
+                    characteristic {
+                        name: "protocol_version",
+                        uuid: "987312e0-2354-11eb-9f10-fbc30a62cfa0",
+                        read: read_protocol_version,
+                    },
+                    characteristic {
+                        name: "history_times",
+                        uuid: "987312e0-2354-11eb-9f10-fbc30a62cfb0",
+                        read: read_history_times,
+                    },
+                ]
+            },
+            service {
+                uuid: "180f",
+                characteristics: [characteristic {
+                    name: "battery_level",
+                    uuid: "2a19",
+                    read: read_battery_level,
+                },]
+            },
+            service {
+                uuid: "181a",
+                characteristics: [characteristic {
+                    name: "ess_humidity",
+                    uuid: "2a6f",
+                    read: read_ess_humidity,
+                    descriptors: [descriptor { uuid: "290c", read: read_es_measurement },],
+                },]
+            },
+            service {
+                uuid: "1805",
+                characteristics: [characteristic {
+                    name: "current_time",
+                    uuid: "2a2b",
+                    read: read_current_time,
+                    write: write_current_time,
+                },]
+            },
+        ]);
+
+        // The soil is sampled again whenever the next sampling falls due while
+        // connected, then at the interval last picked, and the summary notified
+        // and then the record appended to the history. Records are only stored
+        // once disconnected, so sampling stops once no more can be kept.
+        let mut due = self.now().wrapping_add(content.next_sample);
+        let interval = content.interval;
+        let (adc1, pin) = (&mut *self.adc1, &mut *self.hygrometer_adc1_pin);
+        let (enable, delay) = (&mut *self.hygrometer_enable, &mut *self.delay);
+        let climate = &mut *self.climate;
+        let mut live = Live::new();
+        let mut pending = None;
+        let mut notification = || {
+            if let Some(frame) = rpc.borrow_mut().response() {
+                return Some(NotificationData::new(rpc_response_handle, frame.as_bytes()));
+            }
+            let mut data = [0u8; 32];
+            if let Some(record) = pending.take() {
+                let n = serde::serialize(&record, &mut data).ok()?;
+                return Some(NotificationData::new(historical_handle, &data[..n]));
+            }
+            // Compares times allowing the clock to wrap around.
+            let now = (rtc.get_time_ms() / 1000) as u32;
+            if live.is_full() || (1..u32::MAX / 2).contains(&due.wrapping_sub(now)) {
+                return None;
+            }
+            due = now.wrapping_add(interval);
+            let summary = match sample_hygrometer(adc1, pin, enable, delay, climate, settings) {
+                Ok(summary) => summary,
+                Err(err) => {
+                    log::error!("cannot sample while connected: {err:?}");
+                    return None;
+                }
+            };
+            let record = Record { summary, battery, fault: None };
+            live.push(now, record).ok()?;
+            if live.is_full() {
+                log::info!("no more samples until the client disconnects");
+            }
+            pending = Some(record);
+            let n = serde::serialize(&summary, &mut data).ok()?;
+            Some(NotificationData::new(humidity_handle, &data[..n]))
+        };
+        let mut rng = NoRng;
+        blessed::work_until_disconnect(ble, &mut gatt_attributes, &mut rng, &mut notification)
+            .map_err(|_| Error::Radio)?;
+        if let Some(settings) = written.get() {
+            log::info!("settings updated: {settings:?}");
+            if let Err(err) = storage::store_settings(&settings) {
+                log::error!("cannot store the settings, kept until a power loss: {err:?}");
+            }
+            *self.settings = settings;
+        }
+        let commands = rpc.borrow().commands();
+        if let Some((at, unix)) = time.get() {
+            log::info!("time set to {unix} at {at}");
+        }
+        let commands = rpc::Commands { time: time.get().or(commands.time), ..commands };
+        Ok(Served { connected: true, synced: synced.get(), commands, live })
+    }
+
+    fn report(&mut self, event: Event<Hygrometer, Error>) {
+        match event {
+            Event::Woken(cause, jobs) => log::info!("woken up by {cause:?}, due jobs: {jobs:?}"),
+            Event::Irrigation(Ok(decision)) => log::info!("irrigation: {decision:?}"),
+            Event::Irrigation(Err(fault)) => log::warn!("irrigation halted: {fault:?}"),
+            Event::Advertising(reason) => log::info!("advertising: {reason:?}"),
+            Event::Sleeping(secs) => log::info!("sleeping for {secs}s"),
+            Event::BatteryError(err) => log::error!("cannot read the battery: {err:?}"),
+            Event::SensorError(err) => log::error!("cannot sample the sensor: {err:?}"),
+            Event::RadioError(err) => log::error!("cannot serve clients: {err:?}"),
+            event => log::debug!("{event:?}"),
+        }
+    }
+}
+
+/// Powers the hygrometer up, samples it, and powers it down. The readings are
+/// compensated for the temperature, when the air sensor answers.
+fn sample_hygrometer(
+    adc1: &mut Adc<'_, ADC1>,
+    pin: &mut AdcPin<boards::HygrometerAdc, ADC1, boards::AdcCalibration>,
+    enable: &mut Output<'_, boards::HygrometerEnable>,
+    delay: &mut Delay,
+    climate: &mut I2C<'_, I2C0>,
+    settings: DeviceConfig,
+) -> Result<Summary<Hygrometer>, Error> {
+    let temperature = read_temperature(climate, delay);
+    let failed = Cell::new(false);
+    let mut toggle = || enable.toggle();
+    let mut warmup = || delay.delay_millis(HYGROMETER_WARMUP);
+    let mut read_adc = || match adc1.read_oneshot(pin) {
+        Ok(sample) => settings.correct(sample),
+        Err(err) => {
+            log::error!("adc failure: {err:?}");
+            failed.set(true);
+            0
+        }
+    };
+    let summary = sample::perform_sampling(
+        settings.samples,
+        &mut toggle,
+        &mut warmup,
+        &mut read_adc,
+        settings.sensor,
+        temperature,
+    );
+    if failed.get() {
+        Err(Error::Adc)
+    } else {
+        Ok(summary)
+    }
+}
+
+/// Measures the temperature with the SHT3x, in centi-degrees Celsius, or
+/// nothing when the sensor is missing or its reading garbled.
+fn read_temperature(climate: &mut I2C<'_, I2C0>, delay: &mut Delay) -> Option<i16> {
+    if let Err(err) = climate.write(sht3x::ADDRESS, &sht3x::MEASURE_HIGH_REPEATABILITY) {
+        log::warn!("no air sensor, sampling without compensation: {err:?}");
+        return None;
+    }
+    delay.delay_millis(sht3x::MEASURE_DURATION_MS);
+    let mut raw = [0u8; 6];
+    if let Err(err) = climate.read(sht3x::ADDRESS, &mut raw) {
+        log::warn!("cannot read the air sensor: {err:?}");
+        return None;
+    }
+    match sht3x::decode(&raw) {
+        Ok(climate) => Some(climate.temperature),
+        Err(err) => {
+            log::warn!("garbled air sensor reading: {err:?}");
+            None
+        }
+    }
+}
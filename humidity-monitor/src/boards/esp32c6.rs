@@ -0,0 +1,85 @@
+//! ESP32-C6 board.
+
+use core::time::Duration;
+use esp_hal::{
+    analog::adc::AdcCalCurve,
+    clock::Clocks,
+    delay::Delay,
+    gpio::{self, DriveStrength, GpioPin, RtcPinWithResistors},
+    peripherals::{Peripherals, ADC1, SYSTIMER},
+    rtc_cntl::{
+        sleep::{Ext1WakeupSource, TimerWakeupSource, WakeupLevel},
+        Rtc,
+    },
+    timer::systimer::SystemTimer,
+};
+use esp_wifi::EspWifiTimerSource;
+use fugit::MicrosDurationU64;
+use humidity_core::power::Model;
+
+use super::{Parts, Pins, Radio};
+
+pub type HygrometerEnable = GpioPin<14>;
+pub type Alarm = GpioPin<15>;
+pub type Pump = GpioPin<18>;
+pub type Button = GpioPin<4>;
+pub type HygrometerAdc = GpioPin<2>;
+pub type BatteryAdc = GpioPin<3>;
+pub type ClimateSda = GpioPin<6>;
+pub type ClimateScl = GpioPin<7>;
+pub type AdcCalibration = AdcCalCurve<ADC1>;
+pub type RadioTimer = SYSTIMER;
+
+/// Drive strength of the outputs powering the sensor and the alarm.
+pub const DRIVE_STRENGTH: Option<DriveStrength> = None;
+pub const HYGROMETER_SAMPLES: u8 = 64;
+pub const BASE_INTERVAL: u32 = MicrosDurationU64::minutes(5).to_secs() as u32;
+/// Chip reported to the clients, for their power estimates.
+pub const MODEL: Model = Model::Esp32C6;
+
+pub fn split(peripherals: Peripherals) -> Parts {
+    Parts {
+        system: peripherals.SYSTEM,
+        gpio: peripherals.GPIO,
+        io_mux: peripherals.IO_MUX,
+        lpwr: peripherals.LPWR,
+        adc1: peripherals.ADC1,
+        i2c0: peripherals.I2C0,
+        radio: Radio {
+            timer: peripherals.SYSTIMER,
+            rng: peripherals.RNG,
+            radio_clk: peripherals.RADIO_CLK,
+            bt: peripherals.BT,
+        },
+    }
+}
+
+pub fn pins(pins: gpio::Pins) -> Pins {
+    Pins {
+        hygrometer_enable: pins.gpio14,
+        alarm: pins.gpio15,
+        pump: pins.gpio18,
+        button: pins.gpio4,
+        hygrometer_adc: pins.gpio2,
+        battery_adc: pins.gpio3,
+        climate_sda: pins.gpio6,
+        climate_scl: pins.gpio7,
+    }
+}
+
+pub fn radio_timer<'d>(timer: RadioTimer, _clocks: &'d Clocks<'d>) -> impl EspWifiTimerSource + 'd {
+    SystemTimer::new(timer).alarm0
+}
+
+/// Sleeps until the given time, in seconds, elapses or the button is pressed.
+/// Pressing the button pulls the pin low, against the pull-up of the RTC
+/// domain, which stays on while asleep: no external resistor is needed.
+pub fn sleep_deep(rtc: &mut Rtc, delay: &mut Delay, button: &mut Button, secs: u32) -> ! {
+    button.rtcio_pulldown(false);
+    button.rtcio_pullup(true);
+    let timer = TimerWakeupSource::new(Duration::from_secs(secs as u64));
+    let mut wakeup_pins: [(&mut dyn RtcPinWithResistors, WakeupLevel); 1] =
+        [(button, WakeupLevel::Low)];
+    let ext1 = Ext1WakeupSource::new(&mut wakeup_pins);
+    rtc.sleep_deep(&[&timer, &ext1], delay);
+}
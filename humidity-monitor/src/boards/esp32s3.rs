@@ -0,0 +1,83 @@
+//! ESP32-S3 board.
+
+use core::time::Duration;
+use esp_hal::{
+    analog::adc::AdcCalLine,
+    clock::Clocks,
+    delay::Delay,
+    gpio::{self, DriveStrength, GpioPin, RtcPinWithResistors},
+    peripherals::{Peripherals, ADC1, TIMG1},
+    rtc_cntl::{
+        sleep::{Ext0WakeupSource, TimerWakeupSource, WakeupLevel},
+        Rtc,
+    },
+    timer::timg::TimerGroup,
+};
+use esp_wifi::EspWifiTimerSource;
+use fugit::MicrosDurationU64;
+use humidity_core::power::Model;
+
+use super::{Parts, Pins, Radio};
+
+pub type HygrometerEnable = GpioPin<4>;
+pub type Alarm = GpioPin<6>;
+pub type Pump = GpioPin<15>;
+pub type Button = GpioPin<0>;
+pub type HygrometerAdc = GpioPin<5>;
+pub type BatteryAdc = GpioPin<7>;
+pub type ClimateSda = GpioPin<8>;
+pub type ClimateScl = GpioPin<9>;
+pub type AdcCalibration = AdcCalLine<ADC1>;
+pub type RadioTimer = TIMG1;
+
+/// Drive strength of the outputs powering the sensor and the alarm.
+pub const DRIVE_STRENGTH: Option<DriveStrength> = Some(DriveStrength::I5mA);
+pub const HYGROMETER_SAMPLES: u8 = u8::MAX;
+pub const BASE_INTERVAL: u32 = MicrosDurationU64::minutes(15).to_secs() as u32;
+/// Chip reported to the clients, for their power estimates.
+pub const MODEL: Model = Model::Esp32S3;
+
+pub fn split(peripherals: Peripherals) -> Parts {
+    Parts {
+        system: peripherals.SYSTEM,
+        gpio: peripherals.GPIO,
+        io_mux: peripherals.IO_MUX,
+        lpwr: peripherals.LPWR,
+        adc1: peripherals.ADC1,
+        i2c0: peripherals.I2C0,
+        radio: Radio {
+            timer: peripherals.TIMG1,
+            rng: peripherals.RNG,
+            radio_clk: peripherals.RADIO_CLK,
+            bt: peripherals.BT,
+        },
+    }
+}
+
+pub fn pins(pins: gpio::Pins) -> Pins {
+    Pins {
+        hygrometer_enable: pins.gpio4,
+        alarm: pins.gpio6,
+        pump: pins.gpio15,
+        button: pins.gpio0,
+        hygrometer_adc: pins.gpio5,
+        battery_adc: pins.gpio7,
+        climate_sda: pins.gpio8,
+        climate_scl: pins.gpio9,
+    }
+}
+
+pub fn radio_timer<'d>(timer: RadioTimer, clocks: &'d Clocks<'d>) -> impl EspWifiTimerSource + 'd {
+    TimerGroup::new(timer, clocks, None).timer0
+}
+
+/// Sleeps until the given time, in seconds, elapses or the button is pressed.
+/// Pressing the button pulls the pin low, against the pull-up of the RTC
+/// domain, which stays on while asleep: no external resistor is needed.
+pub fn sleep_deep(rtc: &mut Rtc, delay: &mut Delay, button: &mut Button, secs: u32) -> ! {
+    button.rtcio_pulldown(false);
+    button.rtcio_pullup(true);
+    let timer = TimerWakeupSource::new(Duration::from_secs(secs as u64));
+    let ext0 = Ext0WakeupSource::new(button, WakeupLevel::Low);
+    rtc.sleep_deep(&[&timer, &ext0], delay);
+}
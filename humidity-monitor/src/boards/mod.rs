@@ -0,0 +1,52 @@
+//! Board definitions: which chip peripherals and pins the firmware uses, and
+//! the few settings which differ between boards. The board is selected with a
+//! cargo feature, and adding one only takes a new definition next to these.
+
+#[cfg(not(any(feature = "esp32s3", feature = "esp32c6")))]
+compile_error!("select a board with one of the esp32s3 or esp32c6 features");
+
+#[cfg(all(feature = "esp32s3", feature = "esp32c6"))]
+compile_error!("the esp32s3 and esp32c6 features are mutually exclusive");
+
+#[cfg(feature = "esp32c6")]
+mod esp32c6;
+#[cfg(feature = "esp32s3")]
+mod esp32s3;
+
+#[cfg(feature = "esp32c6")]
+pub use esp32c6::*;
+#[cfg(feature = "esp32s3")]
+pub use esp32s3::*;
+
+use esp_hal::peripherals::{ADC1, BT, GPIO, I2C0, IO_MUX, LPWR, RADIO_CLK, RNG, SYSTEM};
+
+/// Peripherals used by the firmware.
+pub struct Parts {
+    pub system: SYSTEM,
+    pub gpio: GPIO,
+    pub io_mux: IO_MUX,
+    pub lpwr: LPWR,
+    pub adc1: ADC1,
+    pub i2c0: I2C0,
+    pub radio: Radio,
+}
+
+/// Peripherals taken over by the radio the first time it is brought up.
+pub struct Radio {
+    pub timer: RadioTimer,
+    pub rng: RNG,
+    pub radio_clk: RADIO_CLK,
+    pub bt: BT,
+}
+
+/// What each pin is wired to.
+pub struct Pins {
+    pub hygrometer_enable: HygrometerEnable,
+    pub alarm: Alarm,
+    pub pump: Pump,
+    pub button: Button,
+    pub hygrometer_adc: HygrometerAdc,
+    pub battery_adc: BatteryAdc,
+    pub climate_sda: ClimateSda,
+    pub climate_scl: ClimateScl,
+}
@@ -1,142 +1,175 @@
 #![no_std]
 #![no_main]
 
-use bleps::{gatt, no_rng::NoRng, Ble, HciConnector};
-use core::time::Duration;
+use core::ptr::addr_of_mut;
 use esp_backtrace as _;
 use esp_hal::{
-    analog::adc::{Adc, AdcCalLine, AdcConfig, Attenuation},
+    analog::adc::{Adc, AdcConfig, Attenuation},
     clock::ClockControl,
     delay::Delay,
-    gpio::{DriveStrength, Io, Level, Output},
+    gpio::{Io, Level, Output},
+    i2c::I2C,
     peripherals::*,
     prelude::*,
-    rng::Rng,
-    rtc_cntl::{sleep::TimerWakeupSource, Rtc},
+    rtc_cntl::Rtc,
     system::SystemControl,
-    timer::timg::TimerGroup,
 };
 use esp_println as _;
-use esp_wifi::{self, ble::controller::BleConnector, EspWifiInitFor};
 use fugit::{MicrosDurationU64, MillisDurationU32};
+#[cfg(feature = "bthome")]
+use humidity_core::bthome::Counter;
 use humidity_core::{
-    historical::Historical,
-    sample::{self, Summary},
+    advertising, irrigation,
+    lifecycle::{Config, Lifecycle, Stage, State},
+    retained::Retained,
+    schedule,
     sensors::Hygrometer,
-    serde,
+    settings::DeviceConfig,
+    wake,
 };
 
-#[ram(rtc_fast)]
-static mut SAMPLE_HISTORY: Historical<128, Summary<Hygrometer>> = Historical::new();
-
-const MEASURE_DELAY: u64 = MicrosDurationU64::minutes(15).to_millis();
-const HYGROMETER_WARMUP: u32 = MillisDurationU32::millis(1000).to_millis();
-const HYGROMETER_SAMPLES: u8 = u8::MAX;
-
-macro_rules! pulse {
-    ($output:ident, $delay:ident, $ms:expr) => {{
-        $output.set_high();
-        $delay.delay_millis($ms);
-        $output.set_low();
-    }};
-}
-
-macro_rules! delayed_pulse {
-    ($output:ident, $delay:ident, $ms:expr, $delay_ms:expr) => {{
-        $delay.delay_millis($delay_ms);
-        pulse!($output, $delay, $ms);
-    }};
-}
-
 mod blessed;
+mod board;
+mod boards;
+mod storage;
+
+#[ram(rtc_fast, zeroed)]
+static mut RETAINED: Retained<State<Hygrometer>, 2> = Retained::new();
+/// Settings written by clients, as also stored in flash.
+#[ram(rtc_fast, zeroed)]
+static mut SETTINGS: Retained<Option<DeviceConfig>, 1> = Retained::new();
+/// Encryption counters of the advertisements, reserved from flash.
+#[cfg(feature = "bthome")]
+#[ram(rtc_fast, zeroed)]
+static mut COUNTER: Retained<Counter, 1> = Retained::new();
+
+/// Settings until a client writes its own.
+const DEFAULT_SETTINGS: DeviceConfig = DeviceConfig {
+    interval: boards::BASE_INTERVAL,
+    samples: boards::HYGROMETER_SAMPLES,
+    sensor: Hygrometer::HW390,
+    calibration: None,
+};
+
+const CONFIG: Config = Config {
+    measure: schedule::Policy {
+        min: MicrosDurationU64::minutes(1).to_secs() as u32,
+        base: boards::BASE_INTERVAL,
+        max: MicrosDurationU64::minutes(60).to_secs() as u32,
+        fast_change: 5,
+        night_start: 22,
+        night_end: 7,
+    },
+    battery_check: MicrosDurationU64::hours(24).to_secs() as u32,
+    rollup: MicrosDurationU64::hours(1).to_secs() as u32,
+    advertise: MicrosDurationU64::minutes(30).to_secs() as u32,
+    watering: irrigation::Profile {
+        dry: 35,
+        target: 60,
+        hysteresis: 5,
+        min_interval: MicrosDurationU64::minutes(30).to_secs() as u32,
+        pulse_ms: MillisDurationU32::secs(3).to_millis(),
+    },
+    limits: irrigation::Limits {
+        max_run_ms: MillisDurationU32::secs(10).to_millis(),
+        daily_budget_ml: 250,
+        flow_ml_per_min: 100,
+        cooldown: MicrosDurationU64::minutes(15).to_secs() as u32,
+        no_effect_pulses: 3,
+        min_rise: 2,
+    },
+    advertising: advertising::Policy { backlog: 24, on_alert: true, on_button: true },
+    windows: wake::Windows {
+        regular_ms: MillisDurationU32::secs(5).to_millis(),
+        extended_ms: MillisDurationU32::secs(60).to_millis(),
+    },
+};
 
 #[entry]
 fn main() -> ! {
     esp_println::logger::init_logger_from_env();
 
-    let peripherals = Peripherals::take();
-    let system = SystemControl::new(peripherals.SYSTEM);
+    let parts = boards::split(Peripherals::take());
+    let system = SystemControl::new(parts.system);
     let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
-    let io = Io::new(peripherals.GPIO, peripherals.IO_MUX);
+    let io = Io::new(parts.gpio, parts.io_mux);
+    let pins = boards::pins(io.pins);
 
-    let mut rtc = Rtc::new(peripherals.LPWR, None);
+    let mut rtc = Rtc::new(parts.lpwr, None);
     let mut delay = Delay::new(&clocks);
 
-    // Pin definitions
-    let hygrometer_enable = &mut Output::new(io.pins.gpio4, Level::Low);
-    hygrometer_enable.set_drive_strength(DriveStrength::I5mA);
-    let alarm = &mut Output::new(io.pins.gpio6, Level::Low);
-    alarm.set_drive_strength(esp_hal::gpio::DriveStrength::I5mA);
-
-    let mut hygrometer_adc_config = AdcConfig::new();
-    let hygrometer_adc1_pin = &mut hygrometer_adc_config
-        .enable_pin_with_cal::<_, AdcCalLine<ADC1>>(io.pins.gpio5, Attenuation::Attenuation11dB);
-    let hygrometer_adc1 = &mut Adc::new(peripherals.ADC1, hygrometer_adc_config);
-    //
-
-    for _ in 0..5 {
-        delayed_pulse!(alarm, delay, 10, 15);
-        delayed_pulse!(alarm, delay, 10, 25);
+    let mut hygrometer_enable = Output::new(pins.hygrometer_enable, Level::Low);
+    let mut alarm = Output::new(pins.alarm, Level::Low);
+    if let Some(strength) = boards::DRIVE_STRENGTH {
+        hygrometer_enable.set_drive_strength(strength);
+        alarm.set_drive_strength(strength);
     }
+    let mut pump = Output::new(pins.pump, Level::Low);
+    let mut button = pins.button;
 
-    let mut toggle = || hygrometer_enable.toggle();
-    let mut warmup = || delay.delay_millis(HYGROMETER_WARMUP);
-    let mut read_adc = || hygrometer_adc1.read_oneshot(hygrometer_adc1_pin).unwrap();
-    let summary = sample::perform_sampling(
-        HYGROMETER_SAMPLES,
-        &mut toggle,
-        &mut warmup,
-        &mut read_adc,
-        Hygrometer::HW390,
+    let mut adc1_config = AdcConfig::new();
+    let mut hygrometer_adc1_pin = adc1_config.enable_pin_with_cal::<_, boards::AdcCalibration>(
+        pins.hygrometer_adc,
+        Attenuation::Attenuation11dB,
     );
-
-    unsafe { SAMPLE_HISTORY.store(summary) };
-
-    let timer = TimerGroup::new(peripherals.TIMG1, &clocks, None).timer0;
-    let init = esp_wifi::initialize(
-        EspWifiInitFor::Ble,
-        timer,
-        Rng::new(peripherals.RNG),
-        peripherals.RADIO_CLK,
-        &clocks,
-    )
-    .unwrap();
-
-    let mut hsync = unsafe { SAMPLE_HISTORY.sync() };
-    let mut read_historical = |_offset: usize, data: &mut [u8]| hsync.write(data).unwrap();
-
-    let mut bluetooth = peripherals.BT;
-    let connector = BleConnector::new(&init, &mut bluetooth);
-    let hci = HciConnector::new(connector, esp_wifi::current_millis);
-    let ble = &mut Ble::new(&hci);
-
-    blessed::start(ble);
-    if blessed::wait_for_connection(ble, &mut delay) {
-        let mut read_last_sample =
-            |_offset: usize, data: &mut [u8]| serde::serialize(&summary, data).unwrap();
-
-        gatt!([service {
-            uuid: "937312e0-2354-11eb-9f10-fbc30a62cf00",
-            characteristics: [
-                characteristic {
-                    name: "humidity",
-                    uuid: "987312e0-2354-11eb-9f10-fbc30a62cf50",
-                    read: read_last_sample,
-                },
-                characteristic {
-                    name: "historical",
-                    uuid: "987312e0-2354-11eb-9f10-fbc30a62cf60",
-                    read: read_historical,
-                },
-            ]
-        },]);
-
-        let mut rng = NoRng;
-        blessed::work_until_disconnect(ble, &mut gatt_attributes, &mut rng);
+    let mut battery_adc1_pin = adc1_config.enable_pin_with_cal::<_, boards::AdcCalibration>(
+        pins.battery_adc,
+        Attenuation::Attenuation11dB,
+    );
+    let mut adc1 = Adc::new(parts.adc1, adc1_config);
+    let mut climate =
+        I2C::new(parts.i2c0, pins.climate_sda, pins.climate_scl, 100.kHz(), &clocks, None);
+
+    // SAFETY: main is the only place the retained state is accessed from.
+    let retained = unsafe { &mut *addr_of_mut!(RETAINED) };
+    if !retained.validate() {
+        log::warn!("retained state lost, starting afresh");
     }
-
-    pulse!(alarm, delay, 100);
-
-    let timer = TimerWakeupSource::new(Duration::from_millis(MEASURE_DELAY));
-    rtc.sleep_deep(&[&timer], &mut delay);
+    // SAFETY: as above.
+    let retained_settings = unsafe { &mut *addr_of_mut!(SETTINGS) };
+    // The retained copy is as recent, flash is only read after a power loss.
+    let mut settings =
+        (*retained_settings.get()).or_else(storage::load_settings).unwrap_or(DEFAULT_SETTINGS);
+    let config = settings.apply(&CONFIG);
+    // SAFETY: as above.
+    #[cfg(feature = "bthome")]
+    let retained_counter = unsafe { &mut *addr_of_mut!(COUNTER) };
+    #[cfg(feature = "bthome")]
+    let mut counter = *retained_counter.get();
+
+    let next_wake = {
+        let mut board = board::Board {
+            clocks: &clocks,
+            rtc: &rtc,
+            delay: &mut delay,
+            hygrometer_enable: &mut hygrometer_enable,
+            alarm: &mut alarm,
+            pump: &mut pump,
+            adc1: &mut adc1,
+            hygrometer_adc1_pin: &mut hygrometer_adc1_pin,
+            battery_adc1_pin: &mut battery_adc1_pin,
+            climate: &mut climate,
+            radio: Some(parts.radio),
+            settings: &mut settings,
+            #[cfg(feature = "bthome")]
+            counter: &mut counter,
+        };
+        let mut lifecycle = Lifecycle::new(&config, retained.get_mut());
+        loop {
+            let stage = lifecycle.step(&mut board);
+            // Seals the retained state and settings after every stage, so that
+            // a panic in a later one does not lose them.
+            lifecycle.state_mut().seal();
+            *retained_settings.get_mut() = Some(*board.settings);
+            #[cfg(feature = "bthome")]
+            {
+                *retained_counter.get_mut() = *board.counter;
+            }
+            if let Stage::Sleep(secs) = stage {
+                break secs;
+            }
+        }
+    };
+
+    boards::sleep_deep(&mut rtc, &mut delay, &mut button, next_wake);
 }
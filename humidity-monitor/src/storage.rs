@@ -0,0 +1,28 @@
+//! Settings kept in flash, so that they survive a power loss.
+
+use embedded_storage::{ReadStorage, Storage};
+use esp_storage::{FlashStorage, FlashStorageError};
+use humidity_core::settings::{DeviceConfig, DEVICE_CONFIG_LEN};
+
+/// Where the settings are kept: the sector after the one of the encryption
+/// counters, still in the nvs partition of the default partition table.
+const SETTINGS_OFFSET: u32 = 0xa000;
+
+/// Reads the settings last stored, if any.
+pub fn load_settings() -> Option<DeviceConfig> {
+    let mut data = [0u8; DEVICE_CONFIG_LEN];
+    if let Err(err) = FlashStorage::new().read(SETTINGS_OFFSET, &mut data) {
+        log::error!("cannot read the settings: {err:?}");
+        return None;
+    }
+    // Erased flash reads as all ones, which is no known version.
+    DeviceConfig::decode(&data).ok()
+}
+
+/// Stores the settings, encoded as sent by the clients.
+pub fn store_settings(settings: &DeviceConfig) -> Result<(), FlashStorageError> {
+    let mut data = [0u8; DEVICE_CONFIG_LEN];
+    // The buffer holds any settings.
+    let n = settings.encode(&mut data).unwrap_or(DEVICE_CONFIG_LEN);
+    FlashStorage::new().write(SETTINGS_OFFSET, &data[..n])
+}
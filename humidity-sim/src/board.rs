@@ -0,0 +1,246 @@
+//! Simulated hardware, on a virtual clock.
+
+use std::net::TcpStream;
+
+use humidity_core::{
+    battery::{Battery, Chemistry},
+    lifecycle::{self, Content, Event, Live, Served},
+    pattern::Pattern,
+    sample::Summary,
+    schedule::Clock,
+    sensors::Hygrometer,
+    settings::DeviceConfig,
+    simulation::Probe,
+    wake::Cause,
+};
+
+use crate::{gatt, soil::Soil};
+
+/// Settings until a client writes its own.
+const SETTINGS: DeviceConfig =
+    DeviceConfig { interval: 15 * 60, samples: 64, sensor: Hygrometer::HW390, calibration: None };
+const FULL_MILLIVOLTS: u16 = 4200;
+const EMPTY_MILLIVOLTS: u16 = 3300;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    Adc,
+    Radio,
+}
+
+/// Failures injected into the hardware, every this many attempts.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Faults {
+    pub adc_every: Option<u32>,
+    pub radio_every: Option<u32>,
+}
+
+/// What happened so far.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub wakes: u32,
+    pub samples: u32,
+    pub sensor_errors: u32,
+    pub waterings: u32,
+    pub watered_ms: u64,
+    pub advertisements: u32,
+    pub connections: u32,
+    pub syncs: u32,
+    pub radio_errors: u32,
+    pub alarms: u32,
+}
+
+pub struct SimBoard {
+    now_ms: u64,
+    cause: Cause,
+    soil: Box<dyn Soil>,
+    probe: Probe<Hygrometer>,
+    /// Days a full battery lasts.
+    battery_days: u32,
+    faults: Faults,
+    attempts: (u32, u32),
+    client: Option<TcpStream>,
+    settings: DeviceConfig,
+    verbose: bool,
+    stats: Stats,
+}
+
+impl SimBoard {
+    pub fn new(soil: Box<dyn Soil>) -> Self {
+        Self {
+            now_ms: 0,
+            cause: Cause::Reset,
+            soil,
+            probe: Probe::new(SETTINGS.sensor, 1),
+            battery_days: 180,
+            faults: Faults::default(),
+            attempts: (0, 0),
+            client: None,
+            settings: SETTINGS,
+            verbose: false,
+            stats: Stats::default(),
+        }
+    }
+
+    pub fn with_faults(self, faults: Faults) -> Self {
+        Self { faults, ..self }
+    }
+
+    pub fn with_probe(self, probe: Probe<Hygrometer>) -> Self {
+        Self { probe, ..self }
+    }
+
+    pub fn with_settings(self, settings: DeviceConfig) -> Self {
+        Self { settings, ..self }
+    }
+
+    pub fn with_battery_days(self, battery_days: u32) -> Self {
+        Self { battery_days: battery_days.max(1), ..self }
+    }
+
+    /// Prints every event of the lifecycle.
+    pub fn verbose(self, verbose: bool) -> Self {
+        Self { verbose, ..self }
+    }
+
+    /// Settings of the next wake.
+    pub fn settings(&self) -> &DeviceConfig {
+        &self.settings
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Sets what wakes the device up next.
+    pub fn set_cause(&mut self, cause: Cause) {
+        self.cause = cause;
+    }
+
+    /// Fast-forwards a deep sleep.
+    pub fn sleep(&mut self, secs: u32) {
+        self.now_ms += secs as u64 * 1000;
+    }
+
+    /// Queues a client, served on the next advertising window.
+    pub fn connect(&mut self, client: TcpStream) {
+        self.client = Some(client);
+    }
+
+    fn fails(every: Option<u32>, attempts: &mut u32) -> bool {
+        *attempts += 1;
+        every.is_some_and(|every| every > 0 && attempts.is_multiple_of(every))
+    }
+}
+
+impl Clock for SimBoard {
+    fn now(&self) -> u32 {
+        (self.now_ms / 1000) as u32
+    }
+}
+
+impl lifecycle::Board for SimBoard {
+    type Sensor = Hygrometer;
+    type Error = Error;
+
+    fn wake_cause(&mut self) -> Cause {
+        self.stats.wakes += 1;
+        self.cause
+    }
+
+    fn beep(&mut self, pattern: Pattern) {
+        if let Pattern::LowBattery
+        | Pattern::LowMoisture
+        | Pattern::SensorFault
+        | Pattern::PumpFault = pattern
+        {
+            self.stats.alarms += 1;
+        }
+    }
+
+    fn read_battery(&mut self) -> Result<Battery, Error> {
+        if Self::fails(self.faults.adc_every, &mut self.attempts.0) {
+            return Err(Error::Adc);
+        }
+        let range = (FULL_MILLIVOLTS - EMPTY_MILLIVOLTS) as u64;
+        let drained = range * self.now() as u64 / (self.battery_days as u64 * 24 * 60 * 60);
+        let millivolts = FULL_MILLIVOLTS - drained.min(range) as u16;
+        Ok(Battery { millivolts, chemistry: Chemistry::LiIon })
+    }
+
+    fn sample(&mut self) -> Result<Summary<Hygrometer>, Error> {
+        if Self::fails(self.faults.adc_every, &mut self.attempts.0) {
+            return Err(Error::Adc);
+        }
+        let now = self.now();
+        let moisture = self.soil.moisture(now);
+        let conditions = self.soil.conditions(now);
+        // The probe stands for the sensor the device was set up with.
+        self.probe.sensor = self.settings.sensor;
+        let mut summary = self.probe.sample(self.settings.samples, moisture, &conditions, now);
+        for reading in [&mut summary.avg, &mut summary.min, &mut summary.max] {
+            *reading = self.settings.correct(*reading);
+        }
+        Ok(summary)
+    }
+
+    fn water(&mut self, pulse_ms: u32) {
+        let now = self.now();
+        self.soil.water(now, pulse_ms);
+        self.now_ms += pulse_ms as u64;
+        self.stats.waterings += 1;
+        self.stats.watered_ms += pulse_ms as u64;
+    }
+
+    fn serve(
+        &mut self,
+        window_ms: u32,
+        content: &Content<'_, Hygrometer>,
+    ) -> Result<Served<Hygrometer>, Error> {
+        self.stats.advertisements += 1;
+        if Self::fails(self.faults.radio_every, &mut self.attempts.1) {
+            return Err(Error::Radio);
+        }
+        let Some(client) = self.client.take() else {
+            self.now_ms += window_ms as u64;
+            return Ok(Served::default());
+        };
+
+        let mut server = gatt::Server::new(content, self.settings, self.now());
+        if let Err(err) = server.serve(client) {
+            if self.verbose {
+                println!("client failed: {err}");
+            }
+            return Err(Error::Radio);
+        }
+        if let Some(settings) = server.written() {
+            self.settings = settings;
+        }
+        // The line protocol has no notifications, nor does time pass while
+        // connected, so nothing is sampled live.
+        Ok(Served {
+            connected: true,
+            synced: server.synced(),
+            commands: server.commands(),
+            live: Live::new(),
+        })
+    }
+
+    fn report(&mut self, event: Event<Hygrometer, Error>) {
+        match event {
+            Event::Sampled(_) => self.stats.samples += 1,
+            Event::SensorError(_) => self.stats.sensor_errors += 1,
+            Event::RadioError(_) => self.stats.radio_errors += 1,
+            Event::Served(served) => {
+                self.stats.connections += served.connected as u32;
+                self.stats.syncs += served.synced as u32;
+            }
+            _ => {}
+        }
+        if self.verbose {
+            let now = self.now();
+            let (days, hours, minutes) = (now / 86_400, now / 3600 % 24, now / 60 % 60);
+            println!("{days:>4}d {hours:02}:{minutes:02} {event:?}");
+        }
+    }
+}
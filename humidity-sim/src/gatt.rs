@@ -0,0 +1,396 @@
+//! In-process GATT server, exposing the same characteristics as the firmware.
+//!
+//! The line protocol has no notifications: responses to RPC requests queue up
+//! instead, and each read of the response characteristic takes the next one,
+//! or nothing when none is left.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::TcpStream,
+};
+
+use humidity_core::{
+    battery::Battery,
+    ess,
+    historical::Syncer,
+    lifecycle::{Content, HISTORY_LEN},
+    rpc::{self, Commands},
+    sample::{Record, Summary},
+    sensors::Hygrometer,
+    serde,
+    settings::DeviceConfig,
+    shared::protocol::{self, Payload, Uuid},
+    time::{cts, Times, WallClock},
+};
+
+use crate::protocol::{Request, Response};
+
+/// Size of a read, matching the attribute payloads of the firmware.
+const MTU: usize = 64;
+
+pub struct Server<'a> {
+    last_sample: Option<Summary<Hygrometer>>,
+    battery: Option<Battery>,
+    history: Syncer<'a, Record<Hygrometer>>,
+    times: Times<'a, HISTORY_LEN>,
+    synced: bool,
+    now: u32,
+    clock: WallClock,
+    /// Time written by the client, with the time of the device clock.
+    time: Option<(u32, u32)>,
+    settings: DeviceConfig,
+    written: Option<DeviceConfig>,
+    rpc: rpc::Server<'a, Hygrometer>,
+}
+
+impl<'a> Server<'a> {
+    /// Serves the content, `now` being the time on the device clock.
+    pub fn new(content: &Content<'a, Hygrometer>, settings: DeviceConfig, now: u32) -> Self {
+        Self {
+            last_sample: content.last_sample,
+            battery: content.battery,
+            history: content.history.sync(),
+            times: content.times(),
+            synced: false,
+            now,
+            clock: content.clock,
+            time: None,
+            settings,
+            written: None,
+            rpc: rpc::Server::new(content, now),
+        }
+    }
+
+    /// Whether the whole history was read.
+    pub fn synced(&self) -> bool {
+        self.synced
+    }
+
+    /// Settings written by the client, if any.
+    pub fn written(&self) -> Option<DeviceConfig> {
+        self.written
+    }
+
+    /// Commands requested over RPC, and the time written to the Current Time
+    /// Service, which takes precedence.
+    pub fn commands(&self) -> Commands {
+        let commands = self.rpc.commands();
+        Commands { time: self.time.or(commands.time), ..commands }
+    }
+
+    pub fn characteristics(&self) -> Vec<String> {
+        protocol::SERVICES
+            .iter()
+            .flat_map(|service| service.characteristics)
+            .map(|characteristic| characteristic.uuid.to_string())
+            .collect()
+    }
+
+    pub fn read(&mut self, uuid: Uuid) -> Result<Vec<u8>, String> {
+        let characteristic =
+            protocol::find(uuid).ok_or(format!("unknown characteristic {uuid}"))?;
+        if !characteristic.read {
+            return Err(format!("write only characteristic {uuid}"));
+        }
+        let mut data = [0u8; MTU];
+        let n = match characteristic.payload {
+            Payload::Summary => match &self.last_sample {
+                Some(summary) => serde::serialize(summary, &mut data),
+                None => Ok(0),
+            },
+            Payload::Records => {
+                let n = self.history.write(&mut data);
+                self.synced = n == Ok(0);
+                n
+            }
+            Payload::BatteryLevel => match &self.battery {
+                Some(battery) => {
+                    data[0] = battery.percentage();
+                    Ok(1)
+                }
+                None => Ok(0),
+            },
+            Payload::Humidity => match &self.last_sample {
+                Some(summary) => {
+                    let value = ess::humidity(summary);
+                    data[..value.len()].copy_from_slice(&value);
+                    Ok(value.len())
+                }
+                None => Ok(0),
+            },
+            Payload::DeviceConfig => serde::serialize(&self.settings, &mut data),
+            Payload::RpcResponse => {
+                return Ok(self.rpc.response().map_or(vec![], |frame| frame.as_bytes().to_vec()))
+            }
+            Payload::Version => return Ok(vec![protocol::VERSION]),
+            Payload::Times => self.times.write(&mut data),
+            Payload::CurrentTime => {
+                return Ok(self
+                    .clock
+                    .unix(self.now)
+                    .map_or(vec![], |unix| cts::encode(unix).to_vec()))
+            }
+            Payload::RpcRequest => unreachable!("not readable"),
+        };
+        n.map(|n| data[..n].to_vec()).map_err(|err| format!("cannot serialize: {err:?}"))
+    }
+
+    pub fn write(&mut self, uuid: Uuid, value: &[u8]) -> Result<(), String> {
+        let characteristic =
+            protocol::find(uuid).ok_or(format!("unknown characteristic {uuid}"))?;
+        if !characteristic.write {
+            return Err(format!("read only characteristic {uuid}"));
+        }
+        match characteristic.payload {
+            Payload::DeviceConfig => {
+                let settings = DeviceConfig::decode(value)
+                    .map_err(|err| format!("invalid settings: {err:?}"))?;
+                self.written = Some(settings);
+            }
+            Payload::CurrentTime => {
+                let unix = cts::decode(value).map_err(|err| format!("invalid time: {err:?}"))?;
+                // Dates the history read from then on already.
+                self.clock.sync(self.now, unix);
+                self.times.sync(self.now, unix);
+                self.time = Some((self.now, unix));
+            }
+            // Invalid requests are answered with an error code, as on the device.
+            Payload::RpcRequest => self.rpc.request(value),
+            payload => unreachable!("{payload:?} not writable"),
+        }
+        Ok(())
+    }
+
+    /// Answers a request, or returns nothing when the client disconnects.
+    pub fn handle(&mut self, request: Request) -> Option<Response> {
+        match request {
+            Request::Discover => Some(Response::Characteristics(self.characteristics())),
+            Request::Read(uuid) => Some(match parse(&uuid).and_then(|uuid| self.read(uuid)) {
+                Ok(value) => Response::Value(value),
+                Err(reason) => Response::Error(reason),
+            }),
+            Request::Write(uuid, value) => {
+                Some(match parse(&uuid).and_then(|uuid| self.write(uuid, &value)) {
+                    Ok(()) => Response::Written,
+                    Err(reason) => Response::Error(reason),
+                })
+            }
+            Request::Disconnect => None,
+        }
+    }
+
+    /// Serves a client until it disconnects.
+    pub fn serve(&mut self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let response = match Request::parse(&line?) {
+                Ok(request) => match self.handle(request) {
+                    Some(response) => response,
+                    None => break,
+                },
+                Err(reason) => Response::Error(reason),
+            };
+            writer.write_all(format!("{}\n", response.encode()).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+fn parse(uuid: &str) -> Result<Uuid, String> {
+    Uuid::parse(uuid).ok_or(format!("invalid uuid {uuid}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use humidity_core::{
+        battery::Chemistry, historical::Historical, lifecycle::HISTORY_LEN, shared::Broadcast,
+    };
+
+    fn summary(avg: u16) -> Summary<Hygrometer> {
+        Summary { n: 1, avg, min: avg, max: avg, sensor: Hygrometer::HW390, compensation: None }
+    }
+
+    const BATTERY: Battery = Battery { millivolts: 4200, chemistry: Chemistry::LiIon };
+    const SETTINGS: DeviceConfig =
+        DeviceConfig { interval: 900, samples: 64, sensor: Hygrometer::HW390, calibration: None };
+
+    #[test]
+    fn test_read() {
+        let mut history = Historical::<HISTORY_LEN, _>::new();
+        history.store(Record { summary: summary(1500), battery: Some(BATTERY), fault: None });
+        let mut stamps = Historical::new();
+        stamps.store(60);
+        let content = Content {
+            history: &history,
+            stamps: &stamps,
+            clock: WallClock::new(),
+            last_sample: Some(summary(1500)),
+            battery: Some(BATTERY),
+            broadcast: Broadcast::default(),
+            sequence: 1,
+            next_sample: 300,
+            interval: 300,
+        };
+        let mut sut = Server::new(&content, SETTINGS, 0);
+
+        assert_eq!(Ok(vec![100]), sut.read(protocol::BATTERY_LEVEL.uuid));
+        let last_sample = sut.read(protocol::HUMIDITY.uuid).unwrap();
+        assert_eq!(Ok(summary(1500)), serde::deserialize(&last_sample));
+        assert_eq!(Ok(5238u16.to_le_bytes().to_vec()), sut.read(protocol::ESS_HUMIDITY.uuid));
+        assert_eq!(Ok(vec![protocol::VERSION]), sut.read(protocol::PROTOCOL_VERSION.uuid));
+        assert!(sut.read(Uuid::from_u16(0x2a00)).is_err());
+        assert_eq!(Some(Response::Value(vec![100])), sut.handle(Request::Read("2A19".to_owned())));
+        assert!(matches!(sut.handle(Request::Read("2a1".to_owned())), Some(Response::Error(_))));
+
+        assert!(!sut.read(protocol::HISTORICAL.uuid).unwrap().is_empty());
+        assert!(!sut.synced());
+        assert_eq!(Ok(vec![]), sut.read(protocol::HISTORICAL.uuid));
+        assert!(sut.synced());
+    }
+
+    #[test]
+    fn test_nothing_to_read() {
+        let history = Historical::<HISTORY_LEN, _>::new();
+        let stamps = Historical::new();
+        let content = Content {
+            history: &history,
+            stamps: &stamps,
+            clock: WallClock::new(),
+            last_sample: None,
+            battery: None,
+            broadcast: Broadcast::default(),
+            sequence: 1,
+            next_sample: 300,
+            interval: 300,
+        };
+        let mut sut = Server::new(&content, SETTINGS, 0);
+
+        assert_eq!(
+            Some(Response::Value(vec![])),
+            sut.handle(Request::Read(protocol::HUMIDITY.uuid.to_string()))
+        );
+        assert_eq!(Ok(vec![]), sut.read(protocol::BATTERY_LEVEL.uuid));
+        assert_eq!(Ok(vec![]), sut.read(protocol::ESS_HUMIDITY.uuid));
+        assert_eq!(None, sut.handle(Request::Disconnect));
+    }
+
+    #[test]
+    fn test_write_settings() {
+        let history = Historical::<HISTORY_LEN, _>::new();
+        let stamps = Historical::new();
+        let content = Content {
+            history: &history,
+            stamps: &stamps,
+            clock: WallClock::new(),
+            last_sample: None,
+            battery: None,
+            broadcast: Broadcast::default(),
+            sequence: 1,
+            next_sample: 300,
+            interval: 300,
+        };
+        let mut sut = Server::new(&content, SETTINGS, 0);
+        let settings = DeviceConfig { interval: 600, ..SETTINGS };
+        let mut data = [0u8; 16];
+        let n = settings.encode(&mut data).unwrap();
+
+        assert_eq!(
+            Ok(SETTINGS),
+            serde::deserialize(&sut.read(protocol::DEVICE_CONFIG.uuid).unwrap())
+        );
+        assert!(sut.write(protocol::DEVICE_CONFIG.uuid, &data[..n - 1]).is_err());
+        assert!(sut.write(protocol::HUMIDITY.uuid, &data[..n]).is_err());
+        assert_eq!(None, sut.written());
+
+        assert_eq!(
+            Some(Response::Written),
+            sut.handle(Request::Write(
+                protocol::DEVICE_CONFIG.uuid.to_string(),
+                data[..n].to_vec()
+            ))
+        );
+        assert_eq!(Some(settings), sut.written());
+    }
+
+    #[test]
+    fn test_rpc() {
+        let mut history = Historical::<HISTORY_LEN, _>::new();
+        history.store(Record { summary: summary(1500), battery: Some(BATTERY), fault: None });
+        let mut stamps = Historical::new();
+        stamps.store(60);
+        let content = Content {
+            history: &history,
+            stamps: &stamps,
+            clock: WallClock::new(),
+            last_sample: None,
+            battery: Some(BATTERY),
+            broadcast: Broadcast::default(),
+            sequence: 1,
+            next_sample: 300,
+            interval: 300,
+        };
+        let mut sut = Server::new(&content, SETTINGS, 120);
+        let mut frame = [0u8; rpc::FRAME_LEN];
+        let call = |sut: &mut Server, request: rpc::Request, frame: &mut [u8]| {
+            let n = request.encode(3, frame).unwrap();
+            sut.write(protocol::RPC_REQUEST.uuid, &frame[..n])
+        };
+
+        assert_eq!(Ok(vec![]), sut.read(protocol::RPC_RESPONSE.uuid));
+        call(&mut sut, rpc::Request::GetHistoryRange { start: 0, count: 2 }, &mut frame).unwrap();
+        let record = sut.read(protocol::RPC_RESPONSE.uuid).unwrap();
+        assert_eq!(
+            Ok((3, rpc::Response::Record(history.get(0).copied().unwrap()))),
+            rpc::Response::decode(rpc::Opcode::GetHistoryRange, &record)
+        );
+        assert_eq!(Ok(vec![3, rpc::Code::Done as u8]), sut.read(protocol::RPC_RESPONSE.uuid));
+        assert_eq!(Ok(vec![]), sut.read(protocol::RPC_RESPONSE.uuid));
+
+        call(&mut sut, rpc::Request::Reboot, &mut frame).unwrap();
+        assert_eq!(Ok(vec![3, rpc::Code::Done as u8]), sut.read(protocol::RPC_RESPONSE.uuid));
+        assert!(sut.commands().reboot);
+        assert!(sut.read(protocol::RPC_REQUEST.uuid).is_err());
+    }
+
+    #[test]
+    fn test_time() {
+        let mut history = Historical::<HISTORY_LEN, _>::new();
+        history.store(Record { summary: summary(1500), battery: Some(BATTERY), fault: None });
+        let mut stamps = Historical::new();
+        stamps.store(60);
+        let mut clock = WallClock::new();
+        clock.sync(100, 1_700_000_000);
+        let content = Content {
+            history: &history,
+            stamps: &stamps,
+            clock,
+            last_sample: None,
+            battery: Some(BATTERY),
+            broadcast: Broadcast::default(),
+            sequence: 1,
+            next_sample: 300,
+            interval: 300,
+        };
+        let mut sut = Server::new(&content, SETTINGS, 120);
+
+        assert_eq!(Ok(cts::encode(1_700_000_020).to_vec()), sut.read(protocol::CURRENT_TIME.uuid));
+        assert_eq!(
+            Ok(1_699_999_960u32.to_le_bytes().to_vec()),
+            sut.read(protocol::HISTORY_TIMES.uuid)
+        );
+        assert_eq!(Ok(vec![]), sut.read(protocol::HISTORY_TIMES.uuid));
+
+        assert!(sut.write(protocol::CURRENT_TIME.uuid, &[0; 10]).is_err());
+        sut.write(protocol::CURRENT_TIME.uuid, &cts::encode(1_800_000_000)).unwrap();
+        assert_eq!(Some((120, 1_800_000_000)), sut.commands().time);
+        assert_eq!(Ok(cts::encode(1_800_000_000).to_vec()), sut.read(protocol::CURRENT_TIME.uuid));
+        let mut sut = Server::new(&content, SETTINGS, 120);
+        sut.write(protocol::CURRENT_TIME.uuid, &cts::encode(1_800_000_000)).unwrap();
+        assert_eq!(
+            Ok(1_799_999_940u32.to_le_bytes().to_vec()),
+            sut.read(protocol::HISTORY_TIMES.uuid)
+        );
+    }
+}
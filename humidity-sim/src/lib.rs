@@ -0,0 +1,16 @@
+//! # Firmware simulator
+//!
+//! Runs the device [lifecycle](humidity_core::lifecycle) on the host, against
+//! a simulated sensor reading a [`soil::Soil`], a virtual clock fast-forwarding
+//! deep sleeps, and a [GATT server](gatt) reachable over a local
+//! [transport](protocol). Weeks of operation take seconds, and clients see the
+//! same characteristics as on a real device.
+
+pub use simulator::{Simulator, CONFIG, DAY};
+
+pub mod board;
+pub mod gatt;
+pub mod protocol;
+pub mod soil;
+
+mod simulator;
@@ -0,0 +1,146 @@
+use std::{env, error::Error, fs, net::TcpListener, process, time::Instant};
+
+use humidity_core::{
+    sensors::Hygrometer,
+    simulation::{self, Model, Probe, Weather},
+};
+use humidity_sim::{
+    board::{Faults, SimBoard},
+    soil::{Pot, Script, Soil},
+    Simulator, CONFIG, DAY,
+};
+
+const USAGE: &str = "\
+Usage: humidity-sim [options]
+
+Options:
+  --days <n>          days to fast-forward, 30 by default
+  --moisture <n>      initial soil moisture, 70 by default
+  --soil <kind>       bonsai or potting, bonsai by default
+  --weather <kind>    indoors or summer, indoors by default
+  --drift <n>         sensor drift in ADC counts per day, 0 by default
+  --script <file>     soil moisture script, made of `<hours> <moisture>` lines
+  --fail-adc <n>      fail every n-th ADC reading
+  --fail-radio <n>    fail every n-th advertising window
+  --listen <addr>     then serve clients connecting to the address
+  --speed <n>         simulated seconds per real second while serving, 60 by default
+  --verbose           print every event
+  --help              print this help";
+
+struct Args {
+    days: u32,
+    moisture: u8,
+    soil: simulation::Soil,
+    weather: Weather,
+    drift: i16,
+    script: Option<String>,
+    faults: Faults,
+    listen: Option<String>,
+    speed: u32,
+    verbose: bool,
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut args = Args {
+        days: 30,
+        moisture: 70,
+        soil: simulation::Soil::BONSAI,
+        weather: Weather::INDOORS,
+        drift: 0,
+        script: None,
+        faults: Faults::default(),
+        listen: None,
+        speed: 60,
+        verbose: false,
+    };
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || argv.next().ok_or_else(|| format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--days" => args.days = value()?.parse()?,
+            "--moisture" => args.moisture = value()?.parse()?,
+            "--soil" => {
+                args.soil = match value()?.as_str() {
+                    "bonsai" => simulation::Soil::BONSAI,
+                    "potting" => simulation::Soil::POTTING_MIX,
+                    soil => return Err(format!("unknown soil {soil}").into()),
+                }
+            }
+            "--weather" => {
+                args.weather = match value()?.as_str() {
+                    "indoors" => Weather::INDOORS,
+                    "summer" => Weather::SUMMER,
+                    weather => return Err(format!("unknown weather {weather}").into()),
+                }
+            }
+            "--drift" => args.drift = value()?.parse()?,
+            "--script" => args.script = Some(value()?),
+            "--fail-adc" => args.faults.adc_every = Some(value()?.parse()?),
+            "--fail-radio" => args.faults.radio_every = Some(value()?.parse()?),
+            "--listen" => args.listen = Some(value()?),
+            "--speed" => args.speed = value()?.parse()?,
+            "--verbose" => args.verbose = true,
+            "--help" => {
+                println!("{USAGE}");
+                process::exit(0);
+            }
+            _ => return Err(format!("unknown argument {arg}, see --help").into()),
+        }
+    }
+    Ok(args)
+}
+
+fn print_stats(sim: &Simulator) {
+    let stats = sim.board().stats();
+    println!(
+        "day {:.1}: {} wakes, {} samples ({} failed), {} waterings ({:.1}s), \
+         {} advertisements, {} connections ({} synced, {} failed), {} alarms",
+        sim.now() as f32 / DAY as f32,
+        stats.wakes,
+        stats.samples,
+        stats.sensor_errors,
+        stats.waterings,
+        stats.watered_ms as f32 / 1000.0,
+        stats.advertisements,
+        stats.connections,
+        stats.syncs,
+        stats.radio_errors,
+        stats.alarms,
+    );
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = parse_args()?;
+    let soil: Box<dyn Soil> = match &args.script {
+        Some(path) => Box::new(Script::parse(&fs::read_to_string(path)?)?),
+        None => Box::new(Pot::new(Model::new(args.soil, args.weather, args.moisture))),
+    };
+    let mut probe = Probe::new(Hygrometer::HW390, 1);
+    probe.drift = args.drift;
+    let board =
+        SimBoard::new(soil).with_probe(probe).with_faults(args.faults).verbose(args.verbose);
+    let mut sim = Simulator::new(CONFIG, board);
+
+    let started = Instant::now();
+    sim.run_until(args.days * DAY);
+    print_stats(&sim);
+    println!("simulated in {:?}", started.elapsed());
+
+    let Some(addr) = args.listen else {
+        return Ok(());
+    };
+    let listener = TcpListener::bind(&addr)?;
+    println!("listening on {addr}, connect a client to wake the device up");
+
+    // Time keeps passing between clients, each of them pressing the button.
+    let mut last = Instant::now();
+    for client in listener.incoming() {
+        let elapsed = last.elapsed().as_secs() as u32;
+        last = Instant::now();
+        sim.run_until(sim.now() + elapsed * args.speed);
+        sim.board_mut().connect(client?);
+        sim.press_button();
+        print_stats(&sim);
+    }
+    Ok(())
+}
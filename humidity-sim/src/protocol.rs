@@ -0,0 +1,201 @@
+//! Line based protocol between the simulated GATT server and its clients,
+//! standing in for the radio. Each request gets a single response:
+//!
+//! ```text
+//! > DISCOVER
+//! < CHARACTERISTICS 987312e0-2354-11eb-9f10-fbc30a62cf50,2a19
+//! > READ 2a19
+//! < VALUE 5f
+//! > READ 2a00
+//! < ERROR unknown characteristic 2a00
+//! > WRITE 987312e0-2354-11eb-9f10-fbc30a62cf70 0158020000400100
+//! < WRITTEN
+//! > DISCONNECT
+//! ```
+
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+use humidity_core::shared::protocol::Uuid;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Lists the characteristics.
+    Discover,
+    /// Reads a characteristic by UUID.
+    Read(String),
+    /// Writes a value to a characteristic by UUID.
+    Write(String, Vec<u8>),
+    /// Ends the connection.
+    Disconnect,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    Characteristics(Vec<String>),
+    Value(Vec<u8>),
+    Written,
+    Error(String),
+}
+
+impl Request {
+    pub fn encode(&self) -> String {
+        match self {
+            Request::Discover => "DISCOVER".to_owned(),
+            Request::Read(uuid) => format!("READ {uuid}"),
+            Request::Write(uuid, value) => format!("WRITE {uuid} {}", encode_hex(value)),
+            Request::Disconnect => "DISCONNECT".to_owned(),
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Self, String> {
+        match line.trim().split_once(' ') {
+            Some(("READ", uuid)) => Ok(Request::Read(uuid.to_owned())),
+            Some(("WRITE", rest)) => match rest.split_once(' ') {
+                Some((uuid, value)) => Ok(Request::Write(uuid.to_owned(), decode_hex(value)?)),
+                None => Err(format!("no value to write in {line:?}")),
+            },
+            None if line.trim() == "DISCOVER" => Ok(Request::Discover),
+            None if line.trim() == "DISCONNECT" => Ok(Request::Disconnect),
+            _ => Err(format!("unknown request {line:?}")),
+        }
+    }
+}
+
+impl Response {
+    pub fn encode(&self) -> String {
+        match self {
+            Response::Characteristics(uuids) => format!("CHARACTERISTICS {}", uuids.join(",")),
+            Response::Value(value) => format!("VALUE {}", encode_hex(value)),
+            Response::Written => "WRITTEN".to_owned(),
+            Response::Error(reason) => format!("ERROR {reason}"),
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match kind {
+            "CHARACTERISTICS" => Ok(Response::Characteristics(
+                rest.split(',').filter(|uuid| !uuid.is_empty()).map(str::to_owned).collect(),
+            )),
+            "VALUE" => decode_hex(rest).map(Response::Value),
+            "WRITTEN" => Ok(Response::Written),
+            "ERROR" => Ok(Response::Error(rest.to_owned())),
+            _ => Err(format!("unknown response {line:?}")),
+        }
+    }
+}
+
+fn encode_hex(value: &[u8]) -> String {
+    value.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(format!("odd length value {hex:?}"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|err| err.to_string()))
+        .collect()
+}
+
+/// Blocking client, connected until dropped. Requests wait until the simulator
+/// serves the connection, which only happens while it advertises.
+pub struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Client {
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let writer = TcpStream::connect(addr)?;
+        writer.set_nodelay(true)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(Self { reader, writer })
+    }
+
+    pub fn request(&mut self, request: &Request) -> io::Result<Response> {
+        self.writer.write_all(format!("{}\n", request.encode()).as_bytes())?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Response::parse(&line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Reads a characteristic, failing if the server reports an error.
+    pub fn read(&mut self, uuid: Uuid) -> io::Result<Vec<u8>> {
+        match self.request(&Request::Read(uuid.to_string()))? {
+            Response::Value(value) => Ok(value),
+            Response::Error(reason) => Err(io::Error::other(reason)),
+            response => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected response {response:?}"),
+            )),
+        }
+    }
+
+    /// Writes a characteristic, failing if the server rejects the value.
+    pub fn write(&mut self, uuid: Uuid, value: &[u8]) -> io::Result<()> {
+        match self.request(&Request::Write(uuid.to_string(), value.to_vec()))? {
+            Response::Written => Ok(()),
+            Response::Error(reason) => Err(io::Error::other(reason)),
+            response => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected response {response:?}"),
+            )),
+        }
+    }
+
+    pub fn disconnect(mut self) -> io::Result<()> {
+        self.writer.write_all(format!("{}\n", Request::Disconnect.encode()).as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_requests_roundtrip() {
+        for request in [
+            Request::Discover,
+            Request::Read("2a19".to_owned()),
+            Request::Write("2a19".to_owned(), vec![0x01, 0xab]),
+            Request::Disconnect,
+        ] {
+            assert_eq!(Ok(request.clone()), Request::parse(&request.encode()));
+        }
+    }
+
+    #[test]
+    fn test_responses_roundtrip() {
+        for response in [
+            Response::Characteristics(vec!["2a19".to_owned(), "2a6f".to_owned()]),
+            Response::Characteristics(vec![]),
+            Response::Value(vec![0x00, 0x5f, 0xff]),
+            Response::Value(vec![]),
+            Response::Written,
+            Response::Error("unknown characteristic".to_owned()),
+        ] {
+            assert_eq!(Ok(response.clone()), Response::parse(&response.encode()));
+        }
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        assert!(Request::parse("WRITE 2a19").is_err());
+        assert!(Request::parse("WRITE 2a19 0").is_err());
+        assert!(Response::parse("VALUE 5").is_err());
+        assert!(Response::parse("VALUE zz").is_err());
+        assert!(Response::parse("HELLO").is_err());
+    }
+}
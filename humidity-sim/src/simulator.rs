@@ -0,0 +1,331 @@
+use humidity_core::{
+    advertising, irrigation,
+    lifecycle::{Config, Lifecycle, State},
+    schedule::{self, Clock},
+    sensors::Hygrometer,
+    settings::DeviceConfig,
+    wake::{self, Cause},
+};
+
+use crate::board::SimBoard;
+
+pub const DAY: u32 = 24 * 60 * 60;
+
+/// Settings of the ESP32-S3 firmware.
+pub const CONFIG: Config = Config {
+    measure: schedule::Policy {
+        min: 60,
+        base: 15 * 60,
+        max: 60 * 60,
+        fast_change: 5,
+        night_start: 22,
+        night_end: 7,
+    },
+    battery_check: DAY,
+    rollup: 60 * 60,
+    advertise: 30 * 60,
+    watering: irrigation::Profile {
+        dry: 35,
+        target: 60,
+        hysteresis: 5,
+        min_interval: 30 * 60,
+        pulse_ms: 3000,
+    },
+    limits: irrigation::Limits {
+        max_run_ms: 10_000,
+        daily_budget_ml: 250,
+        flow_ml_per_min: 100,
+        cooldown: 15 * 60,
+        no_effect_pulses: 3,
+        min_rise: 2,
+    },
+    advertising: advertising::Policy { backlog: 24, on_alert: true, on_button: true },
+    windows: wake::Windows { regular_ms: 5_000, extended_ms: 60_000 },
+};
+
+/// Runs the device lifecycle on a simulated board, keeping its state across
+/// wakes as the retained memory would.
+pub struct Simulator {
+    config: Config,
+    state: Box<State<Hygrometer>>,
+    board: SimBoard,
+    started: bool,
+}
+
+impl Simulator {
+    pub fn new(config: Config, board: SimBoard) -> Self {
+        // Samples on the configured interval until a client changes it.
+        let settings = DeviceConfig { interval: config.measure.base, ..*board.settings() };
+        let board = board.with_settings(settings);
+        Self { config, state: Box::default(), board, started: false }
+    }
+
+    pub fn board(&self) -> &SimBoard {
+        &self.board
+    }
+
+    pub fn board_mut(&mut self) -> &mut SimBoard {
+        &mut self.board
+    }
+
+    pub fn state(&self) -> &State<Hygrometer> {
+        &self.state
+    }
+
+    /// Seconds since the device was powered up.
+    pub fn now(&self) -> u32 {
+        self.board.now()
+    }
+
+    /// Runs a wake, then sleeps until the next one. Returns the time slept, in
+    /// seconds.
+    pub fn wake(&mut self, cause: Cause) -> u32 {
+        let cause = if self.started { cause } else { Cause::Reset };
+        self.started = true;
+        self.board.set_cause(cause);
+        // Settings written by a client apply from the next wake, as on the device.
+        let config = self.board.settings().apply(&self.config);
+        let secs = Lifecycle::new(&config, &mut *self.state).run(&mut self.board);
+        self.board.sleep(secs);
+        secs
+    }
+
+    /// Wakes on every timer until the given time, in seconds.
+    pub fn run_until(&mut self, until: u32) {
+        while self.now() < until {
+            self.wake(Cause::Timer);
+        }
+    }
+
+    pub fn press_button(&mut self) -> u32 {
+        self.wake(Cause::Button)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+    use crate::{
+        board::Faults,
+        protocol::{Client, Request, Response},
+        soil::{Pot, Script},
+    };
+    use humidity_core::{
+        lifecycle::HISTORY_LEN,
+        rpc,
+        sample::Record,
+        serde,
+        shared::protocol,
+        simulation::{Model, Soil, Weather},
+        time::cts,
+    };
+
+    fn simulator(soil: impl crate::soil::Soil + 'static) -> Simulator {
+        Simulator::new(CONFIG, SimBoard::new(Box::new(soil)))
+    }
+
+    fn pot() -> Pot {
+        Pot::new(Model::new(Soil::BONSAI, Weather::INDOORS, 70))
+    }
+
+    fn history_len(sut: &Simulator) -> usize {
+        let mut syncer = sut.state().history().sync();
+        let mut data = [0u8; 64];
+        let mut n = 0;
+        while syncer.write(&mut data).unwrap() > 0 {
+            n += 1;
+        }
+        n
+    }
+
+    #[test]
+    fn test_weeks_of_operation() {
+        let mut sut = simulator(pot());
+
+        sut.run_until(28 * DAY);
+
+        let stats = sut.board().stats();
+        assert!(stats.samples > 28 * 24 * 2);
+        assert!(stats.waterings > 0);
+        assert_eq!(0, stats.sensor_errors);
+        // Soil dries slowly, so samples are mostly on the base interval.
+        assert!(stats.samples < 28 * DAY / 60);
+        assert!(history_len(&sut) > 0);
+    }
+
+    #[test]
+    fn test_moist_soil_never_watered() {
+        let mut sut = simulator(Script::parse("0 80").unwrap());
+
+        sut.run_until(7 * DAY);
+
+        let stats = sut.board().stats();
+        assert_eq!(0, stats.waterings);
+        assert_eq!(7 * DAY / CONFIG.measure.base, stats.samples);
+        // On the first wake, then every half hour, that is every other wake,
+        // until nobody syncing piles up a backlog and every wake advertises.
+        let backlog = CONFIG.advertising.backlog as u32;
+        assert_eq!(1 + backlog / 2 + stats.samples - backlog, stats.advertisements);
+    }
+
+    #[test]
+    fn test_sensor_failures() {
+        let faults = Faults { adc_every: Some(3), radio_every: None };
+        let board = SimBoard::new(Box::new(Script::parse("0 80").unwrap())).with_faults(faults);
+        let mut sut = Simulator::new(CONFIG, board);
+
+        sut.run_until(DAY);
+
+        let stats = sut.board().stats();
+        assert!(stats.sensor_errors > 0);
+        assert!(stats.alarms >= stats.sensor_errors);
+        assert_eq!(0, stats.waterings);
+    }
+
+    #[test]
+    fn test_radio_failures() {
+        let faults = Faults { adc_every: None, radio_every: Some(1) };
+        let board = SimBoard::new(Box::new(pot())).with_faults(faults);
+        let mut sut = Simulator::new(CONFIG, board);
+
+        sut.run_until(DAY);
+
+        let stats = sut.board().stats();
+        assert_eq!(stats.advertisements, stats.radio_errors);
+        assert_eq!(0, stats.connections);
+    }
+
+    #[test]
+    fn test_client_reads_history() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut client = Client::connect(addr).unwrap();
+            let Response::Characteristics(uuids) = client.request(&Request::Discover).unwrap()
+            else {
+                panic!("characteristics expected");
+            };
+            assert!(uuids.contains(&protocol::HISTORICAL.uuid.to_string()));
+
+            let mut records = vec![];
+            loop {
+                let data = client.read(protocol::HISTORICAL.uuid).unwrap();
+                if data.is_empty() {
+                    break;
+                }
+                records.push(serde::deserialize::<Record<Hygrometer>>(&data).unwrap());
+            }
+            let battery = client.read(protocol::BATTERY_LEVEL.uuid).unwrap();
+            client.disconnect().unwrap();
+            (records, battery)
+        });
+
+        let mut sut = simulator(pot());
+        sut.run_until(DAY);
+        let (stream, _) = listener.accept().unwrap();
+        sut.board_mut().connect(stream);
+        sut.press_button();
+
+        let (records, battery) = client.join().unwrap();
+        assert_eq!(history_len(&sut), records.len());
+        assert!(records.len() <= HISTORY_LEN);
+        assert_eq!(1, battery.len());
+        let stats = sut.board().stats();
+        assert_eq!((1, 1), (stats.connections, stats.syncs));
+    }
+
+    #[test]
+    fn test_client_writes_settings() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let settings = DeviceConfig { interval: 30 * 60, ..*simulator(pot()).board().settings() };
+        let client = thread::spawn(move || {
+            let mut client = Client::connect(addr).unwrap();
+            let mut data = [0u8; 16];
+            let n = settings.encode(&mut data).unwrap();
+            client.write(protocol::DEVICE_CONFIG.uuid, &data[..n]).unwrap();
+            let invalid = DeviceConfig { samples: 0, ..settings };
+            let n = serde::serialize(&invalid, &mut data).unwrap();
+            assert!(client.write(protocol::DEVICE_CONFIG.uuid, &data[..n]).is_err());
+            client.disconnect().unwrap();
+        });
+
+        let mut sut = simulator(Script::parse("0 80").unwrap());
+        sut.run_until(DAY);
+        let (stream, _) = listener.accept().unwrap();
+        sut.board_mut().connect(stream);
+        sut.press_button();
+        client.join().unwrap();
+
+        assert_eq!(&settings, sut.board().settings());
+        let samples = sut.board().stats().samples;
+        sut.run_until(2 * DAY);
+        assert_eq!(DAY / settings.interval, sut.board().stats().samples - samples);
+    }
+
+    #[test]
+    fn test_client_sends_commands() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut client = Client::connect(addr).unwrap();
+            let mut frame = [0u8; rpc::FRAME_LEN];
+            for (id, request) in
+                [(1, rpc::Request::ClearHistory), (2, rpc::Request::SetTime(1_700_000_000))]
+            {
+                let n = request.encode(id, &mut frame).unwrap();
+                client.write(protocol::RPC_REQUEST.uuid, &frame[..n]).unwrap();
+                let response = client.read(protocol::RPC_RESPONSE.uuid).unwrap();
+                assert_eq!(vec![id, rpc::Code::Done as u8], response);
+            }
+            client.disconnect().unwrap();
+        });
+
+        let mut sut = simulator(pot());
+        sut.run_until(DAY);
+        assert!(history_len(&sut) > 0);
+        let (stream, _) = listener.accept().unwrap();
+        sut.board_mut().connect(stream);
+        sut.press_button();
+        client.join().unwrap();
+
+        assert_eq!(0, history_len(&sut));
+        assert_eq!(Some(1_700_000_000), sut.state().unix_time(sut.now()));
+    }
+
+    #[test]
+    fn test_client_sets_time() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut client = Client::connect(addr).unwrap();
+            assert_eq!(vec![0u8; 0], client.read(protocol::CURRENT_TIME.uuid).unwrap());
+            client.write(protocol::CURRENT_TIME.uuid, &cts::encode(1_700_000_000)).unwrap();
+            let mut times = vec![];
+            loop {
+                let data = client.read(protocol::HISTORY_TIMES.uuid).unwrap();
+                if data.is_empty() {
+                    break;
+                }
+                times.extend(data.chunks(4).map(|time| serde::deserialize::<u32>(time).unwrap()));
+            }
+            client.disconnect().unwrap();
+            times
+        });
+
+        let mut sut = simulator(pot());
+        sut.run_until(DAY);
+        let (stream, _) = listener.accept().unwrap();
+        sut.board_mut().connect(stream);
+        let now = sut.now();
+        sut.press_button();
+
+        let times = client.join().unwrap();
+        assert_eq!(history_len(&sut), times.len());
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(times.iter().all(|time| (1_700_000_000 - DAY..1_700_000_000).contains(time)));
+        assert_eq!(Some(1_700_000_000 + sut.now() - now), sut.state().unix_time(sut.now()));
+    }
+}
@@ -0,0 +1,136 @@
+//! Soil moisture seen by the simulated sensor.
+
+use humidity_core::simulation::{Conditions, Model};
+
+use crate::CONFIG;
+
+/// Soil moisture over time, in seconds, from 0 (as dry as air) to 100 (as wet
+/// as water).
+pub trait Soil {
+    fn moisture(&mut self, now: u32) -> u8;
+
+    /// Runs the pump for the given time, in milliseconds.
+    fn water(&mut self, now: u32, pulse_ms: u32);
+
+    /// The air around the soil, affecting the sensor.
+    fn conditions(&mut self, _now: u32) -> Conditions {
+        Conditions::REFERENCE
+    }
+}
+
+/// Soil modelled physically, watered by the pump at its nominal flow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pot {
+    pub model: Model,
+    pub flow_ml_per_min: u32,
+}
+
+impl Pot {
+    pub fn new(model: Model) -> Self {
+        Self { model, flow_ml_per_min: CONFIG.limits.flow_ml_per_min }
+    }
+}
+
+impl Soil for Pot {
+    fn moisture(&mut self, now: u32) -> u8 {
+        self.model.moisture(now)
+    }
+
+    fn water(&mut self, now: u32, pulse_ms: u32) {
+        self.model.water(now, self.flow_ml_per_min * pulse_ms / 60_000);
+    }
+
+    fn conditions(&mut self, now: u32) -> Conditions {
+        self.model.weather.at(now)
+    }
+}
+
+/// Moisture following a script, interpolated linearly between its points and
+/// unaffected by watering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    /// Time, in seconds, and moisture, sorted by time.
+    points: Vec<(u32, u8)>,
+}
+
+impl Script {
+    /// Parses a script made of `<hours> <moisture>` lines, in chronological
+    /// order. Empty lines and lines starting with `#` are skipped.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut points: Vec<(u32, u8)> = vec![];
+        for (n, line) in text.lines().enumerate().map(|(n, line)| (n + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("line {n}: expected `<hours> <moisture>`, got {line:?}");
+            let (hours, moisture) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let hours: f32 = hours.parse().map_err(|_| invalid())?;
+            let moisture: u8 = moisture.trim().parse().map_err(|_| invalid())?;
+            let at = (hours * 3600.0) as u32;
+            if points.last().is_some_and(|(last, _)| *last > at) || moisture > 100 {
+                return Err(invalid());
+            }
+            points.push((at, moisture));
+        }
+        if points.is_empty() {
+            return Err("empty script".to_owned());
+        }
+        Ok(Self { points })
+    }
+}
+
+impl Soil for Script {
+    fn moisture(&mut self, now: u32) -> u8 {
+        let upper = self.points.iter().position(|(at, _)| *at >= now);
+        match upper {
+            Some(0) => self.points[0].1,
+            None => self.points[self.points.len() - 1].1,
+            Some(upper) => {
+                let (t0, m0) = self.points[upper - 1];
+                let (t1, m1) = self.points[upper];
+                let ratio = (now - t0) as f32 / (t1 - t0) as f32;
+                (m0 as f32 + (m1 as f32 - m0 as f32) * ratio).round() as u8
+            }
+        }
+    }
+
+    fn water(&mut self, _now: u32, _pulse_ms: u32) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use humidity_core::simulation::{self, Weather};
+
+    #[test]
+    fn test_pot() {
+        let soil = simulation::Soil { evaporation: 0, drainage: 0, ..simulation::Soil::BONSAI };
+        let mut sut = Pot::new(Model::new(soil, Weather::INDOORS, 40));
+
+        assert_eq!(40, sut.moisture(0));
+        // 100 ml/min for 9 s pours 15 ml, a tenth of the pot.
+        sut.water(3600, 9_000);
+        assert_eq!(50, sut.moisture(3600));
+        assert_eq!(Weather::INDOORS.at(3600), sut.conditions(3600));
+    }
+
+    #[test]
+    fn test_script() {
+        let mut sut = Script::parse("# hours moisture\n0 60\n\n10 40\n12 80\n").unwrap();
+
+        assert_eq!(60, sut.moisture(0));
+        assert_eq!(50, sut.moisture(5 * 3600));
+        assert_eq!(60, sut.moisture(11 * 3600));
+        assert_eq!(80, sut.moisture(100 * 3600));
+        sut.water(100 * 3600, 1000);
+        assert_eq!(80, sut.moisture(100 * 3600));
+    }
+
+    #[test]
+    fn test_invalid_script() {
+        assert!(Script::parse("").is_err());
+        assert!(Script::parse("0 60\n1").is_err());
+        assert!(Script::parse("0 160").is_err());
+        assert!(Script::parse("2 60\n1 50").is_err());
+    }
+}
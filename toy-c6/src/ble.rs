@@ -1,6 +1,9 @@
 use embassy_futures::join::join;
-use embassy_futures::select::select;
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
 use embassy_time::Timer;
+use humidity_core::{sample::Summary, sensors::Hygrometer, serde, shared::protocol};
 use log::{info, warn};
 use trouble_host::prelude::*;
 
@@ -13,10 +16,23 @@ const L2CAP_CHANNELS_MAX: usize = 2; // Signal + att
 /// Size of L2CAP packets
 pub const L2CAP_MTU: usize = 255;
 
+/// Room for a serialized summary, zero padded.
+const SUMMARY_LEN: usize = 16;
+
+/// Latest sample, notified to the connected central once taken.
+pub static SAMPLED: Signal<CriticalSectionRawMutex, Summary<Hygrometer>> = Signal::new();
+
+// The UUIDs must be literals, checked against the layout of the humidity monitor.
+const _: () = {
+    assert!(protocol::HUMIDITY_SERVICE.uuid.matches("937312e0-2354-11eb-9f10-fbc30a62cf00"));
+    assert!(protocol::HUMIDITY.uuid.matches("987312e0-2354-11eb-9f10-fbc30a62cf50"));
+};
+
 // GATT Server definition
 #[gatt_server]
 struct Server {
     battery_service: BatteryService,
+    humidity_service: HumidityService,
 }
 
 /// Battery service
@@ -31,6 +47,14 @@ struct BatteryService {
     status: bool,
 }
 
+/// Humidity service, as served by the humidity monitor
+#[gatt_service(uuid = "937312e0-2354-11eb-9f10-fbc30a62cf00")]
+struct HumidityService {
+    /// Latest sample
+    #[characteristic(uuid = "987312e0-2354-11eb-9f10-fbc30a62cf50", read, notify)]
+    humidity: [u8; SUMMARY_LEN],
+}
+
 /// Run the BLE stack.
 pub async fn run<C>(controller: C)
 where
@@ -179,31 +203,42 @@ async fn advertise<'a, 'b, C: Controller>(
     Ok(conn)
 }
 
-/// Example task to use the BLE notifier interface.
-/// This task will notify the connected central of a counter value every 2 seconds.
-/// It will also read the RSSI value every 2 seconds.
-/// and will stop when the connection is closed by the central or an error occurs.
+/// Notifies the connected central of every new sample, and reads the RSSI
+/// (Received Signal Strength Indicator) of the connection every 2 seconds in
+/// between. Stops when the connection is closed by the central or an error
+/// occurs.
 async fn custom_task<C: Controller>(
     server: &Server<'_>,
     conn: &GattConnection<'_, '_>,
     stack: &Stack<'_, C>,
 ) {
-    let mut tick: u8 = 0;
-    let level = server.battery_service.level;
+    let humidity = server.humidity_service.humidity;
     loop {
-        tick = tick.wrapping_add(1);
-        info!("[custom_task] notifying connection of tick {}", tick);
-        if level.notify(conn, &tick).await.is_err() {
-            info!("[custom_task] error notifying connection");
-            break;
-        };
-        // read RSSI (Received Signal Strength Indicator) of the connection.
-        if let Ok(rssi) = conn.raw().rssi(stack).await {
-            info!("[custom_task] RSSI: {:?}", rssi);
-        } else {
-            info!("[custom_task] error getting RSSI");
-            break;
-        };
-        Timer::after_secs(2).await;
+        match select(SAMPLED.wait(), Timer::after_secs(2)).await {
+            Either::First(summary) => {
+                let mut value = [0; SUMMARY_LEN];
+                if let Err(e) = serde::serialize(&summary, &mut value) {
+                    warn!("[custom_task] cannot serialize {:?}: {:?}", summary, e);
+                    continue;
+                }
+                // Kept as the value too, for centrals reading it afterwards.
+                if let Err(e) = server.set(&humidity, &value) {
+                    warn!("[custom_task] cannot set humidity: {:?}", e);
+                }
+                info!("[custom_task] notifying connection of {:?}", summary);
+                if humidity.notify(conn, &value).await.is_err() {
+                    info!("[custom_task] error notifying connection");
+                    break;
+                };
+            }
+            Either::Second(()) => {
+                if let Ok(rssi) = conn.raw().rssi(stack).await {
+                    info!("[custom_task] RSSI: {:?}", rssi);
+                } else {
+                    info!("[custom_task] error getting RSSI");
+                    break;
+                };
+            }
+        }
     }
 }
@@ -3,11 +3,15 @@
 
 use bt_hci::controller::ExternalController;
 use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use embassy_time::Timer;
 use esp_backtrace as _;
+use esp_hal::analog::adc::{Adc, AdcConfig, Attenuation};
 use esp_hal::clock::CpuClock;
 use esp_hal::timer::timg::TimerGroup;
 use esp_println as _;
 use esp_wifi::ble::controller::BleConnector;
+use humidity_core::{sample, sensors::Hygrometer};
 
 use {esp_alloc as _, esp_backtrace as _};
 
@@ -19,6 +23,12 @@ const CONNECTIONS_MAX: usize = 1;
 /// Max number of L2CAP channels.
 const L2CAP_CHANNELS_MAX: usize = 3; // Signal + att + CoC
 
+/// Time between samples, in seconds.
+const SAMPLE_INTERVAL_S: u64 = 60;
+
+/// Readings averaged in a sample.
+const SAMPLES: u8 = 16;
+
 #[esp_hal_embassy::main]
 async fn main(_s: Spawner) {
     esp_println::logger::init_logger_from_env();
@@ -40,5 +50,30 @@ async fn main(_s: Spawner) {
     let connector = BleConnector::new(&init, bluetooth);
     let controller: ExternalController<_, 20> = ExternalController::new(connector);
 
-    ble::run(controller).await;
+    let mut adc1_config = AdcConfig::new();
+    let mut hygrometer = adc1_config.enable_pin(peripherals.GPIO2, Attenuation::_11dB);
+    let mut adc1 = Adc::new(peripherals.ADC1, adc1_config);
+
+    // Samples the soil, with the sensor always powered, and hands the summary
+    // over to the BLE stack to notify.
+    let sampling = async {
+        loop {
+            let summary = sample::perform_sampling(
+                SAMPLES,
+                &mut || {},
+                &mut || {},
+                &mut || loop {
+                    if let Ok(value) = adc1.read_oneshot(&mut hygrometer) {
+                        break value;
+                    }
+                },
+                Hygrometer::HW390,
+                None,
+            );
+            ble::SAMPLED.signal(summary);
+            Timer::after_secs(SAMPLE_INTERVAL_S).await;
+        }
+    };
+
+    join(ble::run(controller), sampling).await;
 }